@@ -0,0 +1,45 @@
+/*
+ * The C interface to SecureStore vaults, provided by the securestore crate when built with the
+ * `ffi` feature. See the documentation of the `ffi` module for the ownership rules.
+ */
+
+#ifndef SECURESTORE_H
+#define SECURESTORE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An open vault. */
+typedef struct securestore securestore;
+
+/* The reason for the last failure on the calling thread, or NULL. Owned by the library. */
+const char *securestore_last_error(void);
+
+/* Opens the vault at `path` with exactly one of `password` and `keyfile`; NULL on failure. */
+securestore *securestore_open(const char *path, const char *password, const char *keyfile);
+
+/* Decrypts the secret `name` into a buffer to be released with securestore_buffer_free(). */
+int securestore_get(const securestore *handle, const char *name, uint8_t **value,
+                    size_t *length);
+
+/* Sets the secret `name` to the `length` bytes at `value`, without saving the vault. */
+int securestore_set(securestore *handle, const char *name, const uint8_t *value, size_t length);
+
+/* Saves the changes made to the vault. */
+int securestore_save(const securestore *handle);
+
+/* Releases a vault, discarding unsaved changes. */
+void securestore_free(securestore *handle);
+
+/* Wipes and releases a buffer returned by securestore_get(). */
+void securestore_buffer_free(uint8_t *value, size_t length);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SECURESTORE_H */
@@ -0,0 +1,102 @@
+//! Async versions of the APIs which create, load and save vaults, for use with tokio.
+//!
+//! Vault files are read and written with `tokio::fs`. Obtaining the keys for a vault (running a
+//! KDF, or calling out to an agent, token or KMS) is blocking, so on a multi-threaded runtime it
+//! is run with [`tokio::task::block_in_place()`], which moves the other tasks on the current
+//! worker thread elsewhere in the meantime, rather than stalling them.
+//!
+//! As a [`KeySource`] may borrow callbacks and readers which are not `Send`, neither are the
+//! futures returned by [`SecretsManager::new_async()`] and [`SecretsManager::load_async()`]. To
+//! load a vault from a spawned task, read it with `tokio::fs::read()` and pass the contents to
+//! [`SecretsManager::from_bytes()`] instead.
+
+use crate::builder::SecretsManagerBuilder;
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::shared::Vault;
+use crate::storage::FileStorage;
+use crate::{KeySource, SecretsManager};
+use std::path::Path;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+impl SecretsManager {
+    /// Creates a new vault on-disk at path `path` without blocking the async runtime. See
+    /// [`SecretsManager::new()`].
+    pub async fn new_async<P: AsRef<Path>>(
+        path: P,
+        key_source: KeySource<'_>,
+    ) -> Result<Self, Error> {
+        Self::builder().create_async(path, key_source).await
+    }
+
+    /// Loads an existing vault on-disk at path `path` without blocking the async runtime. See
+    /// [`SecretsManager::load()`].
+    pub async fn load_async<P: AsRef<Path>>(
+        path: P,
+        key_source: KeySource<'_>,
+    ) -> Result<Self, Error> {
+        Self::builder().load_async(path, key_source).await
+    }
+}
+
+impl<C: CryptoProvider> SecretsManagerBuilder<'_, C> {
+    /// The async version of [`SecretsManagerBuilder::create()`].
+    pub async fn create_async<P: AsRef<Path>>(
+        self,
+        path: P,
+        key_source: KeySource<'_>,
+    ) -> Result<SecretsManager<C>, Error> {
+        blocking(|| self.create(path, key_source))
+    }
+
+    /// The async version of [`SecretsManagerBuilder::load()`].
+    pub async fn load_async<P: AsRef<Path>>(
+        self,
+        path: P,
+        key_source: KeySource<'_>,
+    ) -> Result<SecretsManager<C>, Error> {
+        let path = path.as_ref();
+        let result = async {
+            let contents = tokio::fs::read(path).await.map_err(Error::file(path))?;
+            let vault = Vault::parse(&contents)?;
+            let mut sman = blocking(|| self.unlock(vault, key_source))?;
+            sman.storage = Some(Box::new(FileStorage::new(path)));
+            Ok(sman)
+        };
+        self.loaded(result.await)
+    }
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Saves changes to the underlying vault without blocking the async runtime. Vaults stored
+    /// in files are written with `tokio::fs`, while other [`VaultStorage`] backends are called
+    /// as with [`save()`](Self::save). As with `save()`, unchanged vaults are not written.
+    ///
+    /// [`VaultStorage`]: crate::VaultStorage
+    pub async fn save_async(&self) -> Result<(), Error> {
+        let storage = self.storage()?;
+        if !self.vault.is_dirty() {
+            return Ok(());
+        }
+        let contents = self.to_bytes()?;
+
+        match storage.file_path() {
+            Some(path) => FileStorage::store_async(path, &contents).await?,
+            None => blocking(|| storage.store(&contents))?,
+        }
+        self.vault.mark_clean();
+        self.emit(Event::VaultSaved);
+        Ok(())
+    }
+}
+
+/// Runs the blocking operation `f`, on a multi-threaded runtime via `block_in_place()`.
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
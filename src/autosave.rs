@@ -0,0 +1,45 @@
+//! Saving vaults automatically when their `SecretsManager` is dropped.
+
+use crate::crypto::CryptoProvider;
+use crate::SecretsManager;
+
+/// What to do if a vault with autosave enabled (see [`SecretsManager::with_autosave()`]) fails
+/// to save when the `SecretsManager` is dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OnSaveFailure {
+    /// Print the error to stderr and carry on.
+    Log,
+    /// Panic with the error, unless the thread is already panicking (in which case the error
+    /// is printed to stderr instead, as a second panic would abort the process).
+    Panic,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Enables saving any pending changes when this `SecretsManager` is dropped, so that short
+    /// scripts cannot lose writes by forgetting to call [`save()`](Self::save). If the
+    /// vault fails to save, `on_failure` determines whether the error is logged or panics.
+    ///
+    /// Changes may still be saved explicitly, and nothing is written on drop if the vault has
+    /// not changed since it was last saved.
+    pub fn with_autosave(mut self, on_failure: OnSaveFailure) -> Self {
+        self.autosave = Some(on_failure);
+        self
+    }
+}
+
+impl<C: CryptoProvider> Drop for SecretsManager<C> {
+    fn drop(&mut self) {
+        let on_failure = match self.autosave {
+            Some(on_failure) if self.is_dirty() => on_failure,
+            _ => return,
+        };
+
+        if let Err(e) = self.save() {
+            let message = format!("securestore: failed to save the vault on drop: {}", e);
+            match on_failure {
+                OnSaveFailure::Panic if !std::thread::panicking() => panic!("{}", message),
+                _ => eprintln!("{}", message),
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Copying secrets to the clipboard for `get --clip`, without printing them.
+
+use arboard::Clipboard;
+use securestore::Error;
+use std::io;
+use std::sync::mpsc;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+/// Places `secret` on the clipboard until `timeout` elapses (or the process is interrupted),
+/// then restores the text previously on the clipboard, or clears it. The clipboard is left
+/// alone if something else was copied in the meantime.
+pub fn copy(name: &str, secret: &str, timeout: Duration) -> Result<(), Error> {
+    let mut clipboard = Clipboard::new().map_err(clipboard_error)?;
+    let previous = clipboard.get_text().ok().map(Zeroizing::new);
+    clipboard.set_text(secret).map_err(clipboard_error)?;
+    eprintln!(
+        "Copied {} to the clipboard. Clearing in {} seconds.",
+        name,
+        timeout.as_secs()
+    );
+
+    // On X11 and Wayland the clipboard is served by this process, so it must keep running
+    // until the secret is cleared anyway
+    let (interrupted, wait) = mpsc::channel();
+    let _ = ctrlc::set_handler(move || {
+        let _ = interrupted.send(());
+    });
+    let _ = wait.recv_timeout(timeout);
+
+    let current = clipboard.get_text().ok().map(Zeroizing::new);
+    if current.as_deref().map(String::as_str) == Some(secret) {
+        match previous {
+            Some(previous) => clipboard.set_text(previous.as_str()),
+            None => clipboard.clear(),
+        }
+        .map_err(clipboard_error)?;
+    }
+    Ok(())
+}
+
+fn clipboard_error(error: arboard::Error) -> Error {
+    Error::Io(io::Error::other(format!("clipboard: {}", error)))
+}
@@ -0,0 +1,81 @@
+//! Shell completion scripts, which complete the names of secrets by calling back into the CLI.
+
+use crate::{Args, Command};
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use securestore::{KeySource, SecretsManager};
+use std::ffi::OsString;
+use std::io;
+
+/// Stands in for the word being completed when parsing the words before it.
+const PLACEHOLDER: &str = "\u{1}complete";
+
+const BASH: &str = r#"
+_securestore_names() {
+    local names
+    names=$(securestore complete-names -- "${COMP_WORDS[@]:0:COMP_CWORD}" 2>/dev/null)
+    if [[ -n $names ]]; then
+        COMPREPLY=($(compgen -W "$names" -- "${COMP_WORDS[COMP_CWORD]}"))
+    else
+        _securestore "$@"
+    fi
+}
+complete -F _securestore_names -o bashdefault -o default securestore
+"#;
+
+const ZSH: &str = r#"
+_securestore_names() {
+    local -a names
+    names=(${(f)"$(securestore complete-names -- ${(Q)words[1,CURRENT-1]} 2>/dev/null)"})
+    if (( $#names )); then
+        compadd -a names
+    else
+        _securestore "$@"
+    fi
+}
+compdef _securestore_names securestore
+"#;
+
+const FISH: &str = r#"
+complete -c securestore -n "__fish_seen_subcommand_from get set delete totp" -f -a "(securestore complete-names -- (commandline -opc) 2>/dev/null)"
+"#;
+
+/// Writes the completion script for `shell` to stdout. Except in PowerShell and Elvish, the
+/// names of secrets are completed as well, provided the vault is unlocked with `--keyfile`.
+pub fn generate(shell: Shell) {
+    let mut stdout = io::stdout();
+    clap_complete::generate(shell, &mut Args::command(), "securestore", &mut stdout);
+    match shell {
+        Shell::Bash => print!("{}", BASH),
+        Shell::Zsh => print!("{}", ZSH),
+        Shell::Fish => print!("{}", FISH),
+        _ => {}
+    }
+}
+
+/// Prints the names of the secrets which may complete the command line `words`, i.e. if the
+/// next word is the name of the secret for `get`, `set`, `delete` or `totp`. Nothing is printed unless
+/// the vault is unlocked with `--keyfile`, as completion must never prompt for a password.
+pub fn names(words: &[OsString]) {
+    let mut words = words.to_vec();
+    words.push(PLACEHOLDER.into());
+    let args = match Args::try_parse_from(words) {
+        Ok(args) => args,
+        Err(_) => return,
+    };
+
+    let name = match &args.command {
+        Command::Get { name, .. }
+        | Command::Set { name, .. }
+        | Command::Delete { name }
+        | Command::Totp { name } => name,
+        _ => return,
+    };
+    if let (PLACEHOLDER, Some(keyfile)) = (name.as_str(), &args.keyfile) {
+        if let Ok(sman) = SecretsManager::load(&args.store, KeySource::File(keyfile)) {
+            for name in sman.keys() {
+                println!("{}", name);
+            }
+        }
+    }
+}
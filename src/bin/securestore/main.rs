@@ -0,0 +1,622 @@
+//! A command-line client for creating and manipulating SecureStore vaults.
+
+mod clip;
+mod completions;
+mod run;
+mod shell;
+
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use securestore::{generators, Error, KeySource, SecretsManager};
+use serde_json::json;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use zeroize::Zeroizing;
+
+#[derive(Parser)]
+#[command(
+    name = "securestore",
+    version,
+    about = "Create and manage SecureStore secrets vaults",
+    after_help = "Exits with status 0 on success, 2 for invalid arguments, 3 if a secret was not \
+                  found, 4 if the vault or a secret could not be decrypted or authenticated \
+                  (e.g. with the wrong password or keyfile) and 1 for any other failure."
+)]
+struct Args {
+    /// The path to the secrets vault
+    #[arg(short, long, global = true, default_value = "secrets.json")]
+    store: PathBuf,
+
+    /// Unlock the vault with the keyfile at this path instead of prompting for a password
+    #[arg(short, long, global = true)]
+    keyfile: Option<PathBuf>,
+
+    /// The format of the output of `list`, `get`, `totp`, `diff` and `verify`, and of errors
+    #[arg(short, long, global = true, value_enum, default_value = "text")]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new, empty vault. If `--keyfile` is specified and does not exist, new keys
+    /// are generated and exported to that path.
+    Create {
+        /// Additionally export the (possibly password-derived) keys to this path
+        #[arg(long)]
+        export_key: Option<PathBuf>,
+        /// Overwrite the vault if it already exists, losing the secrets stored in it
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Decrypt and print the secret with the specified name
+    Get {
+        name: String,
+        /// Copy the secret to the clipboard instead of printing it, clearing it again after
+        /// `--timeout` seconds
+        #[arg(short, long)]
+        clip: bool,
+        /// How many seconds to leave the secret on the clipboard
+        #[arg(long, default_value = "30", requires = "clip")]
+        timeout: u64,
+    },
+    /// Add or replace the secret with the specified name
+    Set {
+        name: String,
+        /// The value of the secret. If omitted or `-`, the value is read from stdin (or prompted
+        /// for without echoing on a terminal), keeping it out of shell history and `ps` output.
+        value: Option<String>,
+        /// Set a binary secret, with the value either read from stdin verbatim (`raw`) or
+        /// decoded from base64 (`base64`)
+        #[arg(long, value_enum, value_name = "ENCODING")]
+        binary: Option<Binary>,
+    },
+    /// Generate a random password and store it as the secret with the specified name, without
+    /// printing it
+    Generate {
+        name: String,
+        /// The number of characters in the password
+        #[arg(long, default_value = "32")]
+        length: usize,
+        /// The characters the password is drawn from
+        #[arg(long, value_enum, default_value = "alnum-symbols")]
+        charset: Charset,
+    },
+    /// Print the current one-time password generated from the TOTP seed (an `otpauth://totp/`
+    /// URI) stored as the secret with the specified name
+    Totp { name: String },
+    /// Remove the secret with the specified name
+    Delete { name: String },
+    /// List the names of all secrets in the vault
+    List,
+    /// Export the keys used to unlock the vault to a keyfile
+    ExportKey {
+        path: PathBuf,
+        /// Export the keys as base64-armored text instead of binary
+        #[arg(long)]
+        armor: bool,
+    },
+    /// Export all secrets, decrypted, to a plaintext file
+    Export {
+        path: PathBuf,
+        /// The format of the exported file
+        #[arg(long, value_enum, default_value = "env")]
+        format: Format,
+        /// Uppercase the names of the secrets (env format only)
+        #[arg(long)]
+        uppercase: bool,
+        /// Replace each `/` in the names of the secrets with this string, e.g. `_` (env format
+        /// only)
+        #[arg(long)]
+        separator: Option<String>,
+        #[command(flatten)]
+        columns: CsvColumns,
+    },
+    /// Import secrets from a plaintext file, replacing any existing secrets of the same names
+    Import {
+        path: PathBuf,
+        /// The format of the imported file
+        #[arg(long, value_enum, default_value = "env")]
+        format: Format,
+        #[command(flatten)]
+        columns: CsvColumns,
+    },
+    /// List the secrets added, removed or changed in another copy of the vault, which must be
+    /// unlocked with the same keys
+    Diff { other: PathBuf },
+    /// Check that the vault is authentic and that every secret can be decrypted
+    Verify,
+    /// Merge two divergent copies of a vault, as a git merge driver. The secrets changed in
+    /// `theirs` since `base` are merged into `ours`, which is overwritten with the result; the
+    /// command fails without changing `ours` if a secret was changed differently in each. All
+    /// three vaults must be unlocked with the same keys. To use it, register the driver with
+    /// `git config merge.securestore.driver "securestore merge %O %A %B"` (adding `--keyfile`
+    /// as needed) and add `secrets.json merge=securestore` to `.gitattributes`.
+    Merge {
+        /// The common ancestor of both vaults
+        base: PathBuf,
+        /// The vault being merged into
+        ours: PathBuf,
+        /// The vault being merged from
+        theirs: PathBuf,
+    },
+    /// Run a command with secrets set as environment variables, named by uppercasing the names
+    /// of the secrets and replacing each `/` or other punctuation with `_`, e.g.
+    /// `securestore run -- ./server --port 8080`. Exits with the exit code of the command.
+    Run {
+        /// Only inject this secret (may be repeated); by default all secrets are injected
+        #[arg(long, value_name = "NAME")]
+        only: Vec<String>,
+        /// Prefix the names of the environment variables with this string, e.g. `APP_`, which
+        /// is converted to a variable name in the same way
+        #[arg(long, default_value = "")]
+        prefix: String,
+        /// Write each secret to a private temporary file and set the variable to its path
+        /// instead, e.g. for TLS keys; the files are wiped and removed once the command exits
+        #[arg(long)]
+        files: bool,
+        /// The command to run, and its arguments
+        #[arg(last = true, required = true)]
+        command: Vec<OsString>,
+    },
+    /// Unlock the vault once and edit it interactively, with `get`, `set`, `del`, `ls` and
+    /// `search` commands and tab completion of secret names. Changes are saved as they are
+    /// made.
+    Shell {
+        /// The path to the secrets vault, instead of `--store`
+        path: Option<PathBuf>,
+    },
+    /// Print the completion script for a shell, e.g. `source <(securestore completions bash)`.
+    /// In bash, zsh and fish the names of secrets are completed too if `--keyfile` is given.
+    Completions { shell: clap_complete::Shell },
+    /// Print the names of the secrets completing a command line, for the completion scripts
+    #[command(hide = true)]
+    CompleteNames {
+        #[arg(last = true)]
+        words: Vec<OsString>,
+    },
+}
+
+/// The format of the output of commands
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    /// Plain text, one item per line
+    Text,
+    /// A JSON value, for `jq` and other automation
+    Json,
+}
+
+/// A plaintext format secrets are imported from or exported to
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// `NAME=value` lines, as read by docker-compose and dotenv libraries
+    Env,
+    /// A JSON object mapping names to values
+    Json,
+    /// A header row followed by a row per secret
+    Csv,
+}
+
+/// The characters a generated password is drawn from
+#[derive(Clone, Copy, ValueEnum)]
+enum Charset {
+    /// `0-9`
+    Digits,
+    /// `0-9` and `a-f`
+    Hex,
+    /// `A-Z`, `a-z` and `0-9`
+    Alnum,
+    /// Letters, digits and punctuation other than quotes, backslashes and backticks
+    AlnumSymbols,
+}
+
+impl From<Charset> for generators::Charset {
+    fn from(charset: Charset) -> Self {
+        match charset {
+            Charset::Digits => generators::Charset::Digits,
+            Charset::Hex => generators::Charset::Hex,
+            Charset::Alnum => generators::Charset::Alphanumeric,
+            Charset::AlnumSymbols => generators::Charset::AlphanumericSymbols,
+        }
+    }
+}
+
+/// The encoding of the value of a binary secret
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Binary {
+    /// The bytes of the value, unchanged
+    Raw,
+    /// Base64-encoded bytes
+    Base64,
+}
+
+#[derive(ClapArgs)]
+struct CsvColumns {
+    /// The CSV column holding the names of the secrets
+    #[arg(long, default_value = "name")]
+    key_column: String,
+    /// The CSV column holding the values of the secrets
+    #[arg(long, default_value = "value")]
+    value_column: String,
+}
+
+fn main() {
+    let args = Args::parse();
+    let output = args.output;
+
+    if let Err(e) = run(args) {
+        match output {
+            Output::Text => eprintln!("securestore: {}", e),
+            Output::Json => {
+                let error =
+                    json!({ "error": { "kind": error_kind(&e), "message": e.to_string() } });
+                println!("{}", error);
+            }
+        }
+        exit(exit_code(&e));
+    }
+}
+
+/// The exit code for `error`, distinguishing failures which automation may handle differently.
+fn exit_code(error: &Error) -> i32 {
+    match error_kind(error) {
+        "not_found" => 3,
+        "decryption_failed" => 4,
+        _ => 1,
+    }
+}
+
+/// The kind of `error` reported in JSON output.
+fn error_kind(error: &Error) -> &'static str {
+    match error {
+        Error::SecretNotFound(_) => "not_found",
+        Error::InvalidKey
+        | Error::DecryptionFailure
+        | Error::MacMismatch
+        | Error::VaultTampered => "decryption_failed",
+        _ => "error",
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let store = args.store.as_path();
+
+    match args.command {
+        Command::Create { export_key, force } => {
+            if store.exists() && !force {
+                return Err(Error::File {
+                    path: store.to_path_buf(),
+                    source: io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        "the vault already exists; pass --force to overwrite it",
+                    ),
+                });
+            }
+
+            let sman = match &args.keyfile {
+                Some(keyfile) if !keyfile.exists() => {
+                    let sman = SecretsManager::new(store, KeySource::Generate)?;
+                    sman.export_keyfile(keyfile)?;
+                    sman
+                }
+                Some(keyfile) => SecretsManager::new(store, KeySource::File(keyfile))?,
+                None => {
+                    let password = prompt_password(true)?;
+                    SecretsManager::new(store, KeySource::Password(&password))?
+                }
+            };
+
+            sman.save()?;
+            if let Some(path) = export_key {
+                sman.export_keyfile(path)?;
+            }
+        }
+        Command::Get {
+            name,
+            clip,
+            timeout,
+        } => {
+            let sman = open(store, args.keyfile.as_deref())?;
+            let secret = sman.retrieve_secret(&name)?;
+            if clip {
+                let timeout = std::time::Duration::from_secs(timeout);
+                return clip::copy(&name, secret.expose_secret(), timeout);
+            }
+            match args.output {
+                Output::Text => println!("{}", secret.expose_secret()),
+                Output::Json => {
+                    println!(
+                        "{}",
+                        json!({ "name": name, "value": secret.expose_secret() })
+                    )
+                }
+            }
+        }
+        Command::Set {
+            name,
+            value,
+            binary,
+        } => {
+            let mut sman = open(store, args.keyfile.as_deref())?;
+            let value = read_value(value, binary)?;
+            sman.set(&name, &value[..]);
+            sman.save()?;
+        }
+        Command::Generate {
+            name,
+            length,
+            charset,
+        } => {
+            let mut sman = open(store, args.keyfile.as_deref())?;
+            let password = generators::password(length, charset.into());
+            sman.set(&name, password.expose_secret().as_str());
+            sman.save()?;
+        }
+        Command::Totp { name } => {
+            let sman = open(store, args.keyfile.as_deref())?;
+            let code = sman.totp_code(&name, std::time::SystemTime::now())?;
+            match args.output {
+                Output::Text => println!("{}", code),
+                Output::Json => println!("{}", json!({ "name": name, "code": code })),
+            }
+        }
+        Command::Delete { name } => {
+            let mut sman = open(store, args.keyfile.as_deref())?;
+            sman.remove(&name)?;
+            sman.save()?;
+        }
+        Command::List => {
+            let sman = open(store, args.keyfile.as_deref())?;
+            match args.output {
+                Output::Text => sman.keys().for_each(|name| println!("{}", name)),
+                Output::Json => println!("{}", json!(sman.keys().collect::<Vec<_>>())),
+            }
+        }
+        Command::ExportKey { path, armor } => {
+            let sman = open(store, args.keyfile.as_deref())?;
+            if armor {
+                sman.export_keyfile_armored(path)?;
+            } else {
+                sman.export_keyfile(path)?;
+            }
+        }
+        Command::Export {
+            path,
+            format,
+            uppercase,
+            separator,
+            columns,
+        } => {
+            let sman = open(store, args.keyfile.as_deref())?;
+            let transform = |name: &str| {
+                let name = match &separator {
+                    Some(separator) => name.replace('/', separator),
+                    None => name.to_string(),
+                };
+                match uppercase {
+                    true => name.to_uppercase(),
+                    false => name,
+                }
+            };
+            match format {
+                Format::Env => sman.export_dotenv(path, transform)?,
+                Format::Json => sman.export_plaintext(BufWriter::new(create_private(path)?))?,
+                Format::Csv => sman.export_csv(
+                    create_private(path)?,
+                    &columns.key_column,
+                    &columns.value_column,
+                )?,
+            }
+        }
+        Command::Import {
+            path,
+            format,
+            columns,
+        } => {
+            let mut sman = open(store, args.keyfile.as_deref())?;
+            match format {
+                Format::Env => sman.import_dotenv(path)?,
+                Format::Json => {
+                    let file = File::open(&path).map_err(|source| Error::File { path, source })?;
+                    sman.import_plaintext(BufReader::new(file))?
+                }
+                Format::Csv => {
+                    let file = File::open(&path).map_err(|source| Error::File { path, source })?;
+                    sman.import_csv(file, &columns.key_column, &columns.value_column)?
+                }
+            }
+            sman.save()?;
+        }
+        Command::Diff { other } => {
+            let load = opener(args.keyfile.as_deref())?;
+            let diff = load(store)?.diff(&load(&other)?)?;
+            match args.output {
+                Output::Text => {
+                    let changes = [("+", diff.added), ("-", diff.removed), ("~", diff.changed)];
+                    for (marker, names) in changes {
+                        names
+                            .iter()
+                            .for_each(|name| println!("{} {}", marker, name));
+                    }
+                }
+                Output::Json => {
+                    let diff = json!({
+                        "added": diff.added,
+                        "removed": diff.removed,
+                        "changed": diff.changed,
+                    });
+                    println!("{}", diff);
+                }
+            }
+        }
+        Command::Verify => {
+            // The vault itself is authenticated when it is loaded
+            let sman = open(store, args.keyfile.as_deref())?;
+            let failures: Vec<(&str, Error)> = sman
+                .keys()
+                .filter_map(|name| sman.retrieve_bytes(name).err().map(|e| (name, e)))
+                .collect();
+            match args.output {
+                Output::Text if failures.is_empty() => println!("verified {} secrets", sman.len()),
+                Output::Text => {
+                    for (name, e) in &failures {
+                        eprintln!("securestore: {}: {}", name, e);
+                    }
+                }
+                Output::Json => {
+                    let failures: Vec<_> = failures
+                        .iter()
+                        .map(|(name, e)| {
+                            json!({ "name": name, "kind": error_kind(e), "message": e.to_string() })
+                        })
+                        .collect();
+                    let report = json!({
+                        "ok": failures.is_empty(),
+                        "secrets": sman.len(),
+                        "failures": failures,
+                    });
+                    println!("{}", report);
+                }
+            }
+            if let Some((_, e)) = failures.first() {
+                exit(exit_code(e));
+            }
+        }
+        Command::Merge { base, ours, theirs } => {
+            let load = opener(args.keyfile.as_deref())?;
+            let mut sman = load(&ours)?;
+            sman.merge_three_way(&load(&base)?, &load(&theirs)?)?;
+            sman.save()?;
+        }
+        Command::Run {
+            only,
+            prefix,
+            files,
+            command,
+        } => {
+            let sman = open(store, args.keyfile.as_deref())?;
+            let code = run::run(&sman, &only, &prefix, files, &command)?;
+            drop(sman);
+            exit(code);
+        }
+        Command::Shell { path } => {
+            let store = path.as_deref().unwrap_or(store);
+            let mut sman = open(store, args.keyfile.as_deref())?;
+            shell::shell(&mut sman)?;
+        }
+        Command::Completions { shell } => completions::generate(shell),
+        Command::CompleteNames { words } => completions::names(&words),
+    }
+
+    Ok(())
+}
+
+/// Loads an existing vault, unlocking it with `keyfile` if specified or else with a password
+/// read interactively from the terminal.
+fn open(store: &Path, keyfile: Option<&Path>) -> Result<SecretsManager, Error> {
+    match keyfile {
+        Some(keyfile) => SecretsManager::load(store, KeySource::File(keyfile)),
+        None => SecretsManager::load(
+            store,
+            KeySource::Prompt(Box::new(|| prompt_password(false))),
+        ),
+    }
+}
+
+/// Creates the file at `path` to export decrypted secrets to, replacing any existing file,
+/// readable and writable only by its owner.
+fn create_private(path: PathBuf) -> Result<File, Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = match options.open(&path) {
+        Ok(file) => file,
+        Err(source) => return Err(Error::File { path, source }),
+    };
+    // An existing file keeps its permissions when opened
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(source) = file.set_permissions(std::fs::Permissions::from_mode(0o600)) {
+            return Err(Error::File { path, source });
+        }
+    }
+    Ok(file)
+}
+
+/// Returns the value of a secret given as `value` on the command line, or else read from stdin
+/// or prompted for, decoded according to `binary`.
+fn read_value(value: Option<String>, binary: Option<Binary>) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let input = match value {
+        Some(value) if value != "-" => Zeroizing::new(value.into_bytes()),
+        _ if binary != Some(Binary::Raw) && io::stdin().is_terminal() => {
+            let value = rpassword::prompt_password("Value: ").map_err(Error::Io)?;
+            Zeroizing::new(value.into_bytes())
+        }
+        _ => {
+            let mut input = Zeroizing::new(Vec::new());
+            io::stdin().read_to_end(&mut input).map_err(Error::Io)?;
+            // Text piped in (e.g. from `echo`) usually ends with a newline that isn't part of
+            // the secret
+            if binary != Some(Binary::Raw) {
+                if input.ends_with(b"\n") {
+                    input.pop();
+                }
+                if input.ends_with(b"\r") {
+                    input.pop();
+                }
+            }
+            input
+        }
+    };
+
+    match binary {
+        Some(Binary::Raw) => Ok(input),
+        Some(Binary::Base64) => match base64::decode(&input[..]) {
+            Ok(decoded) => Ok(Zeroizing::new(decoded)),
+            Err(_) => Err(Error::InvalidValue("not valid base64".to_string())),
+        },
+        None if std::str::from_utf8(&input).is_err() => Err(Error::InvalidValue(
+            "not valid UTF-8; set it with --binary raw".to_string(),
+        )),
+        None => Ok(input),
+    }
+}
+
+/// Returns a function loading vaults with `keyfile` if specified, or else with a password read
+/// interactively from the terminal only once, rather than for each vault.
+fn opener(
+    keyfile: Option<&Path>,
+) -> Result<impl Fn(&Path) -> Result<SecretsManager, Error> + '_, Error> {
+    let password = match keyfile {
+        Some(_) => None,
+        None => Some(prompt_password(false)?),
+    };
+    Ok(move |path: &Path| match keyfile {
+        Some(keyfile) => SecretsManager::load(path, KeySource::File(keyfile)),
+        None => SecretsManager::load(
+            path,
+            KeySource::Password(password.as_deref().unwrap_or_default()),
+        ),
+    })
+}
+
+fn prompt_password(confirm: bool) -> Result<String, Error> {
+    let password = rpassword::prompt_password("Password: ").map_err(Error::Io)?;
+
+    if confirm {
+        let confirmation = rpassword::prompt_password("Confirm password: ").map_err(Error::Io)?;
+        if password != confirmation {
+            return Err(Error::InvalidValue(
+                "the passwords do not match".to_string(),
+            ));
+        }
+    }
+
+    Ok(password)
+}
@@ -0,0 +1,129 @@
+//! The `run` subcommand, which runs a command with secrets injected into its environment.
+
+use securestore::{env_var_name, Error, SecretsManager};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zeroize::Zeroizing;
+
+/// Runs `command` with the secrets `only` (or all secrets if empty) set as the environment
+/// variables named by [`env_var_name()`] applied to `prefix` followed by the name of the secret,
+/// returning the exit code of the command. With `files`, each secret is instead written to a
+/// private temporary file, and the variable set to its path; the files are wiped and removed
+/// once the command exits. Otherwise, returns [`Error::InvalidValue`] if a secret is not valid
+/// UTF-8.
+pub fn run(
+    sman: &SecretsManager,
+    only: &[String],
+    prefix: &str,
+    files: bool,
+    command: &[OsString],
+) -> Result<i32, Error> {
+    let names: Vec<&str> = match only.is_empty() {
+        true => sman.keys().collect(),
+        false => only.iter().map(String::as_str).collect(),
+    };
+
+    let mut secrets = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for name in names {
+        // The variable names the scratch file too, so the prefix mustn't escape the directory
+        let variable = env_var_name(&format!("{}{}", prefix, name));
+        let value = Zeroizing::new(sman.retrieve_bytes(name)?);
+        if secrets.insert(variable.clone(), value).is_some() {
+            collisions.push(variable);
+        }
+    }
+    if !collisions.is_empty() {
+        return Err(Error::EnvVarCollision(collisions));
+    }
+
+    let mut child = Command::new(&command[0]);
+    child.args(&command[1..]);
+    let scratch = match files {
+        true => Some(ScratchDir::create()?),
+        false => None,
+    };
+    for (variable, value) in &secrets {
+        match &scratch {
+            Some(scratch) => child.env(variable, scratch.write(variable, value)?),
+            None => match std::str::from_utf8(value) {
+                Ok(value) => child.env(variable, value),
+                Err(_) => {
+                    return Err(Error::InvalidValue(format!(
+                        "{} is not valid UTF-8; pass binary secrets with --files",
+                        variable
+                    )))
+                }
+            },
+        };
+    }
+
+    // The terminal delivers Ctrl-C to the child as well, which decides whether to exit; this
+    // process must outlive it to remove the files
+    let _ = ctrlc::set_handler(|| {});
+    let status = child.status().map_err(|source| Error::File {
+        path: PathBuf::from(&command[0]),
+        source,
+    })?;
+    drop(scratch);
+    Ok(status.code().unwrap_or(1))
+}
+
+/// A private temporary directory of files holding secrets, which are overwritten with zeros
+/// and removed when it is dropped.
+struct ScratchDir {
+    path: PathBuf,
+}
+
+impl ScratchDir {
+    fn create() -> Result<Self, Error> {
+        let path = std::env::temp_dir().join(format!("securestore-run-{}", std::process::id()));
+        let mut builder = fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        match builder.create(&path) {
+            Ok(()) => Ok(ScratchDir { path }),
+            Err(source) => Err(Error::File { path, source }),
+        }
+    }
+
+    /// Writes `value` to the file `name` in the directory, returning its path.
+    fn write(&self, name: &str, value: &[u8]) -> Result<PathBuf, Error> {
+        let path = self.path.join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options
+            .open(&path)
+            .and_then(|mut file| file.write_all(value))
+        {
+            Ok(()) => Ok(path),
+            Err(source) => Err(Error::File { path, source }),
+        }
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Ok(entries) = fs::read_dir(&self.path) {
+            for entry in entries.flatten() {
+                wipe(&entry.path());
+            }
+        }
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Overwrites the file at `path` with zeros, in case it is recovered after being removed.
+fn wipe(path: &Path) {
+    if let Ok(mut file) = OpenOptions::new().write(true).open(path) {
+        let length = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let _ = file.write_all(&vec![0; length as usize]);
+        let _ = file.sync_all();
+    }
+}
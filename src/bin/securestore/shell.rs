@@ -0,0 +1,133 @@
+//! The `shell` subcommand, an interactive prompt for editing a vault which is unlocked once.
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Context, Editor, Helper, Highlighter, Hinter, Validator};
+use securestore::{Error, SecretsManager};
+use std::io;
+
+const COMMANDS: &[&str] = &["del", "exit", "get", "help", "ls", "search", "set"];
+
+const HELP: &str = "\
+get NAME            print the secret NAME
+set NAME [VALUE]    set the secret NAME, prompting for the value if omitted
+del NAME            remove the secret NAME
+ls [PREFIX]         list the names of all secrets, or those in the namespace PREFIX
+search PATTERN      list the names of secrets matching the glob PATTERN
+exit                leave the shell (as does Ctrl-D)";
+
+/// Completes the commands of the shell and the names of secrets.
+#[derive(Helper, Hinter, Highlighter, Validator)]
+struct ShellHelper {
+    names: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        let start = line.rfind(' ').map(|i| i + 1).unwrap_or(0);
+        let word = &line[start..];
+        let candidates: Vec<&str> = match line[..start].split_whitespace().count() {
+            0 => COMMANDS.to_vec(),
+            1 => self.names.iter().map(String::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let matches = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(str::to_string)
+            .collect();
+        Ok((start, matches))
+    }
+}
+
+/// Runs the shell until it is exited, saving the vault after every change.
+pub fn shell(sman: &mut SecretsManager) -> Result<(), Error> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new().map_err(readline_error)?;
+    loop {
+        let names = sman.keys().map(str::to_string).collect();
+        editor.set_helper(Some(ShellHelper { names }));
+
+        let line = match editor.readline("securestore> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(readline_error(e)),
+        };
+        let (command, rest) = split_word(&line);
+        let (name, value) = split_word(rest);
+        // Lines holding a secret are kept out of the history
+        if command != "set" || value.is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        let result = match (command, name) {
+            ("", _) => Ok(()),
+            ("exit" | "quit", _) => return Ok(()),
+            ("help", _) => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            ("get", name) if !name.is_empty() => sman
+                .retrieve_secret(name)
+                .map(|secret| println!("{}", secret.expose_secret())),
+            ("set", name) if !name.is_empty() => {
+                let value = match value {
+                    "" => rpassword::prompt_password("Value: ").map_err(Error::Io),
+                    value => Ok(value.to_string()),
+                };
+                value.and_then(|value| {
+                    sman.set(name, value);
+                    sman.save()
+                })
+            }
+            ("del", name) if !name.is_empty() => sman.remove(name).and_then(|_| sman.save()),
+            ("ls", prefix) => {
+                let names: Vec<&str> = match prefix {
+                    "" => sman.keys().collect(),
+                    prefix => sman.keys_under(prefix).collect(),
+                };
+                names.iter().for_each(|name| println!("{}", name));
+                Ok(())
+            }
+            ("search", pattern) if !pattern.is_empty() => sman
+                .find(pattern)
+                .map(|names| names.iter().for_each(|name| println!("{}", name))),
+            ("get" | "set" | "del" | "search", _) => {
+                eprintln!("{}: missing argument; type `help` for usage", command);
+                Ok(())
+            }
+            _ => {
+                eprintln!("unknown command {}; type `help` for usage", command);
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("securestore: {}", e);
+        }
+    }
+}
+
+/// Splits the first whitespace-separated word from `s`, returning it and the remainder.
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], s[i..].trim()),
+        None => (s, ""),
+    }
+}
+
+fn readline_error(error: ReadlineError) -> Error {
+    match error {
+        ReadlineError::Io(e) => Error::Io(e),
+        e => Error::Io(io::Error::other(e.to_string())),
+    }
+}
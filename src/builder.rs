@@ -0,0 +1,408 @@
+//! Construction of [`SecretsManager`] instances with non-default options.
+
+use crate::autosave::OnSaveFailure;
+use crate::crypto::{CryptoProvider, DefaultProvider};
+use crate::errors::Error;
+use crate::events::{self, Event, EventHook};
+use crate::format::VaultFormat;
+use crate::kdf::Kdf;
+use crate::lock::VaultLock;
+use crate::password::PasswordPolicy;
+use crate::shared::{Scheme, Vault};
+use crate::storage::{FileStorage, VaultStorage};
+use crate::{KeySource, ReadOnlySecretsManager, SecretsManager};
+use std::fmt;
+use std::io::Read;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A builder for creating or loading vaults with non-default options, obtained via
+/// [`SecretsManager::builder()`].
+///
+/// Options may be combined freely, with the vault then created or loaded by one of the
+/// `create*()` or `load*()` methods, or by [`open()`](Self::open) once the location of the
+/// vault and the key source have been set with the builder itself:
+///
+/// ```no_run
+/// # use securestore::{KeySource, SecretsManager};
+/// let sman = SecretsManager::builder()
+///     .path("secrets.json")
+///     .key_source(KeySource::Password("hunter2"))
+///     .readonly(true)
+///     .open()?;
+/// # Ok::<(), securestore::Error>(())
+/// ```
+pub struct SecretsManagerBuilder<'a, C: CryptoProvider = DefaultProvider> {
+    kdf: Kdf,
+    password_policy: Option<PasswordPolicy>,
+    scheme: Scheme,
+    format: VaultFormat,
+    path: Option<PathBuf>,
+    storage: Option<Box<dyn VaultStorage>>,
+    key_source: Option<KeySource<'a>>,
+    readonly: bool,
+    locked: bool,
+    autosave: Option<OnSaveFailure>,
+    compression_threshold: Option<usize>,
+    hash_traced_names: bool,
+    hooks: Vec<EventHook>,
+    allow_unauthenticated: bool,
+    provider: PhantomData<C>,
+}
+
+impl<'a> SecretsManagerBuilder<'a> {
+    pub(crate) fn new() -> Self {
+        SecretsManagerBuilder {
+            kdf: Default::default(),
+            password_policy: None,
+            scheme: Default::default(),
+            format: Default::default(),
+            path: None,
+            storage: None,
+            key_source: None,
+            readonly: false,
+            locked: false,
+            autosave: None,
+            compression_threshold: None,
+            hash_traced_names: false,
+            hooks: Vec::new(),
+            allow_unauthenticated: false,
+            provider: PhantomData,
+        }
+    }
+}
+
+impl<C: CryptoProvider> fmt::Debug for SecretsManagerBuilder<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Neither key sources, storage backends nor hooks are `Debug`, and key sources may hold
+        // secrets
+        f.debug_struct("SecretsManagerBuilder")
+            .field("kdf", &self.kdf)
+            .field("password_policy", &self.password_policy)
+            .field("scheme", &self.scheme)
+            .field("format", &self.format)
+            .field("path", &self.path)
+            .field("readonly", &self.readonly)
+            .field("locked", &self.locked)
+            .field("autosave", &self.autosave)
+            .field("compression_threshold", &self.compression_threshold)
+            .field("hash_traced_names", &self.hash_traced_names)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, C: CryptoProvider> SecretsManagerBuilder<'a, C> {
+    /// Sets the [`CryptoProvider`] used for all cryptographic operations performed by the
+    /// `SecretsManager`. Defaults to [`DefaultProvider`].
+    pub fn provider<P: CryptoProvider>(self) -> SecretsManagerBuilder<'a, P> {
+        SecretsManagerBuilder {
+            kdf: self.kdf,
+            password_policy: self.password_policy,
+            scheme: self.scheme,
+            format: self.format,
+            path: self.path,
+            storage: self.storage,
+            key_source: self.key_source,
+            readonly: self.readonly,
+            locked: self.locked,
+            autosave: self.autosave,
+            compression_threshold: self.compression_threshold,
+            hash_traced_names: self.hash_traced_names,
+            hooks: self.hooks,
+            allow_unauthenticated: self.allow_unauthenticated,
+            provider: PhantomData,
+        }
+    }
+
+    /// Sets the path of the vault on-disk to be loaded by [`open()`](Self::open).
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the [`VaultStorage`] holding the vault to be loaded by [`open()`](Self::open),
+    /// instead of a path on-disk.
+    pub fn storage<S: VaultStorage + 'static>(mut self, storage: S) -> Self {
+        self.storage = Some(Box::new(storage));
+        self
+    }
+
+    /// Sets the key source used to unlock the vault loaded by [`open()`](Self::open).
+    pub fn key_source(mut self, key_source: KeySource<'a>) -> Self {
+        self.key_source = Some(key_source);
+        self
+    }
+
+    /// Opens the vault for reading only, so that attempting to save it returns
+    /// [`Error::ReadOnlyVault`]. Defaults to `false`. Secrets may still be changed in memory;
+    /// use [`load_readonly()`](Self::load_readonly) for a `SecretsManager` which cannot be
+    /// modified at all.
+    pub fn readonly(mut self, readonly: bool) -> Self {
+        self.readonly = readonly;
+        self
+    }
+
+    /// Takes an exclusive lock on the vault when loading it from a path on-disk, blocking
+    /// until the lock is available, as with [`SecretsManager::load_locked()`]. Defaults to
+    /// `false`.
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Saves pending changes when the `SecretsManager` is dropped, as with
+    /// [`SecretsManager::with_autosave()`]. Defaults to `None`, i.e. changes must be saved
+    /// explicitly.
+    pub fn autosave(mut self, on_failure: Option<OnSaveFailure>) -> Self {
+        self.autosave = on_failure;
+        self
+    }
+
+    /// Compresses secrets larger than `threshold` bytes before they are encrypted, as with
+    /// [`SecretsManager::set_compression_threshold()`]. Defaults to `None`, i.e. secrets are
+    /// never compressed.
+    #[cfg(feature = "zstd")]
+    pub fn compression_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Records secret names in traces as keyed hashes, as with
+    /// [`SecretsManager::set_hash_traced_names()`]. Defaults to `false`.
+    #[cfg(feature = "tracing")]
+    pub fn hash_traced_names(mut self, hash: bool) -> Self {
+        self.hash_traced_names = hash;
+        self
+    }
+
+    /// Accepts vaults which record neither a sentinel nor a MAC when they are loaded, such as
+    /// those written before this crate authenticated vaults. Such vaults cannot be checked for
+    /// secrets that were removed, added or rolled back, so this should only be enabled to
+    /// migrate them; they are authenticated from the first time they are saved. Defaults to
+    /// `false`, i.e. loading them returns [`Error::VaultTampered`].
+    pub fn allow_unauthenticated(mut self, allow: bool) -> Self {
+        self.allow_unauthenticated = allow;
+        self
+    }
+
+    /// Registers `hook` to be called with each access to the vault, as with
+    /// [`SecretsManager::on_event()`]. Hooks registered with the builder are also called with
+    /// [`Event::UnlockFailed`] if the vault cannot be loaded.
+    pub fn on_event<F: Fn(&Event) + Send + Sync + 'static>(mut self, hook: F) -> Self {
+        self.hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Loads the existing vault at the path (or in the storage) set with
+    /// [`path()`](Self::path) (or [`storage()`](Self::storage)), unlocking it with the key
+    /// source set with [`key_source()`](Self::key_source). Returns
+    /// [`Error::IncompleteBuilder`] if either has not been set, or
+    /// [`Error::ConflictingBuilderOptions`] if both a path and a storage have been set.
+    pub fn open(mut self) -> Result<SecretsManager<C>, Error> {
+        let key_source = self
+            .key_source
+            .take()
+            .ok_or(Error::IncompleteBuilder("key_source"))?;
+        match (self.path.take(), self.storage.take()) {
+            (Some(_), Some(_)) => Err(Error::ConflictingBuilderOptions("path", "storage")),
+            (Some(path), None) => self.load(path, key_source),
+            (None, Some(storage)) if self.locked => {
+                drop(storage);
+                Err(Error::StorageFailure(
+                    "only vaults stored in files can be locked".to_string(),
+                ))
+            }
+            (None, Some(storage)) => self.loaded(SecretsManager::load_from(
+                storage,
+                key_source,
+                self.allow_unauthenticated,
+            )),
+            (None, None) => Err(Error::IncompleteBuilder("path")),
+        }
+    }
+
+    /// Applies the options which are not specific to how the vault is created or loaded.
+    fn configure(&self, mut sman: SecretsManager<C>) -> SecretsManager<C> {
+        sman.readonly = self.readonly;
+        sman.autosave = self.autosave;
+        sman.compression_threshold = self.compression_threshold;
+        sman.hash_traced_names = self.hash_traced_names;
+        sman.hooks = self.hooks.clone();
+        sman.allow_unauthenticated = self.allow_unauthenticated;
+        sman
+    }
+
+    /// Unlocks the loaded `vault`, accepting it without a sentinel or MAC only if
+    /// [`allow_unauthenticated()`](Self::allow_unauthenticated) is set.
+    pub(crate) fn unlock(
+        &self,
+        vault: Vault,
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        SecretsManager::unlock(vault, key_source, self.allow_unauthenticated)
+    }
+
+    /// Configures the vault loaded with `result`, or reports why it could not be loaded.
+    pub(crate) fn loaded(
+        &self,
+        result: Result<SecretsManager<C>, Error>,
+    ) -> Result<SecretsManager<C>, Error> {
+        match result {
+            Ok(sman) => Ok(self.configure(sman)),
+            Err(error) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %error, "vault could not be loaded");
+                events::emit(&self.hooks, Event::UnlockFailed { error: &error });
+                Err(error)
+            }
+        }
+    }
+
+    /// Sets the KDF used to derive keys when the new vault is protected by
+    /// [`KeySource::Password`]. The KDF is recorded in the vault and used whenever the vault
+    /// is subsequently loaded. Defaults to [`Kdf::default()`].
+    pub fn kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Requires the password protecting the new vault, if it is protected by a password, to meet
+    /// `policy`, returning [`Error::WeakPassword`] from `create*()` otherwise. Passwords used to
+    /// load existing vaults are never checked. Defaults to `None`, i.e. any password is accepted.
+    pub fn password_policy(mut self, policy: Option<PasswordPolicy>) -> Self {
+        self.password_policy = policy;
+        self
+    }
+
+    /// Sets the scheme used to encrypt the secrets in the new vault. The scheme is recorded in
+    /// the vault and used whenever the vault is subsequently loaded. Defaults to
+    /// [`Scheme::default()`], which is interoperable with other SecureStore implementations.
+    pub fn scheme(mut self, scheme: Scheme) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    /// Sets the format the new vault is serialized in. Loaded vaults are always saved in the
+    /// format they were loaded in, regardless of this option. Defaults to
+    /// [`VaultFormat::Json`], which is interoperable with other SecureStore implementations.
+    pub fn format(mut self, format: VaultFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Creates a new vault on-disk at path `path` and loads it in a new instance of
+    /// `SecretsManager`.
+    pub fn create<P: AsRef<Path>>(
+        self,
+        path: P,
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        self.create_with_storage(FileStorage::new(path), key_source)
+    }
+
+    /// Creates a new vault saved to `storage` and loads it in a new instance of
+    /// `SecretsManager`. Nothing is written to `storage` until the vault is first saved.
+    pub fn create_with_storage<S: VaultStorage + 'static>(
+        self,
+        storage: S,
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        let mut sman = self.create_in_memory(key_source)?;
+        sman.storage = Some(Box::new(storage));
+        Ok(sman)
+    }
+
+    /// Creates a new vault held only in memory and loads it in a new instance of
+    /// `SecretsManager`. See [`SecretsManager::new_in_memory()`].
+    pub fn create_in_memory(self, key_source: KeySource) -> Result<SecretsManager<C>, Error> {
+        let mut vault = Vault::new::<C>();
+        vault.scheme = Some(self.scheme);
+        vault.format = self.format;
+        let keys = key_source.protect::<C>(&mut vault, &self.kdf, self.password_policy.as_ref())?;
+        vault.seal_sentinel::<C>(&keys);
+        let sman = SecretsManager {
+            keys,
+            storage: None,
+            vault,
+            lock: None,
+            readonly: false,
+            autosave: None,
+            compression_threshold: None,
+            hash_traced_names: false,
+            hooks: Vec::new(),
+            allow_unauthenticated: false,
+            provider: PhantomData,
+        };
+        Ok(self.configure(sman))
+    }
+
+    /// Creates a new instance of `SecretsManager` referencing an existing vault located
+    /// on-disk. The KDF, scheme and format of the vault are always used, regardless of the
+    /// options set on this builder.
+    pub fn load<P: AsRef<Path>>(
+        self,
+        path: P,
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        let path = path.as_ref();
+        let lock = match self.locked {
+            true => Some(VaultLock::acquire(path)?),
+            false => None,
+        };
+        self.loaded(SecretsManager::load_with_lock(
+            path,
+            key_source,
+            lock,
+            self.allow_unauthenticated,
+        ))
+    }
+
+    /// Loads an existing vault on-disk for reading only. See
+    /// [`SecretsManager::load_readonly()`].
+    pub fn load_readonly<P: AsRef<Path>>(
+        self,
+        path: P,
+        key_source: KeySource,
+    ) -> Result<ReadOnlySecretsManager<C>, Error> {
+        self.load(path, key_source)
+            .map(ReadOnlySecretsManager::from)
+    }
+
+    /// Creates a new instance of `SecretsManager` referencing an existing vault held in
+    /// `storage`, to which changes are subsequently saved. As with [`load()`](Self::load), the
+    /// KDF, scheme and format of the vault are always used.
+    pub fn load_with_storage<S: VaultStorage + 'static>(
+        self,
+        storage: S,
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        self.loaded(SecretsManager::load_from(
+            Box::new(storage),
+            key_source,
+            self.allow_unauthenticated,
+        ))
+    }
+
+    /// Creates a new instance of `SecretsManager` from a serialized vault held only in memory.
+    /// See [`SecretsManager::from_bytes()`].
+    pub fn load_from_bytes(
+        self,
+        bytes: &[u8],
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        let vault = Vault::load(bytes);
+        self.loaded(vault.and_then(|vault| self.unlock(vault, key_source)))
+    }
+
+    /// Creates a new instance of `SecretsManager` from a serialized vault read from `reader`.
+    /// See [`SecretsManager::load_from_reader()`].
+    pub fn load_from_reader<R: Read>(
+        self,
+        reader: R,
+        key_source: KeySource,
+    ) -> Result<SecretsManager<C>, Error> {
+        let vault = Vault::load(reader);
+        self.loaded(vault.and_then(|vault| self.unlock(vault, key_source)))
+    }
+}
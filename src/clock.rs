@@ -0,0 +1,26 @@
+//! The current time, as recorded in the metadata of secrets.
+//!
+//! `SystemTime::now()` panics on wasm32-unknown-unknown, so the clock of the JS host is used
+//! there instead.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub(crate) fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn now() -> SystemTime {
+    use std::time::Duration;
+
+    UNIX_EPOCH + Duration::from_millis(js_sys::Date::now() as u64)
+}
+
+/// The whole seconds from the Unix epoch to `time`, as timestamps are recorded in the vault.
+/// Times before the epoch are recorded as the epoch itself.
+pub(crate) fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,59 @@
+//! Compression of large secrets before they are encrypted.
+//!
+//! Secrets are only compressed if they are larger than the configured threshold and
+//! compressing them actually makes them smaller. The algorithm is recorded alongside each
+//! compressed secret, so secrets are decompressed transparently when retrieved.
+
+use crate::errors::Error;
+use serde_derive::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// The algorithm a secret was compressed with before it was encrypted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Zstd,
+}
+
+/// Compresses `plaintext` if it is larger than `threshold`, returning `None` if it should be
+/// stored uncompressed.
+#[cfg(feature = "zstd")]
+pub(crate) fn compress(
+    plaintext: &[u8],
+    threshold: Option<usize>,
+) -> Option<(Compression, Zeroizing<Vec<u8>>)> {
+    match threshold {
+        Some(threshold) if plaintext.len() > threshold => {
+            let compressed = Zeroizing::new(zstd::bulk::compress(plaintext, 0).ok()?);
+            match compressed.len() < plaintext.len() {
+                true => Some((Compression::Zstd, compressed)),
+                false => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn compress(
+    _plaintext: &[u8],
+    _threshold: Option<usize>,
+) -> Option<(Compression, Zeroizing<Vec<u8>>)> {
+    None
+}
+
+/// Decompresses the decrypted `plaintext` of a secret compressed with `compression`.
+pub(crate) fn decompress(compression: Compression, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    match compression {
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => zstd::stream::decode_all(plaintext)
+            .map_err(|e| Error::CompressionFailure(format!("zstd: {}", e))),
+        #[cfg(not(feature = "zstd"))]
+        Compression::Zstd => {
+            let _ = plaintext;
+            Err(Error::CompressionFailure(
+                "the secret is compressed with zstd, but zstd support is not enabled".to_string(),
+            ))
+        }
+    }
+}
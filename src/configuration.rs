@@ -0,0 +1,77 @@
+//! A source for the `config` crate, layering decrypted secrets into application configuration.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::{KeySource, SecretsManager};
+use config::{ConfigError, Map, Source, Value, ValueKind};
+use std::path::Path;
+
+/// A [`config::Source`] providing the secrets of a vault as configuration properties, e.g.
+///
+/// ```no_run
+/// # use securestore::{KeySource, SecureStoreSource};
+/// # use std::path::Path;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let keyfile = Path::new("secrets.key");
+/// let settings = config::Config::builder()
+///     .add_source(config::File::with_name("settings"))
+///     .add_source(SecureStoreSource::new("secrets.json", KeySource::File(keyfile))?)
+///     .build()?;
+/// let password: String = settings.get("db.password")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The `/` separating the components of a secret name (see
+/// [`SecretsManager::scoped()`]) is replaced with `.`, so the secret `db/password` becomes the
+/// property `db.password` and secrets in the same namespace are grouped into a table.
+///
+/// All secrets are decrypted when the source is created, as the key source cannot be kept to
+/// unlock the vault again later. Like any other configuration property, the values are then held
+/// in ordinary strings and are not wiped from memory.
+#[derive(Clone, Debug)]
+pub struct SecureStoreSource {
+    properties: Map<String, String>,
+    origin: Option<String>,
+}
+
+impl SecureStoreSource {
+    /// Loads the vault at `path`, unlocking it with `key_source`, and decrypts its secrets.
+    /// Returns [`Error::SecretExpired`] if any secret has expired.
+    pub fn new<P: AsRef<Path>>(path: P, key_source: KeySource) -> Result<Self, Error> {
+        let sman = SecretsManager::load(path.as_ref(), key_source)?;
+        let mut source = Self::from_manager(&sman)?;
+        source.origin = Some(path.as_ref().display().to_string());
+        Ok(source)
+    }
+
+    /// Decrypts the secrets of the already unlocked vault `sman`, as with
+    /// [`SecretsManager::iter()`]. Returns [`Error::SecretExpired`] if any secret has expired.
+    pub fn from_manager<C: CryptoProvider>(sman: &SecretsManager<C>) -> Result<Self, Error> {
+        let mut properties = Map::new();
+        for (name, value) in sman.iter() {
+            properties.insert(name.replace('/', "."), value?);
+        }
+        Ok(SecureStoreSource {
+            properties,
+            origin: None,
+        })
+    }
+}
+
+impl Source for SecureStoreSource {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        Ok(self
+            .properties
+            .iter()
+            .map(|(name, value)| {
+                let value = Value::new(self.origin.as_ref(), ValueKind::String(value.clone()));
+                (name.clone(), value)
+            })
+            .collect())
+    }
+}
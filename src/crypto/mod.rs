@@ -0,0 +1,107 @@
+//! The cryptographic primitives SecureStore is built on, abstracted over the library providing
+//! them. OpenSSL is used by default; enabling the `rust-crypto` feature swaps in pure-Rust
+//! implementations from the RustCrypto project instead. Both produce identical output, so the
+//! choice of provider has no bearing on the on-disk format, and other libraries (ring,
+//! BoringSSL, FIPS-validated modules) may be plugged in by implementing [`CryptoProvider`].
+//!
+//! On wasm32-unknown-unknown, the `wasm` feature selects the pure-Rust provider and draws random
+//! bytes from the `crypto.getRandomValues()` API of the JS host. Hosts without that API may
+//! supply their own RNG with a provider whose [`random()`](CryptoProvider::random) uses it,
+//! delegating every other operation to [`RustCrypto`].
+
+use crate::errors::Error;
+use crate::kdf::Digest;
+use crate::shared::{
+    HMAC_SHA256_SIZE, HMAC_SIZE, IV_SIZE, KEY_COUNT, KEY_LENGTH, NONCE_SIZE, TAG_SIZE,
+};
+
+#[cfg(feature = "openssl")]
+mod openssl;
+#[cfg(feature = "rust-crypto")]
+mod rust_crypto;
+
+#[cfg(feature = "openssl")]
+pub use self::openssl::OpenSsl;
+#[cfg(feature = "rust-crypto")]
+pub use self::rust_crypto::RustCrypto;
+
+/// The [`CryptoProvider`] used unless another is specified: [`RustCrypto`] if the `rust-crypto`
+/// feature is enabled, and [`OpenSsl`] otherwise.
+#[cfg(all(feature = "openssl", not(feature = "rust-crypto")))]
+pub type DefaultProvider = OpenSsl;
+/// The [`CryptoProvider`] used unless another is specified: [`RustCrypto`] if the `rust-crypto`
+/// feature is enabled, and [`OpenSsl`] otherwise.
+#[cfg(feature = "rust-crypto")]
+pub type DefaultProvider = RustCrypto;
+
+#[cfg(not(any(feature = "openssl", feature = "rust-crypto")))]
+compile_error!("One of the `openssl` or `rust-crypto` features must be enabled");
+
+/// The AEAD ciphers supported for use as a vault's encryption scheme.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Aead {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+/// A key for an AEAD cipher, spanning the full key material.
+pub type AeadKey = [u8; KEY_COUNT * KEY_LENGTH];
+
+/// The operations a cryptographic library must provide to back a [`SecretsManager`].
+///
+/// Implementations must be interoperable with the standard algorithms named by each method, as
+/// vaults are expected to be readable regardless of the provider that created them. Failures
+/// other than those caused by untrusted input (bad ciphertext) are not expected, so
+/// implementations may panic on them.
+///
+/// [`SecretsManager`]: crate::SecretsManager
+pub trait CryptoProvider {
+    /// Fills `buffer` with bytes from a cryptographically secure RNG.
+    fn random(buffer: &mut [u8]);
+
+    /// Derives `output.len()` bytes from `password` with PBKDF2-HMAC.
+    fn pbkdf2(digest: Digest, password: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]);
+
+    /// Computes the HMAC-SHA1 of the concatenation of `data`.
+    fn hmac_sha1(key: &[u8; KEY_LENGTH], data: &[&[u8]]) -> [u8; HMAC_SIZE];
+
+    /// Computes the HMAC-SHA256 of the concatenation of `data`.
+    fn hmac_sha256(key: &[u8; KEY_LENGTH], data: &[&[u8]]) -> [u8; HMAC_SHA256_SIZE];
+
+    /// Computes the HMAC of `data` with the specified digest and a key of any length, as used by
+    /// one-time passwords.
+    fn hmac(digest: Digest, key: &[u8], data: &[u8]) -> Vec<u8>;
+
+    /// Encrypts `plaintext` with AES-128-CBC and PKCS#7 padding.
+    fn aes_128_cbc_encrypt(key: &[u8; KEY_LENGTH], iv: &[u8; IV_SIZE], plaintext: &[u8])
+        -> Vec<u8>;
+
+    /// Decrypts and unpads `ciphertext` with AES-128-CBC.
+    fn aes_128_cbc_decrypt(
+        key: &[u8; KEY_LENGTH],
+        iv: &[u8; IV_SIZE],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+
+    /// Encrypts `plaintext` with the specified AEAD cipher, returning the ciphertext and writing
+    /// the authentication tag to `tag`.
+    fn aead_encrypt(
+        aead: Aead,
+        key: &AeadKey,
+        nonce: &[u8; NONCE_SIZE],
+        plaintext: &[u8],
+        tag: &mut [u8; TAG_SIZE],
+    ) -> Vec<u8>;
+
+    /// Authenticates and decrypts `ciphertext` with the specified AEAD cipher.
+    fn aead_decrypt(
+        aead: Aead,
+        key: &AeadKey,
+        nonce: &[u8; NONCE_SIZE],
+        ciphertext: &[u8],
+        tag: &[u8; TAG_SIZE],
+    ) -> Result<Vec<u8>, Error>;
+
+    /// Compares two byte strings in constant time (with respect to their contents).
+    fn ct_eq(a: &[u8], b: &[u8]) -> bool;
+}
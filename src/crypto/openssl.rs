@@ -0,0 +1,135 @@
+//! The default cryptographic backend, built on OpenSSL.
+
+use super::{Aead, AeadKey, CryptoProvider};
+use crate::errors::Error;
+use crate::kdf::Digest;
+use crate::shared::{HMAC_SHA256_SIZE, HMAC_SIZE, IV_SIZE, KEY_LENGTH, NONCE_SIZE, TAG_SIZE};
+use ::openssl::hash::MessageDigest;
+use ::openssl::pkey::PKey;
+use ::openssl::sign::Signer;
+use ::openssl::symm::{self, Cipher};
+
+/// The [`CryptoProvider`] implemented with OpenSSL.
+#[derive(Debug)]
+pub struct OpenSsl;
+
+impl OpenSsl {
+    fn message_digest(digest: Digest) -> MessageDigest {
+        match digest {
+            Digest::Sha1 => MessageDigest::sha1(),
+            Digest::Sha256 => MessageDigest::sha256(),
+        }
+    }
+
+    fn aead_cipher(aead: Aead) -> Cipher {
+        match aead {
+            Aead::Aes256Gcm => Cipher::aes_256_gcm(),
+            Aead::ChaCha20Poly1305 => Cipher::chacha20_poly1305(),
+        }
+    }
+}
+
+impl CryptoProvider for OpenSsl {
+    fn random(buffer: &mut [u8]) {
+        ::openssl::rand::rand_bytes(buffer).expect("Error reading bytes from RNG!");
+    }
+
+    fn pbkdf2(digest: Digest, password: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]) {
+        let digest = Self::message_digest(digest);
+        ::openssl::pkcs5::pbkdf2_hmac(password, salt, rounds as usize, digest, output)
+            .expect("PBKDF2 key generation failed!");
+    }
+
+    fn hmac_sha1(key: &[u8; KEY_LENGTH], data: &[&[u8]]) -> [u8; HMAC_SIZE] {
+        let mut hmac = [0u8; HMAC_SIZE];
+        hmac_into(MessageDigest::sha1(), key, data, &mut hmac);
+        hmac
+    }
+
+    fn hmac_sha256(key: &[u8; KEY_LENGTH], data: &[&[u8]]) -> [u8; HMAC_SHA256_SIZE] {
+        let mut hmac = [0u8; HMAC_SHA256_SIZE];
+        hmac_into(MessageDigest::sha256(), key, data, &mut hmac);
+        hmac
+    }
+
+    fn hmac(digest: Digest, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let digest = Self::message_digest(digest);
+        let mut hmac = vec![0u8; digest.size()];
+        hmac_into(digest, key, &[data], &mut hmac);
+        hmac
+    }
+
+    fn aes_128_cbc_encrypt(
+        key: &[u8; KEY_LENGTH],
+        iv: &[u8; IV_SIZE],
+        plaintext: &[u8],
+    ) -> Vec<u8> {
+        // Unlike with decryption, we don't expect this to ever fail
+        symm::encrypt(Cipher::aes_128_cbc(), key, Some(iv), plaintext)
+            .expect("Error encrypting payload!")
+    }
+
+    fn aes_128_cbc_decrypt(
+        key: &[u8; KEY_LENGTH],
+        iv: &[u8; IV_SIZE],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        symm::decrypt(Cipher::aes_128_cbc(), key, Some(iv), ciphertext)
+            .map_err(|_| Error::DecryptionFailure)
+    }
+
+    fn aead_encrypt(
+        aead: Aead,
+        key: &AeadKey,
+        nonce: &[u8; NONCE_SIZE],
+        plaintext: &[u8],
+        tag: &mut [u8; TAG_SIZE],
+    ) -> Vec<u8> {
+        symm::encrypt_aead(
+            Self::aead_cipher(aead),
+            key,
+            Some(nonce),
+            &[],
+            plaintext,
+            tag,
+        )
+        .expect("Error encrypting payload!")
+    }
+
+    fn aead_decrypt(
+        aead: Aead,
+        key: &AeadKey,
+        nonce: &[u8; NONCE_SIZE],
+        ciphertext: &[u8],
+        tag: &[u8; TAG_SIZE],
+    ) -> Result<Vec<u8>, Error> {
+        symm::decrypt_aead(
+            Self::aead_cipher(aead),
+            key,
+            Some(nonce),
+            &[],
+            ciphertext,
+            tag,
+        )
+        .map_err(|_| Error::DecryptionFailure)
+    }
+
+    fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && ::openssl::memcmp::eq(a, b)
+    }
+}
+
+/// Computes the HMAC of the concatenation of `data` with `digest`, writing it to `output`.
+fn hmac_into(digest: MessageDigest, key: &[u8], data: &[&[u8]], output: &mut [u8]) {
+    let key = PKey::hmac(key).expect("Failed to load HMAC encryption key!");
+    let mut signer = Signer::new(digest, &key).expect("Failed to create HMAC signer!");
+
+    for part in data {
+        signer.update(part).unwrap();
+    }
+
+    signer
+        .sign(output)
+        // this is not the same as the HMAC not matching
+        .expect("Failed to create HMAC signature!");
+}
@@ -0,0 +1,142 @@
+//! A pure-Rust cryptographic backend built on the RustCrypto crates, enabled by the
+//! `rust-crypto` feature for targets where linking against OpenSSL is impractical.
+
+use super::{Aead, AeadKey, CryptoProvider};
+use crate::errors::Error;
+use crate::kdf::Digest;
+use crate::shared::{HMAC_SHA256_SIZE, HMAC_SIZE, IV_SIZE, KEY_LENGTH, NONCE_SIZE, TAG_SIZE};
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::Aes256Gcm;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+/// The [`CryptoProvider`] implemented with the pure-Rust RustCrypto crates.
+#[derive(Debug)]
+pub struct RustCrypto;
+
+impl CryptoProvider for RustCrypto {
+    fn random(buffer: &mut [u8]) {
+        getrandom::getrandom(buffer).expect("Error reading bytes from RNG!");
+    }
+
+    fn pbkdf2(digest: Digest, password: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]) {
+        match digest {
+            Digest::Sha1 => pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, rounds, output),
+            Digest::Sha256 => pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, rounds, output),
+        }
+    }
+
+    fn hmac_sha1(key: &[u8; KEY_LENGTH], data: &[&[u8]]) -> [u8; HMAC_SIZE] {
+        let mut mac =
+            <Hmac<Sha1> as Mac>::new_from_slice(key).expect("Failed to load HMAC encryption key!");
+
+        for part in data {
+            mac.update(part);
+        }
+
+        mac.finalize().into_bytes().into()
+    }
+
+    fn hmac_sha256(key: &[u8; KEY_LENGTH], data: &[&[u8]]) -> [u8; HMAC_SHA256_SIZE] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+            .expect("Failed to load HMAC encryption key!");
+
+        for part in data {
+            mac.update(part);
+        }
+
+        mac.finalize().into_bytes().into()
+    }
+
+    fn hmac(digest: Digest, key: &[u8], data: &[u8]) -> Vec<u8> {
+        fn hmac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+            let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+
+        match digest {
+            Digest::Sha1 => hmac::<Hmac<Sha1>>(key, data),
+            Digest::Sha256 => hmac::<Hmac<Sha256>>(key, data),
+        }
+    }
+
+    fn aes_128_cbc_encrypt(
+        key: &[u8; KEY_LENGTH],
+        iv: &[u8; IV_SIZE],
+        plaintext: &[u8],
+    ) -> Vec<u8> {
+        cbc::Encryptor::<aes::Aes128>::new(key.into(), iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext)
+    }
+
+    fn aes_128_cbc_decrypt(
+        key: &[u8; KEY_LENGTH],
+        iv: &[u8; IV_SIZE],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        cbc::Decryptor::<aes::Aes128>::new(key.into(), iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .map_err(|_| Error::DecryptionFailure)
+    }
+
+    fn aead_encrypt(
+        aead: Aead,
+        key: &AeadKey,
+        nonce: &[u8; NONCE_SIZE],
+        plaintext: &[u8],
+        tag: &mut [u8; TAG_SIZE],
+    ) -> Vec<u8> {
+        let mut buffer = plaintext.to_vec();
+        let computed = match aead {
+            Aead::Aes256Gcm => {
+                Aes256Gcm::new(key.into()).encrypt_in_place_detached(nonce.into(), &[], &mut buffer)
+            }
+            Aead::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).encrypt_in_place_detached(
+                nonce.into(),
+                &[],
+                &mut buffer,
+            ),
+        }
+        .expect("Error encrypting payload!");
+
+        tag.copy_from_slice(&computed);
+        buffer
+    }
+
+    fn aead_decrypt(
+        aead: Aead,
+        key: &AeadKey,
+        nonce: &[u8; NONCE_SIZE],
+        ciphertext: &[u8],
+        tag: &[u8; TAG_SIZE],
+    ) -> Result<Vec<u8>, Error> {
+        let mut buffer = ciphertext.to_vec();
+        match aead {
+            Aead::Aes256Gcm => Aes256Gcm::new(key.into()).decrypt_in_place_detached(
+                nonce.into(),
+                &[],
+                &mut buffer,
+                tag.into(),
+            ),
+            Aead::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).decrypt_in_place_detached(
+                nonce.into(),
+                &[],
+                &mut buffer,
+                tag.into(),
+            ),
+        }
+        .map_err(|_| Error::DecryptionFailure)?;
+
+        Ok(buffer)
+    }
+
+    fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && bool::from(a.ct_eq(b))
+    }
+}
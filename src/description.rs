@@ -0,0 +1,122 @@
+//! Free-text descriptions of secrets, explaining what each secret is for.
+//!
+//! Descriptions are covered by the vault MAC, and are either stored in the clear, so that
+//! changes to them can be reviewed in diffs of the vault, or encrypted like the secrets
+//! themselves for descriptions which would reveal too much.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::serial::{BinaryDeserializable, BinarySerializable};
+use crate::shared::{EncryptedBlob, Keys, Scheme};
+use crate::{Plaintext, SecretsManager};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// The description of a secret, as stored in the vault.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Description {
+    Plain(String),
+    Encrypted(EncryptedBlob),
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Adds or replaces the secret `name`, as with [`set()`](Self::set), and describes it with
+    /// `description`, which is stored unencrypted.
+    pub fn set_with_description<T: BinarySerializable>(
+        &mut self,
+        name: &str,
+        value: T,
+        description: &str,
+    ) {
+        self.set(name, value);
+        self.vault.descriptions.insert(
+            name.to_string(),
+            Description::Plain(description.to_string()),
+        );
+    }
+
+    /// Describes the existing secret `name` with `description`, which is stored unencrypted,
+    /// or removes its description if `None`. The description is kept when the value of the
+    /// secret is changed. If the secret cannot be found, returns [`Error::SecretNotFound`].
+    pub fn set_description(&mut self, name: &str, description: Option<&str>) -> Result<(), Error> {
+        let description = description.map(|d| Description::Plain(d.to_string()));
+        self.replace_description(name, description)
+    }
+
+    /// Describes the existing secret `name` with `description`, which is encrypted with the
+    /// keys of the vault. If the secret cannot be found, returns [`Error::SecretNotFound`].
+    pub fn set_encrypted_description(
+        &mut self,
+        name: &str,
+        description: &str,
+    ) -> Result<(), Error> {
+        let blob =
+            EncryptedBlob::encrypt::<C>(&self.keys, self.vault.scheme(), description.as_bytes());
+        self.replace_description(name, Some(Description::Encrypted(blob)))
+    }
+
+    /// The description of the secret `name`, decrypted if it was stored encrypted, or `None` if
+    /// the secret has no description (or does not exist).
+    pub fn description(&self, name: &str) -> Result<Option<String>, Error> {
+        match self.vault.descriptions.get(name) {
+            None => Ok(None),
+            Some(Description::Plain(description)) => Ok(Some(description.clone())),
+            Some(Description::Encrypted(blob)) => {
+                let decrypted = blob.decrypt::<C>(&self.keys, self.vault.scheme())?;
+                String::deserialize(decrypted).map(Some)
+            }
+        }
+    }
+
+    fn replace_description(
+        &mut self,
+        name: &str,
+        description: Option<Description>,
+    ) -> Result<(), Error> {
+        if !self.vault.secrets.contains_key(name) {
+            return Err(Error::SecretNotFound(name.to_string()));
+        }
+        match description {
+            Some(description) => self
+                .vault
+                .descriptions
+                .insert(name.to_string(), description),
+            None => self.vault.descriptions.remove(name),
+        };
+        self.vault.mark_dirty();
+        Ok(())
+    }
+
+    /// Decrypts all encrypted descriptions with the current keys, so that they can be
+    /// re-encrypted with [`Description::seal_all()`].
+    pub(crate) fn decrypt_descriptions(&self) -> Result<Vec<(String, Plaintext)>, Error> {
+        let scheme = self.vault.scheme();
+        let mut decrypted = Vec::new();
+        for (name, description) in &self.vault.descriptions {
+            if let Description::Encrypted(blob) = description {
+                let description = Zeroizing::new(blob.decrypt::<C>(&self.keys, scheme)?);
+                decrypted.push((name.clone(), description));
+            }
+        }
+        Ok(decrypted)
+    }
+}
+
+impl Description {
+    /// Encrypts the descriptions returned by
+    /// [`SecretsManager::decrypt_descriptions()`] with `keys` and `scheme`, replacing those in
+    /// `descriptions`.
+    pub(crate) fn seal_all<C: CryptoProvider>(
+        descriptions: &mut BTreeMap<String, Description>,
+        keys: &Keys,
+        scheme: Scheme,
+        decrypted: Vec<(String, Plaintext)>,
+    ) {
+        for (name, description) in decrypted {
+            let blob = EncryptedBlob::encrypt::<C>(keys, scheme, &description);
+            descriptions.insert(name, Description::Encrypted(blob));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Comparison of the secrets held in two vaults.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::SecretsManager;
+use zeroize::Zeroizing;
+
+/// The names of the secrets which differ between two vaults, as returned by
+/// [`SecretsManager::diff()`]. Each list is in sorted order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VaultDiff {
+    /// Secrets only present in the other vault.
+    pub added: Vec<String>,
+    /// Secrets only present in this vault.
+    pub removed: Vec<String>,
+    /// Secrets present in both vaults, but with different values.
+    pub changed: Vec<String>,
+}
+
+impl VaultDiff {
+    /// Returns `true` if both vaults hold the same secrets with the same values.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Compares the secrets in this vault with those in `other`, e.g. the same vault as checked
+    /// out on another branch, and returns which secrets were added, removed or changed in
+    /// `other`. Only the names of the secrets are returned.
+    ///
+    /// Secrets stored identically in both vaults (with the same IV, MAC and ciphertext) are
+    /// unchanged. Otherwise both values are decrypted and compared, so a secret that was merely
+    /// re-encrypted (e.g. by rotating the keys of `other`) is not reported as changed, and each
+    /// vault reports the secrets it decrypted to its hooks. Returns an error if a secret cannot
+    /// be decrypted.
+    pub fn diff(&self, other: &SecretsManager<C>) -> Result<VaultDiff, Error> {
+        let ours = &self.vault.secrets;
+        let theirs = &other.vault.secrets;
+        let mut diff = VaultDiff {
+            added: theirs
+                .keys()
+                .filter(|name| !ours.contains_key(*name))
+                .cloned()
+                .collect(),
+            removed: ours
+                .keys()
+                .filter(|name| !theirs.contains_key(*name))
+                .cloned()
+                .collect(),
+            changed: Vec::new(),
+        };
+
+        let (our_scheme, their_scheme) = (self.vault.scheme(), other.vault.scheme());
+        for (name, blob) in ours {
+            let other_blob = match theirs.get(name) {
+                Some(other_blob) if other_blob != blob => other_blob,
+                _ => continue,
+            };
+            let value = Zeroizing::new(blob.decrypt::<C>(&self.keys, our_scheme)?);
+            let other_value = Zeroizing::new(other_blob.decrypt::<C>(&other.keys, their_scheme)?);
+            self.emit(Event::SecretRead { name });
+            other.emit(Event::SecretRead { name });
+            if value != other_value {
+                diff.changed.push(name.clone());
+            }
+        }
+
+        Ok(diff)
+    }
+}
@@ -0,0 +1,87 @@
+//! Export of secrets to the environment of the current process, for applications which only
+//! read their configuration from environment variables.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+/// How [`SecretsManager::export_to_env()`] resolves a secret mapping to an environment variable
+/// which is already set, or to the same variable as another secret.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnvCollision {
+    /// Replace the existing value, with later secrets (in sorted order) replacing earlier ones.
+    Overwrite,
+    /// Keep the existing value, or the value of the first secret (in sorted order).
+    Keep,
+    /// Set no variables at all, failing with [`Error::EnvVarCollision`].
+    Fail,
+}
+
+/// Converts the secret name `name` to a conventional environment variable name, upper-casing it
+/// and replacing every character other than an ASCII letter or digit with `_`, so that
+/// `db/password` becomes `DB_PASSWORD`. Suitable as the `transform` of
+/// [`SecretsManager::export_to_env()`].
+pub fn env_var_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Sets an environment variable of the current process for every secret in the store,
+    /// named `prefix` followed by `transform` applied to the name of the secret (e.g.
+    /// [`env_var_name()`]), and returns the names of the variables set. Variables which are
+    /// already set, or which more than one secret maps to, are resolved according to
+    /// `collision`.
+    ///
+    /// All secrets are decrypted and checked before any variable is set, so the environment is
+    /// left unchanged on failure. Returns [`Error::InvalidEnvVar`] if a variable name is empty
+    /// or contains `=` or NUL, or a value contains NUL.
+    ///
+    /// The environment is shared by the whole process, so this should be called before any
+    /// other threads are started, and the values remain readable by child processes and for
+    /// the lifetime of the process.
+    pub fn export_to_env<F: Fn(&str) -> String>(
+        &self,
+        prefix: &str,
+        transform: F,
+        collision: EnvCollision,
+    ) -> Result<Vec<String>, Error> {
+        let mut variables = BTreeMap::new();
+        let mut collisions = Vec::new();
+        for (name, value) in self.iter() {
+            let value = Zeroizing::new(value?);
+            let variable = format!("{}{}", prefix, transform(&name));
+            if variable.is_empty() || variable.contains(['=', '\0']) || value.contains('\0') {
+                return Err(Error::InvalidEnvVar(variable));
+            }
+
+            if variables.contains_key(&variable) || std::env::var_os(&variable).is_some() {
+                match collision {
+                    EnvCollision::Overwrite => {}
+                    EnvCollision::Keep => continue,
+                    EnvCollision::Fail => {
+                        collisions.push(variable);
+                        continue;
+                    }
+                }
+            }
+            variables.insert(variable, value);
+        }
+
+        if !collisions.is_empty() {
+            collisions.sort();
+            collisions.dedup();
+            return Err(Error::EnvVarCollision(collisions));
+        }
+        for (variable, value) in &variables {
+            std::env::set_var(variable, value.as_str());
+        }
+        Ok(variables.into_keys().collect())
+    }
+}
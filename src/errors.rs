@@ -1,12 +1,245 @@
+//! The error type returned by all fallible SecureStore operations.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
 #[derive(Debug)]
 pub enum Error {
+    /// The vault does not record an IV, so keys cannot be derived from a password for it.
     MissingVaultIV,
+    /// The keyfile is not a valid binary or armored keyfile.
     InvalidKeyfile,
-    /// May be caused by using the wrong key or attempting to load ciphertext that has been
-    /// tampered with.
+    /// The keyfile (or buffer or stream of key material) is shorter than a full set of keys.
+    KeyfileTooShort {
+        /// The number of bytes required
+        expected: usize,
+        /// The number of bytes available
+        actual: usize,
+    },
+    /// The keys supplied to unlock the vault (e.g. the password or keyfile) are not the keys
+    /// the vault was created with.
+    InvalidKey,
+    /// The ciphertext is malformed and cannot be decrypted. May be caused by attempting to load
+    /// ciphertext that has been tampered with or truncated.
     DecryptionFailure,
-    SecretNotFound,
+    /// The HMAC or authentication tag of a secret did not verify, most likely because the
+    /// wrong keys were used or the ciphertext has been tampered with.
+    MacMismatch,
+    /// No secret with the specified name exists in the vault.
+    SecretNotFound(String),
+    /// A secret with the specified name already exists in the vault.
+    SecretExists(String),
+    /// The secret with the specified name has passed its expiration time and must be set again
+    /// before it can be retrieved.
+    SecretExpired(String),
+    /// The secret `name` has no previous value numbered `version` in its history.
+    VersionNotFound {
+        name: String,
+        version: usize,
+    },
+    /// The vault on-disk does not match the MAC recorded when it was last saved, because it was
+    /// modified (e.g. by removing, adding or reverting secrets) outside of SecureStore. May
+    /// also be caused by loading the vault with the wrong keys.
+    VaultTampered,
+    /// The password supplied to verify a password change did not match the vault's password.
+    InvalidPassword,
+    /// The vault was written in a format newer than this version of SecureStore understands,
+    /// or its version is invalid.
     UnsupportedVaultVersion,
+    /// The vault is encrypted with a scheme that this version of SecureStore does not support.
+    UnsupportedSchemeVersion(String),
+    /// The vault is not valid JSON, or does not have the expected structure, at the specified
+    /// (1-based) line and column.
+    MalformedVault {
+        line: usize,
+        col: usize,
+        source: serde_json::Error,
+    },
+    /// The vault is not valid in its [`VaultFormat`](crate::VaultFormat), for the specified
+    /// reason. Malformed JSON vaults are reported as [`Error::MalformedVault`] instead.
+    MalformedVaultFormat(String),
+    /// A file of secrets being imported is malformed at the specified (1-based) line.
+    MalformedImport {
+        line: usize,
+        reason: &'static str,
+    },
+    /// The parameters specified for (or recorded in the vault for) password-based key
+    /// derivation are out of range.
+    InvalidKdfParameters,
+    /// The password for a new vault does not meet the
+    /// [`PasswordPolicy`](crate::PasswordPolicy) set for it, for the specified reason.
+    WeakPassword(String),
+    /// The vault is locked by another process or `SecretsManager` instance.
+    VaultLocked,
+    /// The vault is held only in memory, so has no path or storage to be saved to.
+    InMemoryVault,
+    /// The vault was opened read-only, so cannot be saved.
+    ReadOnlyVault,
+    /// The [`SecretsManagerBuilder`](crate::SecretsManagerBuilder) option with the specified
+    /// name must be set to open the vault.
+    IncompleteBuilder(&'static str),
+    /// The [`SecretsManagerBuilder`](crate::SecretsManagerBuilder) options with the specified
+    /// names cannot both be set to open the vault.
+    ConflictingBuilderOptions(&'static str, &'static str),
+    /// A [`VaultStorage`](crate::VaultStorage) backend failed to load or store the vault.
+    StorageFailure(String),
+    /// The vault in storage was modified (or created) by someone else since it was loaded, so
+    /// saving it would have overwritten their changes. Load the vault again and reapply the
+    /// changes.
+    VaultConflict,
+    /// The secrets with the specified names have different values in the two vaults being
+    /// merged, and the merge strategy does not choose between them.
+    MergeConflict(Vec<String>),
+    /// The environment variable named by a [`KeySource`](crate::KeySource) is not set or is
+    /// not valid unicode.
+    EnvVar(std::env::VarError),
+    /// The environment variables with the specified names are already set, or more than one
+    /// secret would be exported to each, when exporting secrets with
+    /// [`EnvCollision::Fail`](crate::EnvCollision::Fail).
+    EnvVarCollision(Vec<String>),
+    /// A secret cannot be exported to the environment variable with the specified name, as the
+    /// name is empty or contains `=` or NUL, or the value contains NUL.
+    InvalidEnvVar(String),
+    /// An external key source (such as an agent, hardware token or key management service)
+    /// failed to provide the keys for the vault.
+    KeySourceFailure(String),
+    /// The vault does not contain keys wrapped by the KMS specified by the key source.
+    MissingWrappedKey,
+    /// The key shares supplied are malformed, belong to different splits or are fewer than the
+    /// threshold required to reconstruct the keys, or the share count and threshold requested
+    /// are out of range.
+    InvalidKeyShares,
+    /// The public key is not a valid X25519 public key for a recipient of the vault.
+    InvalidRecipient,
+    /// The X25519 identity specified is not one of the vault's recipients.
+    NotARecipient,
+    /// A secret could not be compressed or decompressed, or was compressed with an algorithm
+    /// this build of SecureStore does not support.
+    CompressionFailure(String),
+    /// The pattern passed to [`SecretsManager::find()`](crate::SecretsManager::find) is not a
+    /// valid glob (or regular expression), for the specified reason.
+    InvalidPattern(String),
+    /// The secret is not a valid TOTP seed for
+    /// [`SecretsManager::totp_code()`](crate::SecretsManager::totp_code), for the specified
+    /// reason.
+    InvalidTotp(String),
+    /// A value supplied for a secret or password is not valid in the form it was supplied in,
+    /// for the specified reason.
+    InvalidValue(String),
+    /// Reading or writing the file at `path` failed.
+    File {
+        path: PathBuf,
+        source: std::io::Error,
+    },
     Serde(serde_json::Error),
     Io(std::io::Error),
 }
+
+impl Error {
+    /// Returns a function which attaches `path` to an I/O error, for use with `map_err()`.
+    pub(crate) fn file(path: &Path) -> impl FnOnce(std::io::Error) -> Error + '_ {
+        move |source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MissingVaultIV => write!(f, "the vault has no IV to derive keys with"),
+            Error::InvalidKeyfile => write!(f, "invalid keyfile"),
+            Error::KeyfileTooShort { expected, actual } => write!(
+                f,
+                "keyfile too short: expected {} bytes, found {}",
+                expected, actual
+            ),
+            Error::InvalidKey => write!(f, "incorrect password or keyfile for this vault"),
+            Error::DecryptionFailure => write!(f, "malformed ciphertext"),
+            Error::MacMismatch => write!(
+                f,
+                "secret failed authentication (wrong keys or tampered ciphertext)"
+            ),
+            Error::SecretNotFound(name) => write!(f, "secret not found: {}", name),
+            Error::SecretExists(name) => write!(f, "secret already exists: {}", name),
+            Error::SecretExpired(name) => write!(f, "secret has expired: {}", name),
+            Error::VersionNotFound { name, version } => {
+                write!(f, "secret {} has no previous version {}", name, version)
+            }
+            Error::VaultTampered => write!(f, "the vault has been modified outside of SecureStore"),
+            Error::InvalidPassword => write!(f, "incorrect password"),
+            Error::UnsupportedVaultVersion => write!(f, "unsupported vault version"),
+            Error::UnsupportedSchemeVersion(scheme) => {
+                write!(f, "unsupported encryption scheme: {}", scheme)
+            }
+            Error::MalformedVault { line, col, source } => write!(
+                f,
+                "malformed vault at line {}, column {}: {}",
+                line, col, source
+            ),
+            Error::MalformedVaultFormat(reason) => write!(f, "malformed vault: {}", reason),
+            Error::MalformedImport { line, reason } => {
+                write!(f, "malformed import at line {}: {}", line, reason)
+            }
+            Error::InvalidKdfParameters => write!(f, "invalid key derivation parameters"),
+            Error::WeakPassword(reason) => write!(f, "weak password: {}", reason),
+            Error::VaultLocked => write!(f, "the vault is locked by another process"),
+            Error::InMemoryVault => write!(f, "the vault is held in memory and has no storage"),
+            Error::ReadOnlyVault => write!(f, "the vault was opened read-only"),
+            Error::IncompleteBuilder(option) => {
+                write!(f, "the builder option {} is not set", option)
+            }
+            Error::ConflictingBuilderOptions(first, second) => {
+                write!(
+                    f,
+                    "the builder options {} and {} cannot both be set",
+                    first, second
+                )
+            }
+            Error::StorageFailure(message) => write!(f, "storage failure: {}", message),
+            Error::VaultConflict => {
+                write!(f, "the vault was modified in storage since it was loaded")
+            }
+            Error::MergeConflict(names) => {
+                write!(f, "conflicting values for secrets: {}", names.join(", "))
+            }
+            Error::EnvVar(e) => write!(f, "environment variable: {}", e),
+            Error::EnvVarCollision(names) => {
+                write!(f, "environment variables already set: {}", names.join(", "))
+            }
+            Error::InvalidTotp(reason) => write!(f, "invalid TOTP seed: {}", reason),
+            Error::InvalidValue(reason) => write!(f, "invalid value: {}", reason),
+            Error::InvalidEnvVar(name) => {
+                write!(
+                    f,
+                    "cannot export a secret as environment variable: {}",
+                    name
+                )
+            }
+            Error::KeySourceFailure(message) => write!(f, "key source failure: {}", message),
+            Error::MissingWrappedKey => write!(f, "the vault has no keys wrapped by this KMS"),
+            Error::InvalidKeyShares => write!(f, "invalid or insufficient key shares"),
+            Error::InvalidRecipient => write!(f, "invalid X25519 recipient public key"),
+            Error::NotARecipient => {
+                write!(f, "the X25519 identity is not a recipient of the vault")
+            }
+            Error::CompressionFailure(message) => write!(f, "compression failure: {}", message),
+            Error::InvalidPattern(reason) => write!(f, "invalid pattern: {}", reason),
+            Error::File { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Serde(e) => write!(f, "serialization error: {}", e),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::EnvVar(e) => Some(e),
+            Error::MalformedVault { source, .. } | Error::Serde(source) => Some(source),
+            Error::File { source, .. } | Error::Io(source) => Some(source),
+            _ => None,
+        }
+    }
+}
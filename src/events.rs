@@ -0,0 +1,50 @@
+//! Notification of accesses to a vault, for feeding audit logs.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+use std::sync::Arc;
+
+/// An access to a vault, as passed to the hooks registered with
+/// [`SecretsManager::on_event()`] and [`SecretsManagerBuilder::on_event()`].
+///
+/// [`SecretsManagerBuilder::on_event()`]: crate::SecretsManagerBuilder::on_event
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// A secret was decrypted.
+    SecretRead { name: &'a str },
+    /// A secret was added or given a new value.
+    SecretWritten { name: &'a str },
+    /// A secret was removed.
+    SecretRemoved { name: &'a str },
+    /// The vault was saved to its storage.
+    VaultSaved,
+    /// The vault could not be loaded or unlocked, e.g. because the wrong password was
+    /// supplied. Only reported to hooks registered with the builder.
+    UnlockFailed { error: &'a Error },
+}
+
+/// A hook registered to be called with each [`Event`].
+pub(crate) type EventHook = Arc<dyn Fn(&Event) + Send + Sync>;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Registers `hook` to be called after each access to the vault, e.g. to record who read
+    /// which secret when. Hooks are called synchronously, in the order they were registered,
+    /// and are passed only the names of secrets, never their values.
+    pub fn on_event<F: Fn(&Event) + Send + Sync + 'static>(&mut self, hook: F) {
+        self.hooks.push(Arc::new(hook));
+    }
+
+    /// Calls every registered hook with `event`.
+    pub(crate) fn emit(&self, event: Event) {
+        self.trace(&event);
+        emit(&self.hooks, event)
+    }
+}
+
+/// Calls each of `hooks` with `event`.
+pub(crate) fn emit(hooks: &[EventHook], event: Event) {
+    for hook in hooks {
+        hook(&event);
+    }
+}
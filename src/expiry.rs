@@ -0,0 +1,78 @@
+//! Expiration of secrets which must be rotated by a certain time.
+//!
+//! The time a secret expires is stored unencrypted in the vault, in seconds since the Unix
+//! epoch, and is covered by the vault MAC. Once a secret has expired it can no longer be
+//! retrieved until it is set again, which clears its expiration.
+
+use crate::clock;
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::serial::BinarySerializable;
+use crate::SecretsManager;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Adds the secret `value` to the store under `name`, as with [`set()`](Self::set), to be
+    /// retrieved only until `expires`. Retrieving the secret after that time returns
+    /// [`Error::SecretExpired`].
+    pub fn set_with_expiry<T: BinarySerializable>(
+        &mut self,
+        name: &str,
+        value: T,
+        expires: SystemTime,
+    ) {
+        self.set(name, value);
+        self.vault
+            .expiry
+            .insert(name.to_string(), clock::seconds(expires));
+    }
+
+    /// Sets the time the existing secret `name` expires, or with `None` removes its expiration,
+    /// without changing its value. If the secret cannot be found, returns
+    /// [`Error::SecretNotFound`].
+    pub fn set_expiry(&mut self, name: &str, expires: Option<SystemTime>) -> Result<(), Error> {
+        if !self.vault.secrets.contains_key(name) {
+            return Err(Error::SecretNotFound(name.to_string()));
+        }
+        match expires {
+            Some(expires) => self
+                .vault
+                .expiry
+                .insert(name.to_string(), clock::seconds(expires)),
+            None => self.vault.expiry.remove(name),
+        };
+        self.vault.mark_dirty();
+        Ok(())
+    }
+
+    /// The time the secret `name` expires, or `None` if it does not expire (or does not exist).
+    /// Expiration times are only recorded to the second.
+    pub fn expiry(&self, name: &str) -> Option<SystemTime> {
+        self.vault
+            .expiry
+            .get(name)
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(*secs))
+    }
+
+    /// Returns an iterator over the names of all secrets which have expired, in sorted order,
+    /// e.g. for tooling which rotates secrets before they are needed again.
+    pub fn expired_keys(&self) -> impl Iterator<Item = &str> {
+        let now = clock::seconds(clock::now());
+        self.vault
+            .expiry
+            .iter()
+            .filter(move |(_, expires)| **expires <= now)
+            .map(|(name, _)| name.as_str())
+            .filter(move |name| self.vault.secrets.contains_key(*name))
+    }
+
+    /// Returns [`Error::SecretExpired`] if the secret `name` has expired.
+    pub(crate) fn check_expiry(&self, name: &str) -> Result<(), Error> {
+        match self.vault.expiry.get(name) {
+            Some(expires) if *expires <= clock::seconds(clock::now()) => {
+                Err(Error::SecretExpired(name.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
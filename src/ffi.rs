@@ -0,0 +1,208 @@
+//! A C interface to vaults, for services written in C, C++, Go and other languages which can call
+//! C functions, declared in `include/securestore.h`.
+//!
+//! The crate must be built as a C library to link against it, e.g. with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! Vaults are opened with [`securestore_open()`], which returns an opaque handle to be passed to
+//! the other functions and released with [`securestore_free()`]. Functions returning `int`
+//! return `0` on success and `-1` on failure, and functions returning a pointer return `NULL` on
+//! failure; the reason for the last failure on the calling thread is then available from
+//! [`securestore_last_error()`]. Secret values are returned in buffers owned by the library,
+//! which must be released with [`securestore_buffer_free()`] so they can be wiped.
+
+use crate::{KeySource, SecretsManager};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::Path;
+use std::ptr;
+use zeroize::{Zeroize, Zeroizing};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records `error` as the last error on this thread, returning `-1`.
+fn fail(error: &dyn std::fmt::Display) -> c_int {
+    let message = CString::new(error.to_string().replace('\0', ""))
+        .unwrap_or_else(|_| CString::new("unknown error").unwrap());
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    -1
+}
+
+/// Converts the possibly-NULL C string `s` to a `&str`, naming it `what` in errors.
+unsafe fn string<'a>(s: *const c_char, what: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", what));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("{} is not valid UTF-8", what))
+}
+
+/// Returns the message describing the last failure of a function called on this thread, or
+/// `NULL` if none has failed. The message is owned by the library and remains valid until the
+/// next failure on this thread.
+#[no_mangle]
+pub extern "C" fn securestore_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Opens the vault at `path`, unlocking it with `password` or the keyfile at `keyfile`, exactly
+/// one of which must be non-NULL. Returns a handle to the vault, or `NULL` on failure.
+///
+/// # Safety
+///
+/// `path` and whichever of `password` and `keyfile` is non-NULL must be valid NUL-terminated
+/// strings.
+#[no_mangle]
+pub unsafe extern "C" fn securestore_open(
+    path: *const c_char,
+    password: *const c_char,
+    keyfile: *const c_char,
+) -> *mut SecretsManager {
+    let result = (|| {
+        let path = string(path, "path")?;
+        let sman = match (password.is_null(), keyfile.is_null()) {
+            (false, true) => {
+                let password = string(password, "password")?;
+                SecretsManager::load(path, KeySource::Password(password))
+            }
+            (true, false) => {
+                let keyfile = string(keyfile, "keyfile")?;
+                SecretsManager::load(path, KeySource::File(Path::new(keyfile)))
+            }
+            _ => return Err("exactly one of password and keyfile must be set".to_string()),
+        };
+        sman.map_err(|e| e.to_string())
+    })();
+
+    match result {
+        Ok(sman) => Box::into_raw(Box::new(sman)),
+        Err(e) => {
+            fail(&e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Decrypts the secret `name`, storing a pointer to its value in `*value` and its length in
+/// `*length`. The value is not NUL-terminated, and must be released with
+/// [`securestore_buffer_free()`].
+///
+/// # Safety
+///
+/// `handle` must have been returned by [`securestore_open()`] and not yet freed, `name` must be
+/// a valid NUL-terminated string, and `value` and `length` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn securestore_get(
+    handle: *const SecretsManager,
+    name: *const c_char,
+    value: *mut *mut u8,
+    length: *mut usize,
+) -> c_int {
+    let sman = match handle.as_ref() {
+        Some(sman) => sman,
+        None => return fail(&"handle is NULL"),
+    };
+    let name = match string(name, "name") {
+        Ok(name) => name,
+        Err(e) => return fail(&e),
+    };
+    if value.is_null() || length.is_null() {
+        return fail(&"value and length must not be NULL");
+    }
+
+    match sman.retrieve::<Vec<u8>>(name) {
+        Ok(secret) => {
+            // Copied into an allocation of the exact length, so that the buffer can be wiped
+            // and freed knowing only its length
+            let secret: Box<[u8]> = Zeroizing::new(secret).as_slice().into();
+            *length = secret.len();
+            *value = Box::into_raw(secret) as *mut u8;
+            0
+        }
+        Err(e) => fail(&e),
+    }
+}
+
+/// Adds the `length` bytes at `value` to the vault as the secret `name`, replacing any
+/// existing value. The vault is not written until [`securestore_save()`] is called.
+///
+/// # Safety
+///
+/// `handle` must have been returned by [`securestore_open()`] and not yet freed, `name` must be
+/// a valid NUL-terminated string, and `value` must be valid for reads of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn securestore_set(
+    handle: *mut SecretsManager,
+    name: *const c_char,
+    value: *const u8,
+    length: usize,
+) -> c_int {
+    let sman = match handle.as_mut() {
+        Some(sman) => sman,
+        None => return fail(&"handle is NULL"),
+    };
+    let name = match string(name, "name") {
+        Ok(name) => name,
+        Err(e) => return fail(&e),
+    };
+    let value = match (value.is_null(), length) {
+        (_, 0) => &[][..],
+        (true, _) => return fail(&"value is NULL"),
+        (false, _) => std::slice::from_raw_parts(value, length),
+    };
+
+    sman.set(name, value);
+    0
+}
+
+/// Saves the changes made to the vault.
+///
+/// # Safety
+///
+/// `handle` must have been returned by [`securestore_open()`] and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn securestore_save(handle: *const SecretsManager) -> c_int {
+    match handle.as_ref() {
+        Some(sman) => match sman.save() {
+            Ok(()) => 0,
+            Err(e) => fail(&e),
+        },
+        None => fail(&"handle is NULL"),
+    }
+}
+
+/// Releases the handle returned by [`securestore_open()`], wiping the keys from memory. Unsaved
+/// changes are lost. Does nothing if `handle` is NULL.
+///
+/// # Safety
+///
+/// `handle` must be NULL or have been returned by [`securestore_open()`], and must not be used
+/// again.
+#[no_mangle]
+pub unsafe extern "C" fn securestore_free(handle: *mut SecretsManager) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Wipes and releases the buffer of `length` bytes returned by [`securestore_get()`]. Does
+/// nothing if `value` is NULL.
+///
+/// # Safety
+///
+/// `value` must be NULL or have been returned by [`securestore_get()`] along with `length`, and
+/// must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn securestore_buffer_free(value: *mut u8, length: usize) {
+    if !value.is_null() {
+        let mut secret = Box::from_raw(ptr::slice_from_raw_parts_mut(value, length));
+        secret.zeroize();
+    }
+}
@@ -0,0 +1,143 @@
+//! Storage of whole files as secrets, such as certificate bundles and key files, along with the
+//! name and permissions needed to restore them.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+/// The permissions files are restored with if neither the caller nor the vault specify any.
+#[cfg(unix)]
+const DEFAULT_MODE: u32 = 0o600;
+
+/// Details of a file stored with [`SecretsManager::set_file()`], used to restore it with
+/// [`SecretsManager::retrieve_to_file()`]. Stored unencrypted (but authenticated) in the vault,
+/// like the names of the secrets themselves.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// The name (without any directories) of the file.
+    pub filename: String,
+    /// The Unix permission bits of the file, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Adds the contents of the file at `path` as the secret `name`, replacing any existing
+    /// secret of that name, and records the name and (on Unix) permissions of the file so that
+    /// it can be restored with [`retrieve_to_file()`](Self::retrieve_to_file).
+    ///
+    /// The file can also be retrieved as any other secret, e.g. with
+    /// [`retrieve_bytes()`](Self::retrieve_bytes). Replacing the secret by other means discards
+    /// the file details.
+    pub fn set_file<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let filename = path
+            .file_name()
+            .ok_or_else(|| not_a_file(path))?
+            .to_string_lossy()
+            .into_owned();
+        let metadata = std::fs::metadata(path).map_err(Error::file(path))?;
+        let contents = Zeroizing::new(std::fs::read(path).map_err(Error::file(path))?);
+
+        self.set_bytes(name, &contents);
+        self.vault.files.insert(
+            name.to_string(),
+            FileMetadata {
+                filename,
+                mode: mode_of(&metadata),
+            },
+        );
+        Ok(())
+    }
+
+    /// The details of the file stored as the secret `name` with
+    /// [`set_file()`](Self::set_file), or `None` if the secret was not set from a file.
+    pub fn file_metadata(&self, name: &str) -> Option<&FileMetadata> {
+        self.vault.files.get(name)
+    }
+
+    /// Decrypts the secret `name` and writes it to a file, replacing any existing file, and
+    /// returns the path of the file written. If `path` is an existing directory, the file is
+    /// created in it under its original name (or the last component of `name`, for secrets not
+    /// set from a file).
+    ///
+    /// On Unix, the file is given the permissions `mode` if specified, else the permissions the
+    /// file had when it was stored, or `0o600` if those are not known. `mode` is ignored on
+    /// other platforms. If the secret cannot be found, returns [`Error::SecretNotFound`].
+    pub fn retrieve_to_file<P: AsRef<Path>>(
+        &self,
+        name: &str,
+        path: P,
+        mode: Option<u32>,
+    ) -> Result<PathBuf, Error> {
+        let metadata = self.file_metadata(name);
+        let path = match path.as_ref() {
+            // Only the last component of the name is used, so files are never written outside
+            // of `dir`
+            dir if dir.is_dir() => {
+                let filename = metadata.map_or(name, |m| m.filename.as_str());
+                match Path::new(filename).file_name() {
+                    Some(filename) => dir.join(filename),
+                    None => return Err(not_a_file(&dir.join(filename))),
+                }
+            }
+            path => path.to_path_buf(),
+        };
+        let contents = Zeroizing::new(self.retrieve_bytes(name)?);
+        let mode = mode.or_else(|| metadata.and_then(|m| m.mode));
+        write_file(&path, &contents, mode)?;
+        Ok(path)
+    }
+}
+
+fn not_a_file(path: &Path) -> Error {
+    Error::File {
+        path: path.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file name"),
+    }
+}
+
+#[cfg(unix)]
+fn mode_of(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode_of(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Writes `contents` to the file at `path`, which is never readable by others while the
+/// contents are written unless `mode` allows it.
+fn write_file(path: &Path, contents: &[u8], mode: Option<u32>) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(DEFAULT_MODE);
+        let mut file = options.open(path).map_err(Error::file(path))?;
+        // An existing file keeps its permissions when opened, and new files are subject to the
+        // umask, so the permissions are always set explicitly: first to keep the contents
+        // private as they are written, then to those requested
+        file.set_permissions(std::fs::Permissions::from_mode(DEFAULT_MODE))
+            .map_err(Error::file(path))?;
+        file.write_all(contents).map_err(Error::file(path))?;
+        let mode = mode.unwrap_or(DEFAULT_MODE);
+        file.set_permissions(std::fs::Permissions::from_mode(mode))
+            .map_err(Error::file(path))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+        let mut file = options.open(path).map_err(Error::file(path))?;
+        file.write_all(contents).map_err(Error::file(path))
+    }
+}
@@ -0,0 +1,163 @@
+//! Searching for secrets by name with glob patterns (and, optionally, regular expressions).
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Returns the names of all secrets matching the glob `pattern`, in sorted order.
+    ///
+    /// As in shell globs, `?` matches any one character and `*` any number of characters
+    /// within a component of the name (i.e. other than `/`), while `**` matches across
+    /// components, so `db/*/password` matches `db/prod/password` and `**/password` matches
+    /// `password` and `db/prod/password`. `[abc]`, `[a-z]` and `[!abc]` match a single
+    /// character in (or not in) the set. Returns [`Error::InvalidPattern`] for an unterminated
+    /// set.
+    pub fn find(&self, pattern: &str) -> Result<Vec<&str>, Error> {
+        let glob = parse(pattern)?;
+        Ok(self
+            .keys()
+            .filter(|name| {
+                let name: Vec<char> = name.chars().collect();
+                matches(&glob, &name)
+            })
+            .collect())
+    }
+
+    /// Returns the names of all secrets matching the regular expression `pattern`, in sorted
+    /// order. The pattern is not anchored, so use `^` and `$` to match whole names. Returns
+    /// [`Error::InvalidPattern`] if the pattern is not a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn find_regex(&self, pattern: &str) -> Result<Vec<&str>, Error> {
+        let regex = regex::Regex::new(pattern).map_err(|e| Error::InvalidPattern(e.to_string()))?;
+        Ok(self.keys().filter(|name| regex.is_match(name)).collect())
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `**`
+    DoubleStar,
+    /// `**/`, which also matches no components at all
+    Components,
+    /// `[...]`, with inclusive ranges of characters
+    Set {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+fn parse(pattern: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '?' => Token::Any,
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                match chars.peek() == Some(&'/') {
+                    true => {
+                        chars.next();
+                        Token::Components
+                    }
+                    false => Token::DoubleStar,
+                }
+            }
+            '*' => Token::Star,
+            '[' => {
+                let negated = chars.next_if_eq(&'!').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let start = match chars.next() {
+                        // A `]` straight after the opening bracket is part of the set
+                        Some(']') if !ranges.is_empty() => break,
+                        Some(c) => c,
+                        None => {
+                            return Err(Error::InvalidPattern(format!(
+                                "unterminated [ in {}",
+                                pattern
+                            )))
+                        }
+                    };
+                    let end = match chars.peek() == Some(&'-') {
+                        true => {
+                            chars.next();
+                            match chars.next_if(|c| *c != ']') {
+                                Some(end) => end,
+                                // A trailing `-` is part of the set
+                                None => {
+                                    ranges.push(('-', '-'));
+                                    start
+                                }
+                            }
+                        }
+                        false => start,
+                    };
+                    ranges.push((start, end));
+                }
+                Token::Set { negated, ranges }
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Matches `name` against the parsed glob, remembering whether each remainder of the glob
+/// matched each remainder of the name, so that globs with many stars take polynomial time.
+fn matches(glob: &[Token], name: &[char]) -> bool {
+    let mut memo = vec![None; (glob.len() + 1) * (name.len() + 1)];
+    matches_at(glob, name, 0, 0, &mut memo)
+}
+
+/// Matches `name[n..]` against `glob[g..]`.
+fn matches_at(
+    glob: &[Token],
+    name: &[char],
+    g: usize,
+    n: usize,
+    memo: &mut [Option<bool>],
+) -> bool {
+    let key = g * (name.len() + 1) + n;
+    if let Some(matched) = memo[key] {
+        return matched;
+    }
+    let matched = match glob.get(g) {
+        None => n == name.len(),
+        Some(Token::Char(c)) => {
+            name.get(n) == Some(c) && matches_at(glob, name, g + 1, n + 1, memo)
+        }
+        Some(Token::Any) => {
+            matches!(name.get(n), Some(c) if *c != '/')
+                && matches_at(glob, name, g + 1, n + 1, memo)
+        }
+        Some(Token::Set { negated, ranges }) => match name.get(n) {
+            Some(c) => {
+                let found = ranges.iter().any(|(start, end)| (start..=end).contains(&c));
+                found != *negated && *c != '/' && matches_at(glob, name, g + 1, n + 1, memo)
+            }
+            None => false,
+        },
+        Some(Token::Star) => {
+            let component = name[n..]
+                .iter()
+                .position(|c| *c == '/')
+                .map_or(name.len(), |i| n + i);
+            (n..=component).any(|i| matches_at(glob, name, g + 1, i, memo))
+        }
+        Some(Token::DoubleStar) => (n..=name.len()).any(|i| matches_at(glob, name, g + 1, i, memo)),
+        Some(Token::Components) => {
+            matches_at(glob, name, g + 1, n, memo)
+                || (n + 1..=name.len())
+                    .any(|i| name[i - 1] == '/' && matches_at(glob, name, g + 1, i, memo))
+        }
+    };
+    memo[key] = Some(matched);
+    matched
+}
@@ -0,0 +1,94 @@
+//! The containers a vault may be serialized in.
+//!
+//! The format only determines how the fields of the vault are written out: the fields
+//! themselves, the encryption of the secrets and the MAC over the vault (always computed over
+//! the compact JSON serialization) are the same in every format, so a vault can be converted
+//! between formats without re-encrypting anything.
+
+use crate::errors::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The on-disk representation of a vault, set when the vault is created (with
+/// [`SecretsManagerBuilder::format()`](crate::SecretsManagerBuilder::format)) and detected
+/// automatically when it is loaded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum VaultFormat {
+    /// A pretty-printed JSON object, as read and written by all SecureStore implementations.
+    #[default]
+    Json,
+    /// A YAML document. Comments added to the file are not preserved when the vault is saved.
+    #[cfg(feature = "yaml")]
+    Yaml,
+    /// A CBOR map, holding binary data as raw byte strings rather than base64, which makes
+    /// vaults holding many (or large) binary secrets about a quarter smaller. The map is
+    /// preceded by the CBOR self-describe tag, which identifies the format at load.
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+/// The CBOR self-describe tag (55799), which marks the start of a CBOR vault.
+#[cfg(feature = "cbor")]
+const CBOR_MAGIC: &[u8] = &[0xd9, 0xd9, 0xf7];
+
+impl VaultFormat {
+    /// Determines the format of the serialized vault `contents`. CBOR vaults are recognized by
+    /// their tag and JSON vaults by their opening brace; anything else is assumed to be YAML,
+    /// if supported.
+    pub(crate) fn detect(contents: &[u8]) -> Self {
+        #[cfg(feature = "cbor")]
+        if contents.starts_with(CBOR_MAGIC) {
+            return VaultFormat::Cbor;
+        }
+        let first = contents.iter().find(|b| !b.is_ascii_whitespace());
+        match first {
+            #[cfg(feature = "yaml")]
+            Some(b) if *b != b'{' => VaultFormat::Yaml,
+            _ => VaultFormat::Json,
+        }
+    }
+
+    /// Serializes `value`. Text formats end with a newline.
+    pub(crate) fn serialize<T: Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            VaultFormat::Json => {
+                let mut contents = serde_json::to_vec_pretty(value).map_err(Error::Serde)?;
+                contents.push(b'\n');
+                Ok(contents)
+            }
+            #[cfg(feature = "yaml")]
+            VaultFormat::Yaml => serde_yaml::to_string(value)
+                .map(String::into_bytes)
+                .map_err(|e| Error::MalformedVaultFormat(e.to_string())),
+            #[cfg(feature = "cbor")]
+            VaultFormat::Cbor => {
+                let mut contents = CBOR_MAGIC.to_vec();
+                ciborium::into_writer(value, &mut contents)
+                    .map_err(|e| Error::MalformedVaultFormat(e.to_string()))?;
+                Ok(contents)
+            }
+        }
+    }
+
+    /// Deserializes `contents`, which must be in this format.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, contents: &[u8]) -> Result<T, Error> {
+        match self {
+            VaultFormat::Json => {
+                serde_json::from_slice(contents).map_err(|e| Error::MalformedVault {
+                    line: e.line(),
+                    col: e.column(),
+                    source: e,
+                })
+            }
+            #[cfg(feature = "yaml")]
+            VaultFormat::Yaml => serde_yaml::from_slice(contents)
+                .map_err(|e| Error::MalformedVaultFormat(e.to_string())),
+            #[cfg(feature = "cbor")]
+            VaultFormat::Cbor => {
+                let contents = contents.strip_prefix(CBOR_MAGIC).unwrap_or(contents);
+                ciborium::from_reader(contents)
+                    .map_err(|e| Error::MalformedVaultFormat(e.to_string()))
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Generation of random secrets, so that strong credentials can be stored without ever being
+//! chosen or typed by a person.
+
+use crate::crypto::{CryptoProvider, DefaultProvider};
+use crate::secret::SecretString;
+use zeroize::Zeroizing;
+
+/// The characters a password generated by [`password()`] is drawn from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Charset {
+    /// `0-9`, e.g. for PINs.
+    Digits,
+    /// `0-9` and `a-f`.
+    Hex,
+    /// `A-Z`, `a-z` and `0-9`.
+    Alphanumeric,
+    /// `A-Z`, `a-z`, `0-9` and ASCII punctuation other than quotes, backslashes and
+    /// backticks, so that passwords can be pasted into shells and config files unescaped.
+    AlphanumericSymbols,
+}
+
+const DIGITS: &[u8] = b"0123456789";
+const HEX: &[u8] = b"0123456789abcdef";
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const ALPHANUMERIC_SYMBOLS: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+impl Charset {
+    /// The characters in this set.
+    pub fn chars(self) -> &'static str {
+        let chars = match self {
+            Charset::Digits => DIGITS,
+            Charset::Hex => HEX,
+            Charset::Alphanumeric => ALPHANUMERIC,
+            Charset::AlphanumericSymbols => ALPHANUMERIC_SYMBOLS,
+        };
+        // All of the sets are ASCII
+        std::str::from_utf8(chars).unwrap()
+    }
+}
+
+/// Generates a password of `length` characters, each chosen uniformly at random from
+/// `charset` with the CSPRNG of the [`DefaultProvider`].
+pub fn password(length: usize, charset: Charset) -> SecretString {
+    let chars = charset.chars().as_bytes();
+    // Random bytes of at least `limit` are rejected, so that every character is equally likely
+    let limit = 256 - 256 % chars.len();
+
+    let mut password = String::with_capacity(length);
+    let mut random = Zeroizing::new([0u8; 64]);
+    while password.len() < length {
+        DefaultProvider::random(&mut *random);
+        for byte in random.iter().map(|b| usize::from(*b)) {
+            if byte < limit && password.len() < length {
+                password.push(char::from(chars[byte % chars.len()]));
+            }
+        }
+    }
+    SecretString::new(password)
+}
@@ -0,0 +1,162 @@
+//! Previous values of secrets, kept in the vault so that overwritten secrets can be recovered.
+//!
+//! History is disabled by default. Once enabled with
+//! [`SecretsManager::set_history_depth()`], the depth is recorded in the vault, so every
+//! process updating the vault keeps the same number of versions. Previous versions are
+//! encrypted exactly like the current value and are re-encrypted along with it when the keys
+//! or scheme change.
+
+use crate::clock;
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::files::FileMetadata;
+use crate::serial::BinaryDeserializable;
+use crate::shared::{EncryptedBlob, Keys, Scheme};
+use crate::SecretsManager;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A previous value of a secret, as stored in the vault.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Version {
+    /// When the value was replaced, in seconds since the Unix epoch.
+    pub replaced: u64,
+    pub value: EncryptedBlob,
+    /// When the value expired, in seconds since the Unix epoch, if it had an expiration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+    /// The details of the file the value was set from, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileMetadata>,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Keeps up to `depth` previous values of each secret whenever it is set, or none if
+    /// `depth` is zero (the default). The depth is stored in the vault. Reducing it discards
+    /// the oldest versions of secrets with more than `depth` previous versions.
+    pub fn set_history_depth(&mut self, depth: usize) {
+        self.vault.history_depth = Some(depth).filter(|d| *d > 0);
+        for versions in self.vault.history.values_mut() {
+            versions.truncate(depth);
+        }
+        self.vault
+            .history
+            .retain(|_, versions| !versions.is_empty());
+        self.vault.mark_dirty();
+    }
+
+    /// The number of previous values kept for each secret.
+    pub fn history_depth(&self) -> usize {
+        self.vault.history_depth.unwrap_or(0)
+    }
+
+    /// Returns the times at which each previous value of the secret `name` was replaced, most
+    /// recent first, so that the value replaced at `history(name)[n]` is retrieved with
+    /// [`retrieve_version(name, n)`](Self::retrieve_version).
+    pub fn history(&self, name: &str) -> Vec<SystemTime> {
+        self.vault
+            .history
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|v| UNIX_EPOCH + Duration::from_secs(v.replaced))
+            .collect()
+    }
+
+    /// Decrypts and retrieves the `n`th previous value of the secret `name`, where `0` is the
+    /// value it had before it was last set. Returns [`Error::VersionNotFound`] if there is no
+    /// such version.
+    pub fn retrieve_version<T: BinaryDeserializable>(
+        &self,
+        name: &str,
+        n: usize,
+    ) -> Result<T, Error> {
+        let version = self.version(name, n)?;
+        let decrypted = version
+            .value
+            .decrypt::<C>(&self.keys, self.vault.scheme())?;
+        self.emit(Event::SecretRead { name });
+        T::deserialize(decrypted)
+    }
+
+    /// Restores the most recent previous value of the secret `name`, discarding its current
+    /// value, so that repeated rollbacks step further back in its history. The expiration and
+    /// file details (see [`set_file()`](Self::set_file)) the previous value had are restored
+    /// along with it, or cleared if it had none. Returns [`Error::VersionNotFound`] if the
+    /// secret has no previous values.
+    pub fn rollback(&mut self, name: &str) -> Result<(), Error> {
+        self.version(name, 0)?;
+        let versions = self.vault.history.get_mut(name).expect("version was found");
+        let previous = versions.remove(0);
+        if versions.is_empty() {
+            self.vault.history.remove(name);
+        }
+        self.vault.secrets.insert(name.to_string(), previous.value);
+        match previous.expires {
+            Some(expires) => self.vault.expiry.insert(name.to_string(), expires),
+            None => self.vault.expiry.remove(name),
+        };
+        match previous.file {
+            Some(file) => self.vault.files.insert(name.to_string(), file),
+            None => self.vault.files.remove(name),
+        };
+        self.touch(name);
+        self.vault.mark_dirty();
+        self.emit(Event::SecretWritten { name });
+        Ok(())
+    }
+
+    fn version(&self, name: &str, n: usize) -> Result<&Version, Error> {
+        self.vault
+            .history
+            .get(name)
+            .and_then(|versions| versions.get(n))
+            .ok_or_else(|| Error::VersionNotFound {
+                name: name.to_string(),
+                version: n,
+            })
+    }
+
+    /// Records `previous`, the value of the secret `name` which is being replaced, along with
+    /// its expiration and file details, if history is enabled.
+    pub(crate) fn push_version(&mut self, name: &str, previous: EncryptedBlob) {
+        let depth = self.history_depth();
+        if depth == 0 {
+            return;
+        }
+        let version = Version {
+            replaced: clock::seconds(clock::now()),
+            value: previous,
+            expires: self.vault.expiry.get(name).copied(),
+            file: self.vault.files.get(name).cloned(),
+        };
+        let versions = self.vault.history.entry(name.to_string()).or_default();
+        versions.insert(0, version);
+        versions.truncate(depth);
+    }
+
+    /// Decrypts every previous version with the current keys and encrypts it again with
+    /// `keys` and `scheme`, without modifying the vault.
+    pub(crate) fn reencrypt_history(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+    ) -> Result<BTreeMap<String, Vec<Version>>, Error> {
+        let mut history = BTreeMap::new();
+        for (name, versions) in &self.vault.history {
+            let mut reencrypted = Vec::with_capacity(versions.len());
+            for version in versions {
+                reencrypted.push(Version {
+                    replaced: version.replaced,
+                    value: self.reencrypt_blob(&version.value, keys, scheme)?,
+                    expires: version.expires,
+                    file: version.file.clone(),
+                });
+            }
+            history.insert(name.clone(), reencrypted);
+        }
+        Ok(history)
+    }
+}
@@ -0,0 +1,199 @@
+//! Password-based key derivation functions supported for deriving vault keys.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::shared::{IV_SIZE, KEY_COUNT, KEY_LENGTH, PBKDF2_ROUNDS};
+use crate::trace;
+use serde_derive::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// A key derivation function, along with its parameters, used to derive keys from a password.
+/// The KDF a vault was created with is recorded in the vault so that it can be unlocked with
+/// the same parameters later on.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(tag = "algorithm", rename_all = "lowercase")]
+pub enum Kdf {
+    /// PBKDF2 with a configurable HMAC digest and number of rounds.
+    Pbkdf2 {
+        /// The digest to use as the underlying HMAC PRF
+        digest: Digest,
+        /// The number of iterations to perform
+        rounds: u32,
+    },
+    /// The memory-hard Argon2id KDF.
+    Argon2id {
+        /// The amount of memory to use, in KiB
+        memory_kib: u32,
+        /// The number of passes over the memory
+        iterations: u32,
+        /// The number of lanes to compute in parallel
+        parallelism: u32,
+    },
+    /// The memory-hard scrypt KDF.
+    Scrypt {
+        /// The base-2 logarithm of the CPU/memory cost parameter `N`
+        log_n: u8,
+        /// The block size
+        r: u32,
+        /// The parallelization parameter
+        p: u32,
+    },
+}
+
+/// The hash functions that may be used as the HMAC PRF for PBKDF2.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Digest {
+    Sha1,
+    Sha256,
+}
+
+/// The number of rounds of PBKDF2-HMAC-SHA256 performed by the default KDF, as recommended by
+/// OWASP.
+const PBKDF2_SHA256_ROUNDS: u32 = 600_000;
+
+/// The most rounds of PBKDF2 accepted, so that a vault recording an absurd number of rounds is
+/// rejected rather than taking hours to unlock.
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+
+/// The most memory Argon2id may use, in KiB (1 GiB).
+const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// The most passes over the memory Argon2id may perform.
+const MAX_ARGON2_ITERATIONS: u32 = 64;
+
+/// The most lanes Argon2id may compute in parallel.
+const MAX_ARGON2_PARALLELISM: u32 = 64;
+
+/// The largest cost scrypt may be configured with, as the base-2 logarithm of `N`; at the
+/// default block size, 2^20 uses 1 GiB of memory.
+const MAX_SCRYPT_LOG_N: u8 = 20;
+
+/// The most memory scrypt may use, in bytes (1 GiB, as for Argon2id), which is `128 * r * N`.
+const MAX_SCRYPT_MEMORY: u64 = MAX_ARGON2_MEMORY_KIB as u64 * 1024;
+
+/// The largest product of the block size and parallelization parameter scrypt may be configured
+/// with, as each degree of parallelism repeats the memory-hard computation.
+const MAX_SCRYPT_PR: u64 = 64;
+
+/// The largest block size scrypt may be configured with.
+const MAX_SCRYPT_R: u32 = 32;
+
+/// The largest parallelization parameter scrypt may be configured with.
+const MAX_SCRYPT_P: u32 = 16;
+
+/// The default KDF for new vaults, PBKDF2-HMAC-SHA256 with 600,000 rounds. Unlike
+/// [`Kdf::legacy()`], these parameters may be raised over time without affecting existing
+/// vaults, as the parameters are recorded in each vault. Vaults created with this KDF can only
+/// be unlocked by SecureStore implementations which read the KDF recorded in the vault; create
+/// vaults with [`Kdf::legacy()`] to share them with implementations which do not.
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf::Pbkdf2 {
+            digest: Digest::Sha256,
+            rounds: PBKDF2_SHA256_ROUNDS,
+        }
+    }
+}
+
+impl Kdf {
+    /// The KDF used by vaults that do not record one, including those created by other
+    /// SecureStore implementations: PBKDF2-HMAC-SHA1 with [`PBKDF2_ROUNDS`] rounds.
+    pub fn legacy() -> Self {
+        Kdf::Pbkdf2 {
+            digest: Digest::Sha1,
+            rounds: PBKDF2_ROUNDS,
+        }
+    }
+
+    /// Argon2id with the parameters recommended by OWASP: 19 MiB of memory, two iterations, and
+    /// a single degree of parallelism.
+    pub fn argon2id() -> Self {
+        Kdf::Argon2id {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+
+    /// scrypt with the parameters recommended by OWASP: a cost of 2^17 (using 128 MiB of memory),
+    /// a block size of eight, and a single degree of parallelism.
+    pub fn scrypt() -> Self {
+        Kdf::Scrypt {
+            log_n: 17,
+            r: 8,
+            p: 1,
+        }
+    }
+
+    /// Verifies that the parameters are within the bounds accepted by this crate, returning
+    /// [`Error::InvalidKdfParameters`] if not. The parameters recorded in a vault are untrusted,
+    /// so they are checked when it is loaded, before any key is derived.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let valid = match self {
+            Kdf::Pbkdf2 { rounds, .. } => (1..=MAX_PBKDF2_ROUNDS).contains(rounds),
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                *memory_kib <= MAX_ARGON2_MEMORY_KIB
+                    && *iterations <= MAX_ARGON2_ITERATIONS
+                    && *parallelism <= MAX_ARGON2_PARALLELISM
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                *log_n <= MAX_SCRYPT_LOG_N
+                    && *r <= MAX_SCRYPT_R
+                    && *p <= MAX_SCRYPT_P
+                    && 128 * u64::from(*r) * (1u64 << log_n) <= MAX_SCRYPT_MEMORY
+                    && u64::from(*r) * u64::from(*p) <= MAX_SCRYPT_PR
+            }
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(Error::InvalidKdfParameters),
+        }
+    }
+
+    /// Derives the raw material for [`KEY_COUNT`] keys from `password`, salted with the vault IV.
+    /// The returned buffer is wiped from memory when dropped.
+    pub(crate) fn derive<C: CryptoProvider>(
+        &self,
+        password: &str,
+        iv: &[u8; IV_SIZE],
+    ) -> Result<Zeroizing<[u8; KEY_COUNT * KEY_LENGTH]>, Error> {
+        let _span = trace::span!("securestore.kdf", kdf = ?self);
+        self.validate()?;
+        let mut key_data = Zeroizing::new([0u8; KEY_COUNT * KEY_LENGTH]);
+
+        match self {
+            Kdf::Pbkdf2 { digest, rounds } => {
+                C::pbkdf2(*digest, password.as_bytes(), iv, *rounds, &mut *key_data);
+            }
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                use argon2::{Algorithm, Argon2, Params, Version};
+
+                let params =
+                    Params::new(*memory_kib, *iterations, *parallelism, Some(key_data.len()))
+                        .map_err(|_| Error::InvalidKdfParameters)?;
+
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), iv, &mut *key_data)
+                    .map_err(|_| Error::InvalidKdfParameters)?;
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(*log_n, *r, *p, key_data.len())
+                    .map_err(|_| Error::InvalidKdfParameters)?;
+
+                scrypt::scrypt(password.as_bytes(), iv, &params, &mut *key_data)
+                    .map_err(|_| Error::InvalidKdfParameters)?;
+            }
+        }
+
+        Ok(key_data)
+    }
+}
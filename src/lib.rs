@@ -1,65 +1,434 @@
-#![feature(nll)]
+#[cfg(feature = "tokio")]
+mod async_io;
+mod autosave;
+mod builder;
+mod clock;
+mod compression;
+#[cfg(feature = "config")]
+mod configuration;
+mod crypto;
+mod description;
+mod diff;
+mod env;
 mod errors;
+mod events;
+mod expiry;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod files;
+mod find;
+mod format;
+pub mod generators;
+mod history;
+mod kdf;
+mod lock;
+mod merge;
+mod metadata;
+mod namespace;
+mod password;
+mod plaintext;
+mod protected;
+#[cfg(feature = "python")]
+mod python;
+mod readonly;
+mod secret;
 mod serial;
+mod shamir;
 mod shared;
+mod sources;
+mod storage;
+mod stream;
+mod tags;
 #[cfg(test)]
 mod tests;
+mod tombstones;
+mod totp;
+mod trace;
+#[cfg(feature = "notify")]
+mod watch;
 
-use self::shared::{EncryptedBlob, Keys, Vault};
-use crate::errors::Error;
-pub use crate::serial::{BinaryDeserializable, BinarySerializable};
-use openssl::rand;
+pub use self::autosave::OnSaveFailure;
+pub use self::builder::SecretsManagerBuilder;
+use self::description::Description;
+use self::events::EventHook;
+use self::lock::VaultLock;
+use self::shared::{EncryptedBlob, KeyData, Keys, Vault, WrappedKey};
+pub use self::shared::{MacAlgorithm, Scheme};
+#[cfg(feature = "config")]
+pub use crate::configuration::SecureStoreSource;
+#[cfg(feature = "openssl")]
+pub use crate::crypto::OpenSsl;
+#[cfg(feature = "rust-crypto")]
+pub use crate::crypto::RustCrypto;
+pub use crate::crypto::{Aead, CryptoProvider, DefaultProvider};
+pub use crate::diff::VaultDiff;
+pub use crate::env::{env_var_name, EnvCollision};
+pub use crate::errors::Error;
+pub use crate::events::Event;
+pub use crate::files::FileMetadata;
+pub use crate::format::VaultFormat;
+pub use crate::kdf::{Digest, Kdf};
+pub use crate::merge::MergeStrategy;
+pub use crate::metadata::SecretMetadata;
+pub use crate::namespace::ScopedSecrets;
+pub use crate::password::PasswordPolicy;
+pub use crate::readonly::ReadOnlySecretsManager;
+pub use crate::secret::{Secret, SecretString};
+pub use crate::serial::{BinaryDeserializable, BinarySerializable, Json};
+#[cfg(feature = "s3")]
+pub use crate::storage::S3Storage;
+#[cfg(feature = "sqlite")]
+pub use crate::storage::SqliteStorage;
+pub use crate::storage::{FileStorage, VaultStorage};
+#[cfg(feature = "notify")]
+pub use crate::watch::WatchedVault;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "recipients")]
+use std::convert::TryInto;
 use std::fs::File;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
 /// Used to specify where encryption/decryption keys should be loaded from
 pub enum KeySource<'a> {
-    /// Load the keys from a binary file on-disk
+    /// Load the keys from a binary or armored (see [`SecretsManager::export_keyfile_armored()`])
+    /// keyfile on-disk
     File(&'a Path),
+    /// Load the keys from a keyfile protected by `passphrase`, as exported by
+    /// [`SecretsManager::export_keyfile_with_password()`]
+    ProtectedFile { path: &'a Path, passphrase: &'a str },
+    /// Load the keys from an in-memory buffer with the same layout as a keyfile on-disk
+    Buffer(&'a [u8]),
+    /// Load the keys from a stream with the same layout as a keyfile on-disk, reading exactly
+    /// as many bytes as a keyfile contains
+    Reader(&'a mut dyn Read),
+    /// Reconstruct the keys from the key share files at the specified paths, as created by
+    /// [`SecretsManager::export_keys_split()`]. At least as many distinct shares as the
+    /// threshold chosen when splitting the keys must be supplied.
+    Shares(&'a [PathBuf]),
     /// Derive keys from the specified password
     Password(&'a str),
+    /// Derive keys from the specified password, using the specified KDF if creating a new
+    /// vault or rotating keys. Existing vaults are always unlocked with the KDF recorded in the
+    /// vault, so this is equivalent to [`KeySource::Password`] when loading a vault.
+    PasswordWithKdf(&'a str, Kdf),
+    /// Derive keys from the password stored in the specified environment variable
+    EnvPassword(&'a str),
+    /// Load the keys from the specified environment variable, containing the base64-encoded
+    /// contents of a keyfile
+    EnvKeyBase64(&'a str),
+    /// Derive keys from a password obtained by invoking the specified callback, which is only
+    /// called once the password is actually needed to unlock or create the vault
+    Prompt(Box<dyn Fn() -> Result<String, Error> + 'a>),
+    /// Load the keys from an age-encrypted (binary or armored) keyfile at `path`, decrypted
+    /// with the identities in the age identity file at `identity`
+    #[cfg(feature = "age")]
+    AgeFile { path: &'a Path, identity: &'a Path },
+    /// Load the keys from an OpenPGP-encrypted (binary or armored) keyfile on-disk, decrypted
+    /// by shelling out to `gpg`
+    #[cfg(feature = "gpg")]
+    GpgFile(&'a Path),
+    /// Protect the vault with keys generated by and wrapped with the AWS KMS key `key_id` (a key
+    /// ID, ARN or alias), storing the wrapped keys in the vault. Credentials and the region are
+    /// read from the standard `AWS_*` environment variables.
+    #[cfg(feature = "aws-kms")]
+    AwsKms { key_id: &'a str },
+    /// Protect the vault with new keys wrapped by the RSA key `key_name` in the Azure Key Vault
+    /// or Managed HSM at `vault_url`, storing the wrapped keys in the vault. Requests are
+    /// authorized with `AZURE_ACCESS_TOKEN` if set, or else with the managed identity of the VM.
+    #[cfg(feature = "azure")]
+    AzureKeyVault {
+        vault_url: &'a str,
+        key_name: &'a str,
+    },
+    /// Protect the vault with new keys wrapped by the Google Cloud KMS key `key_name` (of the
+    /// form `projects/*/locations/*/keyRings/*/cryptoKeys/*`), storing the wrapped keys in the
+    /// vault. Requests are authorized with `GOOGLE_OAUTH_ACCESS_TOKEN` if set, or else with the
+    /// default service account of the GCE instance.
+    #[cfg(feature = "gcp")]
+    GcpKms { key_name: &'a str },
+    /// Protect the vault with new keys wrapped by the key `key` of the transit secrets engine of
+    /// the HashiCorp Vault server at `addr` (e.g. `https://vault.example.com:8200`), authorized
+    /// with the Vault token `token`. The wrapped keys are stored in the vault.
+    #[cfg(feature = "vault-transit")]
+    VaultTransit {
+        addr: &'a str,
+        key: &'a str,
+        token: &'a str,
+    },
+    /// Derive keys from a signature over a vault-specific challenge made by the key held in
+    /// ssh-agent whose comment or `SHA256:` fingerprint matches `identity`. Only Ed25519 and
+    /// RSA keys are supported, as other key types do not produce deterministic signatures.
+    #[cfg(all(feature = "ssh-agent", unix))]
+    SshAgent { identity: &'a str },
+    /// Load the keys from the platform credential store entry identified by `service` and
+    /// `account`, as stored by [`SecretsManager::export_keys_to_keychain()`]
+    #[cfg(feature = "keychain")]
+    OsKeychain { service: &'a str, account: &'a str },
+    /// Derive keys from an HMAC-SHA256 over a vault-specific challenge computed by the secret
+    /// key labelled `label` in the token in slot `slot` of the PKCS#11 module (shared library)
+    /// at `module`, logging in to the token with `pin`. The HMAC key never leaves the token.
+    #[cfg(feature = "pkcs11")]
+    Pkcs11 {
+        module: &'a Path,
+        slot: u64,
+        pin: &'a str,
+        label: &'a str,
+    },
+    /// Load the keys from a keyfile sealed to this machine's TPM 2.0 with
+    /// [`SecretsManager::export_keyfile_tpm()`], unsealed by shelling out to tpm2-tools
+    #[cfg(feature = "tpm")]
+    Tpm { path: &'a Path },
+    /// Derive keys from the response of the YubiKey HMAC-SHA1 challenge-response configuration
+    /// in `slot` (1 or 2) to a vault-specific challenge, combined with `password` (if any) and
+    /// stretched with the vault's KDF. Challenge-response is performed by `ykchalresp`.
+    #[cfg(feature = "yubikey")]
+    YubikeyChallengeResponse { slot: u8, password: Option<&'a str> },
+    /// Unlock the keys encrypted to one of the vault's recipients with that recipient's X25519
+    /// private key (see [`SecretsManager::add_recipient()`]). A vault newly protected by this
+    /// key source gets new keys, encrypted to this identity as its only recipient.
+    #[cfg(feature = "recipients")]
+    X25519Identity(&'a [u8; 32]),
     /// Generate new keys from a secure RNG
     Generate,
 }
 
+/// A decrypted secret, wiped from memory when dropped.
+type Plaintext = Zeroizing<Vec<u8>>;
+
 /// The primary interface used for interacting with the SecureStore.
-pub struct SecretsManager {
+///
+/// All cryptographic operations are performed by the [`CryptoProvider`] `C`, which defaults to
+/// [`DefaultProvider`]. Use [`SecretsManager::builder()`] to create or load a vault with a
+/// different provider.
+pub struct SecretsManager<C: CryptoProvider = DefaultProvider> {
     vault: Vault,
-    path: PathBuf,
+    /// Where the vault is saved to, or `None` for vaults held only in memory
+    storage: Option<Box<dyn VaultStorage>>,
     keys: Keys,
+    lock: Option<VaultLock>,
+    /// Whether saving the vault is forbidden
+    readonly: bool,
+    /// Whether (and how) pending changes are saved when dropped
+    autosave: Option<OnSaveFailure>,
+    /// The size above which secrets are compressed before they are encrypted
+    compression_threshold: Option<usize>,
+    /// Whether secret names are hashed when traced
+    hash_traced_names: bool,
+    /// The hooks called with each access to the vault
+    hooks: Vec<EventHook>,
+    /// Whether vaults without a sentinel or MAC are accepted when (re)loaded
+    allow_unauthenticated: bool,
+    provider: PhantomData<C>,
 }
 
 impl SecretsManager {
     /// Creates a new vault on-disk at path `p` and loads it in a new instance
     /// of `SecretsManager`.
     pub fn new<P: AsRef<Path>>(path: P, key_source: KeySource) -> Result<Self, Error> {
-        let path = path.as_ref();
+        Self::builder().create(path, key_source)
+    }
 
-        let vault = Vault::new();
-        Ok(SecretsManager {
-            keys: key_source.extract_keys(&vault.iv)?,
-            path: PathBuf::from(path),
-            vault,
-        })
+    /// Creates a new vault held only in memory, which is never written to the filesystem. Use
+    /// [`SecretsManager::to_bytes()`] to serialize the vault for storage elsewhere (e.g. in a
+    /// database column) and [`SecretsManager::from_bytes()`] to load it again.
+    pub fn new_in_memory(key_source: KeySource) -> Result<Self, Error> {
+        Self::builder().create_in_memory(key_source)
+    }
+
+    /// Loads a vault held only in memory from its serialized form, as returned by
+    /// [`SecretsManager::to_bytes()`] or read from a vault file.
+    pub fn from_bytes(bytes: &[u8], key_source: KeySource) -> Result<Self, Error> {
+        Self::builder().load_from_bytes(bytes, key_source)
+    }
+
+    /// Loads a vault by reading its serialized form from `reader` (e.g. stdin, a section of a
+    /// larger file or an HTTP response body) until EOF. Like vaults loaded with
+    /// [`SecretsManager::from_bytes()`], the vault is held only in memory and may be written
+    /// back out with [`SecretsManager::save_to_writer()`].
+    pub fn load_from_reader<R: Read>(reader: R, key_source: KeySource) -> Result<Self, Error> {
+        Self::builder().load_from_reader(reader, key_source)
+    }
+
+    /// Loads an existing vault on-disk for reading only, returning a
+    /// [`ReadOnlySecretsManager`] which cannot modify or save the vault.
+    pub fn load_readonly<P: AsRef<Path>>(
+        path: P,
+        key_source: KeySource,
+    ) -> Result<ReadOnlySecretsManager, Error> {
+        Self::builder().load_readonly(path, key_source)
+    }
+
+    /// Returns a [`SecretsManagerBuilder`] to customize the creation or loading of a vault.
+    pub fn builder<'a>() -> SecretsManagerBuilder<'a> {
+        SecretsManagerBuilder::new()
     }
 
     /// Creates a new instance of `SecretsManager` referencing an existing vault
     /// located on-disk.
     pub fn load<P: AsRef<Path>>(path: P, key_source: KeySource) -> Result<Self, Error> {
+        Self::builder().load(path, key_source)
+    }
+
+    /// Creates a new instance of `SecretsManager` referencing an existing vault located
+    /// on-disk, holding an exclusive advisory lock on the vault until the `SecretsManager` is
+    /// dropped. Blocks until any other process or instance holding the lock releases it.
+    ///
+    /// Locking is cooperative: it only protects against lost updates between instances that
+    /// were all opened with `load_locked()` or [`SecretsManager::try_load_locked()`].
+    pub fn load_locked<P: AsRef<Path>>(path: P, key_source: KeySource) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let lock = VaultLock::acquire(path)?;
+        Self::load_with_lock(path, key_source, Some(lock), false)
+    }
+
+    /// Behaves like [`SecretsManager::load_locked()`] but returns [`Error::VaultLocked`]
+    /// instead of blocking if the vault is already locked.
+    pub fn try_load_locked<P: AsRef<Path>>(path: P, key_source: KeySource) -> Result<Self, Error> {
         let path = path.as_ref();
+        let lock = VaultLock::try_acquire(path)?;
+        Self::load_with_lock(path, key_source, Some(lock), false)
+    }
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    fn load_with_lock(
+        path: &Path,
+        key_source: KeySource,
+        lock: Option<VaultLock>,
+        allow_unauthenticated: bool,
+    ) -> Result<Self, Error> {
+        let storage = Box::new(FileStorage::new(path));
+        let mut sman = Self::load_from(storage, key_source, allow_unauthenticated)?;
+        sman.lock = lock;
+        Ok(sman)
+    }
+
+    /// Loads and unlocks the vault held in `storage`.
+    pub(crate) fn load_from(
+        storage: Box<dyn VaultStorage>,
+        key_source: KeySource,
+        allow_unauthenticated: bool,
+    ) -> Result<Self, Error> {
+        let _span = trace::span!("securestore.load");
+        let vault = Vault::parse(&storage.load()?)?;
+        let mut sman = Self::unlock(vault, key_source, allow_unauthenticated)?;
+        sman.storage = Some(storage);
+        Ok(sman)
+    }
 
-        let vault = Vault::from_file(path)?;
+    /// Unlocks `vault` with `key_source`, verifying that the keys are correct and that the
+    /// vault has not been tampered with. Vaults without a sentinel or MAC are only accepted if
+    /// `allow_unauthenticated` is set.
+    pub(crate) fn unlock(
+        mut vault: Vault,
+        key_source: KeySource,
+        allow_unauthenticated: bool,
+    ) -> Result<Self, Error> {
+        let _span = trace::span!("securestore.unlock");
+        let keys = key_source.unlock::<C>(&vault)?;
+        vault.verify_sentinel::<C>(&keys, allow_unauthenticated)?;
+        vault.verify_mac::<C>(&keys, allow_unauthenticated)?;
+        // Unauthenticated vaults are given a sentinel, so that they require a MAC once saved
+        if vault.sentinel.is_none() {
+            vault.seal_sentinel::<C>(&keys);
+        }
         Ok(SecretsManager {
-            keys: key_source.extract_keys(&vault.iv)?,
-            path: PathBuf::from(path),
+            keys,
+            storage: None,
             vault,
+            lock: None,
+            readonly: false,
+            autosave: None,
+            compression_threshold: None,
+            hash_traced_names: false,
+            hooks: Vec::new(),
+            allow_unauthenticated,
+            provider: PhantomData,
         })
     }
 
-    /// Saves changes to the underlying vault specified by the path supplied during
-    /// construction of this `SecretsManager` instance.
+    /// Returns `true` if this instance holds an advisory lock on its vault.
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Saves changes to the underlying vault specified by the path (or [`VaultStorage`])
+    /// supplied during construction of this `SecretsManager` instance. Returns
+    /// [`Error::InMemoryVault`] for vaults held only in memory, which must be serialized with
+    /// [`to_bytes()`](Self::to_bytes) instead.
+    ///
+    /// Nothing is written if the vault has not changed since it was loaded or last saved (see
+    /// [`is_dirty()`](Self::is_dirty)), so saving an unchanged vault does not touch the file.
     pub fn save(&self) -> Result<(), Error> {
-        self.vault.save(&self.path)
+        let storage = self.storage()?;
+        if !self.vault.is_dirty() {
+            return Ok(());
+        }
+
+        let _span = trace::span!("securestore.save");
+        storage.store(&self.to_bytes()?)?;
+        self.vault.mark_clean();
+        self.emit(Event::VaultSaved);
+        Ok(())
+    }
+
+    /// The storage the vault is saved to, if it may be saved.
+    fn storage(&self) -> Result<&dyn VaultStorage, Error> {
+        match &self.storage {
+            _ if self.readonly => Err(Error::ReadOnlyVault),
+            Some(storage) => Ok(storage.as_ref()),
+            None => Err(Error::InMemoryVault),
+        }
+    }
+
+    /// Returns `true` if secrets, keys or recipients have been added, removed or changed since
+    /// the vault was loaded or last saved. Newly created vaults are dirty until first saved.
+    pub fn is_dirty(&self) -> bool {
+        self.vault.is_dirty()
+    }
+
+    /// Compresses secrets larger than `threshold` bytes with zstd before they are encrypted,
+    /// from the next time they are set (or re-encrypted); `None` (the default) disables
+    /// compression. Secrets which do not shrink are stored uncompressed. Compressed secrets
+    /// are decompressed transparently when retrieved, whatever the threshold.
+    ///
+    /// The length of the ciphertext reveals how well a compressed secret compresses, so
+    /// compression should not be used for secrets mixing attacker-controlled data with other
+    /// secret data.
+    #[cfg(feature = "zstd")]
+    pub fn set_compression_threshold(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    /// Returns the format the vault is serialized in.
+    pub fn format(&self) -> VaultFormat {
+        self.vault.format
+    }
+
+    /// Changes the format the vault is serialized in from the next time it is saved. The
+    /// secrets are not re-encrypted.
+    pub fn set_format(&mut self, format: VaultFormat) {
+        if self.vault.format != format {
+            self.vault.format = format;
+            self.vault.mark_dirty();
+        }
+    }
+
+    /// Serializes the vault, in the same format as it is saved on-disk, for storage outside of
+    /// the filesystem. The secrets remain encrypted.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.vault.to_bytes::<C>(&self.keys)
+    }
+
+    /// Writes the serialized vault, as returned by [`to_bytes()`](Self::to_bytes), to
+    /// `writer`. The writer is flushed but not closed, so the vault may be followed by other
+    /// data.
+    pub fn save_to_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(&self.to_bytes()?).map_err(Error::Io)?;
+        writer.flush().map_err(Error::Io)
     }
 
     /// Exports the private key(s) resident in memory to a path on-disk. Note that
@@ -71,66 +440,778 @@ impl SecretsManager {
         self.keys.export(path)
     }
 
+    /// Exports the private key(s) resident in memory to a path on-disk, encrypted with a key
+    /// derived from `passphrase`, so that the keyfile alone is not enough to unlock the vault.
+    /// The keyfile may subsequently be loaded with [`KeySource::ProtectedFile`].
+    pub fn export_keyfile_with_password<P: AsRef<Path>>(
+        &self,
+        path: P,
+        passphrase: &str,
+    ) -> Result<(), Error> {
+        protected::export::<C>(&self.keys.combined()[..], path.as_ref(), passphrase)
+    }
+
+    /// Exports the private key(s) resident in memory to a path on-disk in a labeled,
+    /// base64-armored text format that is easier to paste into CI secrets or review than the
+    /// binary format written by [`export_keyfile()`](Self::export_keyfile). Armored keyfiles
+    /// are loaded with [`KeySource::File`], same as binary ones.
+    pub fn export_keyfile_armored<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        self.keys.export_armored(path)
+    }
+
+    /// Splits the private key(s) resident in memory into `n` shares of which any `k` suffice to
+    /// reconstruct the keys (with [`KeySource::Shares`]), writing the shares to files named
+    /// `share-<i>.key` in the directory `dir` and returning their paths. Fewer than `k` shares
+    /// reveal nothing about the keys.
+    pub fn export_keys_split<P: AsRef<Path>>(
+        &self,
+        n: u8,
+        k: u8,
+        dir: P,
+    ) -> Result<Vec<PathBuf>, Error> {
+        shamir::split::<C>(&self.keys.combined(), n, k, dir.as_ref())
+    }
+
+    /// Stores the private key(s) resident in memory in the platform credential store (the macOS
+    /// Keychain, Windows Credential Manager or Secret Service) under `service` and `account`,
+    /// from where they may subsequently be loaded with [`KeySource::OsKeychain`].
+    #[cfg(feature = "keychain")]
+    pub fn export_keys_to_keychain(&self, service: &str, account: &str) -> Result<(), Error> {
+        sources::keychain::store(service, account, &self.keys.combined()[..])
+    }
+
+    /// Exports the private key(s) resident in memory to a keyfile on-disk sealed to this
+    /// machine's TPM 2.0, which may subsequently be loaded with [`KeySource::Tpm`]. If `pcrs`
+    /// is specified (in tpm2-tools syntax, e.g. `sha256:0,7`), the keys can only be unsealed
+    /// while the selected PCRs retain their current values.
+    #[cfg(feature = "tpm")]
+    pub fn export_keyfile_tpm<P: AsRef<Path>>(
+        &self,
+        path: P,
+        pcrs: Option<&str>,
+    ) -> Result<(), Error> {
+        sources::tpm::seal(&self.keys.combined()[..], path.as_ref(), pcrs)
+    }
+
     /// Decrypts and retrieves a single secret from the loaded store. If the secret
-    /// cannot be found, returns [`Option::None`].
+    /// cannot be found, returns [`Error::SecretNotFound`]; if it cannot be decrypted with the
+    /// loaded keys, returns [`Error::MacMismatch`] or [`Error::DecryptionFailure`]. Secrets
+    /// past their [expiry](Self::set_with_expiry) return [`Error::SecretExpired`].
     pub fn retrieve<T: BinaryDeserializable>(&self, name: &str) -> Result<T, Error> {
-        match self.vault.data.get(name) {
-            None => Err(Error::SecretNotFound),
+        self.check_expiry(name)?;
+        match self.vault.secrets.get(name) {
+            None => Err(Error::SecretNotFound(name.to_string())),
             Some(blob) => {
-                let decrypted = blob.decrypt(&self.keys)?;
-                Ok(T::deserialize(decrypted))
+                let decrypted = blob.decrypt::<C>(&self.keys, self.vault.scheme())?;
+                self.emit(Event::SecretRead { name });
+                // Ownership of the plaintext passes to `T`, which is responsible for wiping it
+                T::deserialize(decrypted)
             }
         }
     }
 
+    /// Decrypts and retrieves a single UTF-8 secret from the loaded store, wrapped in a
+    /// [`SecretString`] which is redacted when formatted and zeroized when dropped. If the
+    /// secret cannot be found, returns [`Error::SecretNotFound`].
+    pub fn retrieve_secret(&self, name: &str) -> Result<SecretString, Error> {
+        self.retrieve(name)
+    }
+
     /// Adds a new secret or replaces an existing secret identified by `name` to the store.
-    pub fn set<T: BinarySerializable>(&mut self, name: &str, value: T) -> () {
-        let encrypted = EncryptedBlob::encrypt(&self.keys, &T::serialize(&value));
-        self.vault.data.insert(name.to_string(), encrypted);
+    ///
+    /// Every call encrypts the secret under its own fresh random IV (or nonce), stored
+    /// alongside the ciphertext, so identical values never produce identical ciphertext.
+    pub fn set<T: BinarySerializable>(&mut self, name: &str, value: T) {
+        let plaintext = Zeroizing::new(T::serialize(&value));
+        let encrypted = EncryptedBlob::encrypt_compressed::<C>(
+            &self.keys,
+            self.vault.scheme(),
+            &plaintext,
+            self.compression_threshold,
+        );
+        self.insert(name, encrypted);
+    }
+
+    /// Adds the secret identified by `name` only if no secret of that name exists, returning
+    /// `true` if it was added and `false` if an existing secret was left unchanged.
+    pub fn set_if_absent<T: BinarySerializable>(&mut self, name: &str, value: T) -> bool {
+        if self.vault.secrets.contains_key(name) {
+            return false;
+        }
+        self.set(name, value);
+        true
+    }
+
+    /// Replaces the existing secret identified by `name`, returning its previous value. If the
+    /// secret cannot be found, returns [`Error::SecretNotFound`] without adding it; if the
+    /// previous value cannot be decrypted, the secret is left unchanged.
+    pub fn replace<T, O>(&mut self, name: &str, value: T) -> Result<O, Error>
+    where
+        T: BinarySerializable,
+        O: BinaryDeserializable,
+    {
+        let previous = self.retrieve(name)?;
+        self.set(name, value);
+        Ok(previous)
+    }
+
+    /// Retrieves the secret identified by `name` if it exists, and otherwise generates it with
+    /// `generate` and adds it to the store, e.g. to create a signing key the first time an
+    /// application starts. The new secret is not persisted until [`save()`](Self::save) is
+    /// called.
+    pub fn retrieve_or_insert_with<T, F>(&mut self, name: &str, generate: F) -> Result<T, Error>
+    where
+        T: BinarySerializable + BinaryDeserializable,
+        F: FnOnce() -> T,
+    {
+        if self.vault.secrets.contains_key(name) {
+            return self.retrieve(name);
+        }
+        let value = generate();
+        self.set_bytes(name, &Zeroizing::new(value.serialize()));
+        Ok(value)
+    }
+
+    /// Decrypts and retrieves a single binary secret from the loaded store, without any
+    /// conversion of the decrypted payload.
+    pub fn retrieve_bytes(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.retrieve(name)
+    }
+
+    /// Adds a new binary secret or replaces an existing secret identified by `name` to the store.
+    pub fn set_bytes(&mut self, name: &str, value: &[u8]) {
+        self.set(name, value)
+    }
+
+    /// Removes the secret identified by `name` from the store. If the secret
+    /// cannot be found, returns [`Error::SecretNotFound`].
+    pub fn remove(&mut self, name: &str) -> Result<(), Error> {
+        self.vault
+            .secrets
+            .remove(name)
+            .ok_or_else(|| Error::SecretNotFound(name.to_string()))?;
+        self.forget(name);
+        self.vault.mark_dirty();
+        self.emit(Event::SecretRemoved { name });
+        Ok(())
+    }
+
+    /// Discards everything recorded about the secret `name` besides its value.
+    fn forget(&mut self, name: &str) {
+        self.vault.files.remove(name);
+        self.vault.metadata.remove(name);
+        self.vault.descriptions.remove(name);
+        self.vault.tags.remove(name);
+        self.vault.history.remove(name);
+        self.vault.expiry.remove(name);
+    }
+
+    /// Renames the secret `old` to `new`, keeping its encrypted value and metadata as they are,
+    /// so the secret is never decrypted. Hooks are told that `old` was removed and `new` was
+    /// written. If `old` cannot be found, returns
+    /// [`Error::SecretNotFound`]; if a secret named `new` already exists, returns
+    /// [`Error::SecretExists`] and leaves both secrets unchanged.
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        if !self.vault.secrets.contains_key(old) {
+            return Err(Error::SecretNotFound(old.to_string()));
+        }
+        if self.vault.secrets.contains_key(new) {
+            return Err(Error::SecretExists(new.to_string()));
+        }
+        self.discard_tombstone(new);
+
+        fn move_entry<T>(map: &mut BTreeMap<String, T>, old: &str, new: &str) {
+            if let Some(value) = map.remove(old) {
+                map.insert(new.to_string(), value);
+            }
+        }
+        move_entry(&mut self.vault.secrets, old, new);
+        move_entry(&mut self.vault.files, old, new);
+        move_entry(&mut self.vault.metadata, old, new);
+        move_entry(&mut self.vault.descriptions, old, new);
+        move_entry(&mut self.vault.tags, old, new);
+        move_entry(&mut self.vault.history, old, new);
+        move_entry(&mut self.vault.expiry, old, new);
+        self.vault.mark_dirty();
+        self.emit(Event::SecretRemoved { name: old });
+        self.emit(Event::SecretWritten { name: new });
+        Ok(())
+    }
+
+    /// Replaces the keys used to protect the store with keys loaded from `new_source`,
+    /// re-encrypting every secret with the new keys. A new vault IV is generated so that
+    /// keys derived from a password differ even if the same password is used again.
+    ///
+    /// All secrets are decrypted before any changes are made, so the store is left untouched
+    /// if any secret fails to decrypt with the current keys.
+    pub fn rotate_keys(&mut self, new_source: KeySource) -> Result<(), Error> {
+        let scheme = self.vault.scheme();
+        let decrypted = self.decrypt_all()?;
+        let descriptions = self.decrypt_descriptions()?;
+
+        let mut header = Vault::new::<C>();
+        let keys = new_source.protect::<C>(&mut header, &Kdf::default(), None)?;
+        let history = self.reencrypt_history(&keys, scheme)?;
+        let tombstones = self.reencrypt_tombstones(&keys, scheme)?;
+
+        self.vault.secrets = decrypted
+            .into_iter()
+            .map(|(name, secret, chunked)| {
+                let blob = self.reencrypt(&keys, scheme, &secret, chunked);
+                (name, blob)
+            })
+            .collect();
+        Description::seal_all::<C>(&mut self.vault.descriptions, &keys, scheme, descriptions);
+        self.vault.history = history;
+        self.vault.tombstones = tombstones;
+        self.vault.iv = header.iv;
+        self.vault.seal_sentinel::<C>(&keys);
+        self.vault.kdf = header.kdf;
+        self.vault.wrapped_key = header.wrapped_key;
+        // Keys encrypted to the existing recipients are re-encrypted with the new keys
+        #[cfg(feature = "recipients")]
+        for recipient in &self.vault.recipients {
+            if !header
+                .recipients
+                .iter()
+                .any(|r| r.public_key == recipient.public_key)
+            {
+                let public_key = recipient.public_key[..]
+                    .try_into()
+                    .map_err(|_| Error::InvalidRecipient)?;
+                let wrapped = sources::x25519::wrap::<C>(public_key, &keys.combined()[..])?;
+                header.recipients.push(wrapped);
+            }
+        }
+        header
+            .recipients
+            .sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        self.vault.recipients = header.recipients;
+        self.vault.mark_dirty();
+        self.keys = keys;
+
+        Ok(())
+    }
+
+    /// Migrates the vault to the latest vault format, returning `true` if any changes were
+    /// made. Vaults written in older formats remain readable, but are saved in the format they
+    /// were loaded in until upgraded. Changes are not persisted until [`save()`](Self::save)
+    /// is called.
+    ///
+    /// Upgrading never changes the keys, KDF or scheme the vault is protected with; use
+    /// [`upgrade_scheme()`](Self::upgrade_scheme) to migrate the secrets to another scheme.
+    pub fn upgrade(&mut self) -> Result<bool, Error> {
+        Ok(self.vault.migrate())
+    }
+
+    /// Re-encrypts every secret in the store with `scheme`, which is recorded in the vault. As
+    /// with [`rotate_keys()`](Self::rotate_keys), the store is left untouched if any secret
+    /// fails to decrypt.
+    pub fn upgrade_scheme(&mut self, scheme: Scheme) -> Result<(), Error> {
+        let decrypted = self.decrypt_all()?;
+        let descriptions = self.decrypt_descriptions()?;
+        let history = self.reencrypt_history(&self.keys, scheme)?;
+        let tombstones = self.reencrypt_tombstones(&self.keys, scheme)?;
+
+        self.vault.secrets = decrypted
+            .into_iter()
+            .map(|(name, secret, chunked)| {
+                let blob = self.reencrypt(&self.keys, scheme, &secret, chunked);
+                (name, blob)
+            })
+            .collect();
+        Description::seal_all::<C>(
+            &mut self.vault.descriptions,
+            &self.keys,
+            scheme,
+            descriptions,
+        );
+        self.vault.history = history;
+        self.vault.tombstones = tombstones;
+        self.vault.scheme = Some(scheme);
+        if self.vault.sentinel.is_some() {
+            self.vault.seal_sentinel::<C>(&self.keys);
+        }
+        self.vault.mark_dirty();
+
+        Ok(())
+    }
+
+    /// Adds the newly encrypted value of the secret `name` to the store, replacing any existing
+    /// value (which is kept in its history, if enabled) and the details of any file it was set
+    /// from.
+    fn insert(&mut self, name: &str, blob: EncryptedBlob) {
+        self.discard_tombstone(name);
+        if let Some(previous) = self.vault.secrets.insert(name.to_string(), blob) {
+            self.push_version(name, previous);
+        }
+        self.vault.files.remove(name);
+        self.vault.expiry.remove(name);
+        self.touch(name);
+        self.vault.mark_dirty();
+        self.emit(Event::SecretWritten { name });
+    }
+
+    /// Decrypts every secret in the store with the current keys, noting which secrets were
+    /// encrypted in chunks.
+    fn decrypt_all(&self) -> Result<Vec<(String, Plaintext, bool)>, Error> {
+        let scheme = self.vault.scheme();
+        let mut decrypted = Vec::with_capacity(self.vault.secrets.len());
+        for (name, blob) in &self.vault.secrets {
+            let secret = Zeroizing::new(blob.decrypt::<C>(&self.keys, scheme)?);
+            decrypted.push((name.clone(), secret, !blob.chunks.is_empty()));
+        }
+
+        Ok(decrypted)
+    }
+
+    /// Decrypts `blob` with the current keys and encrypts it again with `keys` and `scheme`.
+    fn reencrypt_blob(
+        &self,
+        blob: &EncryptedBlob,
+        keys: &Keys,
+        scheme: Scheme,
+    ) -> Result<EncryptedBlob, Error> {
+        let value = Zeroizing::new(blob.decrypt::<C>(&self.keys, self.vault.scheme())?);
+        Ok(self.reencrypt(keys, scheme, &value, !blob.chunks.is_empty()))
+    }
+
+    fn reencrypt(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+        secret: &[u8],
+        chunked: bool,
+    ) -> EncryptedBlob {
+        match chunked {
+            true => EncryptedBlob::encrypt_chunked::<C>(keys, scheme, secret),
+            false => EncryptedBlob::encrypt_compressed::<C>(
+                keys,
+                scheme,
+                secret,
+                self.compression_threshold,
+            ),
+        }
+    }
+
+    /// Changes the password protecting a password-based store from `old` to `new`,
+    /// re-encrypting all secrets with keys derived from `new` and a fresh IV, then
+    /// rewriting the vault on-disk. The KDF recorded in the vault is preserved. Returns
+    /// [`Error::InvalidPassword`] without making any changes if `old` is not the password the
+    /// store was unlocked with, or the error [`save()`](Self::save) would return if the store
+    /// is read-only or in-memory. If writing the vault fails, the store is left unlocked with
+    /// `new`, and `save()` may be retried.
+    pub fn change_password(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        let kdf = self.vault.kdf.clone();
+        let old_keys = KeySource::Password(old).extract_keys::<C>(&self.vault.iv, kdf.as_ref())?;
+        if !old_keys.ct_eq::<C>(&self.keys) {
+            return Err(Error::InvalidPassword);
+        }
+        // Don't rotate to keys that could never be saved
+        self.storage()?;
+
+        let kdf = kdf.unwrap_or_else(Kdf::legacy);
+        self.rotate_keys(KeySource::PasswordWithKdf(new, kdf))?;
+        self.save()
+    }
+
+    /// Migrates a vault protected by `password` to the current default KDF
+    /// ([`Kdf::default()`]), e.g. from the PBKDF2-HMAC-SHA1 used by older vaults, re-encrypting
+    /// all secrets with keys derived from `password` and a fresh IV as with
+    /// [`rotate_keys()`](Self::rotate_keys). Returns `false` without making any changes if
+    /// the vault already uses the default KDF, or [`Error::InvalidPassword`] if `password` is
+    /// not the password the store was unlocked with. Changes are not persisted until
+    /// [`save()`](Self::save) is called.
+    pub fn upgrade_kdf(&mut self, password: &str) -> Result<bool, Error> {
+        let keys = KeySource::Password(password)
+            .extract_keys::<C>(&self.vault.iv, self.vault.kdf.as_ref())?;
+        if !keys.ct_eq::<C>(&self.keys) {
+            return Err(Error::InvalidPassword);
+        }
+        if self.vault.kdf == Some(Kdf::default()) {
+            return Ok(false);
+        }
+
+        self.rotate_keys(KeySource::PasswordWithKdf(password, Kdf::default()))?;
+        Ok(true)
+    }
+
+    /// Grants the holder of the X25519 private key corresponding to `public_key` access to the
+    /// vault, by encrypting the vault keys to `public_key`. No secrets are re-encrypted. The
+    /// recipient may then unlock the vault with [`KeySource::X25519Identity`].
+    #[cfg(feature = "recipients")]
+    pub fn add_recipient(&mut self, public_key: &[u8; 32]) -> Result<(), Error> {
+        let recipient = sources::x25519::wrap::<C>(public_key, &self.keys.combined()[..])?;
+        self.remove_recipient(public_key);
+        self.vault.recipients.push(recipient);
+        self.vault
+            .recipients
+            .sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        self.vault.mark_dirty();
+        Ok(())
+    }
+
+    /// Removes the recipient with the X25519 public key `public_key`, returning `false` if it
+    /// was not a recipient. Note that a removed recipient may have retained a copy of the vault
+    /// keys, so [`rotate_keys()`](Self::rotate_keys) should also be called to fully revoke
+    /// their access.
+    #[cfg(feature = "recipients")]
+    pub fn remove_recipient(&mut self, public_key: &[u8; 32]) -> bool {
+        let count = self.vault.recipients.len();
+        self.vault
+            .recipients
+            .retain(|r| r.public_key != public_key.as_ref());
+        if self.vault.recipients.len() == count {
+            return false;
+        }
+        self.vault.mark_dirty();
+        true
+    }
+
+    /// Returns an iterator over the X25519 public keys of the vault's recipients, in sorted order.
+    #[cfg(feature = "recipients")]
+    pub fn recipients(&self) -> impl Iterator<Item = &[u8]> {
+        self.vault.recipients.iter().map(|r| &r.public_key[..])
+    }
+
+    /// Returns an iterator over the names of all secrets in the store, in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.vault.secrets.keys().map(String::as_str)
+    }
+
+    /// Returns an iterator over the names and decrypted UTF-8 values of all secrets in the
+    /// store, in sorted order. Each secret is decrypted as the iterator reaches it, and a secret
+    /// which fails to decrypt yields an error without ending the iteration.
+    pub fn iter(&self) -> impl Iterator<Item = (String, Result<String, Error>)> + '_ {
+        let scheme = self.vault.scheme();
+        self.vault.secrets.iter().map(move |(name, blob)| {
+            let value = self
+                .check_expiry(name)
+                .and_then(|_| blob.decrypt::<C>(&self.keys, scheme))
+                .and_then(String::deserialize);
+            if value.is_ok() {
+                self.emit(Event::SecretRead { name });
+            }
+            (name.clone(), value)
+        })
+    }
+
+    /// Decrypts every secret in the store in a single pass, e.g. to load all configuration
+    /// when an application starts, returning the UTF-8 value of each secret by name. Fails with
+    /// the first error encountered if any secret cannot be decrypted.
+    pub fn retrieve_all(&self) -> Result<HashMap<String, String>, Error> {
+        self.retrieve_all_as()
+    }
+
+    /// Decrypts every binary secret in the store in a single pass, returning the value of each
+    /// secret by name. See [`retrieve_all()`](Self::retrieve_all).
+    pub fn retrieve_all_bytes(&self) -> Result<HashMap<String, Vec<u8>>, Error> {
+        self.retrieve_all_as()
+    }
+
+    fn retrieve_all_as<T: BinaryDeserializable>(&self) -> Result<HashMap<String, T>, Error> {
+        let scheme = self.vault.scheme();
+        let mut secrets = HashMap::with_capacity(self.vault.secrets.len());
+        for (name, blob) in &self.vault.secrets {
+            self.check_expiry(name)?;
+            let value = T::deserialize(blob.decrypt::<C>(&self.keys, scheme)?)?;
+            self.emit(Event::SecretRead { name });
+            secrets.insert(name.clone(), value);
+        }
+        Ok(secrets)
+    }
+
+    /// Returns the number of secrets in the store.
+    pub fn len(&self) -> usize {
+        self.vault.secrets.len()
+    }
+
+    /// Returns `true` if the store contains no secrets.
+    pub fn is_empty(&self) -> bool {
+        self.vault.secrets.is_empty()
     }
 }
 
 impl<'a> KeySource<'a> {
-    fn extract_keys(&self, iv: &Option<[u8; shared::IV_SIZE]>) -> Result<Keys, Error> {
-        match &self {
+    /// The KDF to record in a vault newly protected by this key source, falling back to
+    /// `default` for password sources that don't specify one.
+    fn new_vault_kdf(&self, default: &Kdf) -> Option<Kdf> {
+        match self {
+            KeySource::Password(_) | KeySource::EnvPassword(_) | KeySource::Prompt(_) => {
+                Some(default.clone())
+            }
+            #[cfg(feature = "yubikey")]
+            KeySource::YubikeyChallengeResponse { .. } => Some(default.clone()),
+            KeySource::PasswordWithKdf(_, kdf) => Some(kdf.clone()),
+            _ => None,
+        }
+    }
+
+    /// Loads, derives or unwraps the keys for the existing vault `vault`.
+    fn unlock<C: CryptoProvider>(self, vault: &Vault) -> Result<Keys, Error> {
+        #[cfg(feature = "recipients")]
+        if let KeySource::X25519Identity(secret) = self {
+            let key_data = sources::x25519::unwrap::<C>(secret, &vault.recipients)?;
+            return KeySource::Buffer(&key_data).extract_keys::<C>(&None, None);
+        }
+
+        if let Some(wrapped) = &vault.wrapped_key {
+            if let Some(key_data) = self.unwrap_key(wrapped)? {
+                return KeySource::Buffer(&key_data).extract_keys::<C>(&None, None);
+            }
+        }
+
+        self.extract_keys::<C>(&vault.iv, vault.kdf.as_ref())
+    }
+
+    /// Creates or derives the keys for a vault newly protected by this key source, recording
+    /// the KDF (falling back to `default_kdf` for password sources) and any wrapped keys in
+    /// `header`, which must have a fresh IV.
+    fn protect<C: CryptoProvider>(
+        self,
+        header: &mut Vault,
+        default_kdf: &Kdf,
+        policy: Option<&PasswordPolicy>,
+    ) -> Result<Keys, Error> {
+        if let Some(policy) = policy {
+            // Passwords from the environment or a prompt are only obtained once, then checked
+            match self {
+                KeySource::Password(password) | KeySource::PasswordWithKdf(password, _) => {
+                    policy.check(password)?
+                }
+                KeySource::EnvPassword(var) => {
+                    let password = Zeroizing::new(std::env::var(var).map_err(Error::EnvVar)?);
+                    return KeySource::Password(&password).protect::<C>(
+                        header,
+                        default_kdf,
+                        Some(policy),
+                    );
+                }
+                KeySource::Prompt(prompt) => {
+                    let password = Zeroizing::new(prompt()?);
+                    return KeySource::Password(&password).protect::<C>(
+                        header,
+                        default_kdf,
+                        Some(policy),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        header.kdf = self.new_vault_kdf(default_kdf);
+        header.wrapped_key = None;
+
+        #[cfg(feature = "recipients")]
+        if let KeySource::X25519Identity(secret) = self {
+            let key_data = Self::random_key_data::<C>();
+            let public_key = sources::x25519::public_key(secret);
+            header.recipients = vec![sources::x25519::wrap::<C>(&public_key, &key_data)?];
+            return KeySource::Buffer(&key_data).extract_keys::<C>(&None, None);
+        }
+
+        if let Some((key_data, wrapped)) = self.wrap_new_key::<C>()? {
+            header.wrapped_key = Some(wrapped);
+            return KeySource::Buffer(&key_data).extract_keys::<C>(&None, None);
+        }
+
+        self.extract_keys::<C>(&header.iv, header.kdf.as_ref())
+    }
+
+    /// Unwraps the keys in `wrapped` for KMS-backed key sources, returning `None` for all other
+    /// key sources.
+    // Arms are added by the features for each KMS
+    #[allow(unused_variables, clippy::match_single_binding)]
+    fn unwrap_key(&self, wrapped: &WrappedKey) -> Result<Option<KeyData>, Error> {
+        match self {
+            #[cfg(feature = "aws-kms")]
+            KeySource::AwsKms { key_id } => sources::aws_kms::unwrap(key_id, wrapped).map(Some),
+            #[cfg(feature = "azure")]
+            KeySource::AzureKeyVault {
+                vault_url,
+                key_name,
+            } => sources::azure_key_vault::unwrap(vault_url, key_name, wrapped).map(Some),
+            #[cfg(feature = "gcp")]
+            KeySource::GcpKms { key_name } => sources::gcp_kms::unwrap(key_name, wrapped).map(Some),
+            #[cfg(feature = "vault-transit")]
+            KeySource::VaultTransit { addr, key, token } => {
+                sources::vault_transit::unwrap(addr, key, token, wrapped).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Generates new keys and wraps them for KMS-backed key sources, returning `None` for all
+    /// other key sources.
+    #[allow(clippy::match_single_binding, clippy::extra_unused_type_parameters)]
+    fn wrap_new_key<C: CryptoProvider>(&self) -> Result<Option<(KeyData, WrappedKey)>, Error> {
+        match self {
+            #[cfg(feature = "aws-kms")]
+            KeySource::AwsKms { key_id } => sources::aws_kms::generate(key_id).map(Some),
+            #[cfg(feature = "azure")]
+            KeySource::AzureKeyVault {
+                vault_url,
+                key_name,
+            } => {
+                let key_data = Self::random_key_data::<C>();
+                let wrapped = sources::azure_key_vault::wrap(vault_url, key_name, &key_data)?;
+                Ok(Some((key_data, wrapped)))
+            }
+            #[cfg(feature = "gcp")]
+            KeySource::GcpKms { key_name } => {
+                let key_data = Self::random_key_data::<C>();
+                let wrapped = sources::gcp_kms::wrap(key_name, &key_data)?;
+                Ok(Some((key_data, wrapped)))
+            }
+            #[cfg(feature = "vault-transit")]
+            KeySource::VaultTransit { addr, key, token } => {
+                let key_data = Self::random_key_data::<C>();
+                let wrapped = sources::vault_transit::wrap(addr, key, token, &key_data)?;
+                Ok(Some((key_data, wrapped)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Generates the raw material for new keys from a secure RNG.
+    #[cfg(any(
+        feature = "azure",
+        feature = "gcp",
+        feature = "recipients",
+        feature = "vault-transit"
+    ))]
+    fn random_key_data<C: CryptoProvider>() -> KeyData {
+        let mut key_data = Zeroizing::new(vec![0u8; shared::KEY_COUNT * shared::KEY_LENGTH]);
+        C::random(&mut key_data);
+        key_data
+    }
+
+    /// Loads or derives the keys for a vault with the specified IV and recorded KDF, where a
+    /// `kdf` of `None` denotes [`Kdf::legacy()`].
+    fn extract_keys<C: CryptoProvider>(
+        self,
+        iv: &Option<[u8; shared::IV_SIZE]>,
+        kdf: Option<&Kdf>,
+    ) -> Result<Keys, Error> {
+        match self {
             KeySource::Generate => {
-                let mut buffer = [0u8; shared::KEY_COUNT * shared::KEY_LENGTH];
-                rand::rand_bytes(&mut buffer).expect("Key generation failure!");
+                let mut buffer = Zeroizing::new([0u8; shared::KEY_COUNT * shared::KEY_LENGTH]);
+                C::random(&mut *buffer);
 
                 Keys::import(&buffer[..])
             }
             KeySource::File(path) => {
-                match std::fs::metadata(path) {
-                    Err(e) => return Err(Error::Io(e)),
-                    Ok(attr) => {
-                        if attr.len() as usize != shared::KEY_COUNT * shared::KEY_LENGTH {
-                            return Err(Error::InvalidKeyfile);
-                        }
-                    }
-                };
+                let length = std::fs::metadata(path).map_err(Error::file(path))?.len();
+                if length as usize <= shared::KEY_COUNT * shared::KEY_LENGTH {
+                    let file = File::open(path).map_err(Error::file(path))?;
+                    return Keys::import(&file);
+                }
+                if length > shared::MAX_ARMORED_SIZE {
+                    return Err(Error::InvalidKeyfile);
+                }
+
+                let contents = Zeroizing::new(std::fs::read(path).map_err(Error::file(path))?);
+                match Keys::dearmor(&contents) {
+                    Some(key_data) => KeySource::Buffer(&key_data?).extract_keys::<C>(iv, kdf),
+                    None => Err(Error::InvalidKeyfile),
+                }
+            }
+            KeySource::ProtectedFile { path, passphrase } => {
+                let key_data = protected::import::<C>(path, passphrase)?;
+                KeySource::Buffer(&key_data).extract_keys::<C>(iv, kdf)
+            }
+            KeySource::Buffer(buffer) => {
+                if buffer.len() > shared::KEY_COUNT * shared::KEY_LENGTH {
+                    return Err(Error::InvalidKeyfile);
+                }
 
-                let file = File::open(path).map_err(Error::Io)?;
-                Keys::import(&file)
+                Keys::import(buffer)
+            }
+            KeySource::Reader(reader) => Keys::import(reader),
+            KeySource::Shares(paths) => {
+                let key_data = shamir::combine(paths)?;
+                Keys::import(&key_data[..])
             }
-            KeySource::Password(password) => {
-                use openssl::hash::MessageDigest;
-                use openssl::pkcs5::pbkdf2_hmac;
+            #[cfg(feature = "aws-kms")]
+            KeySource::AwsKms { .. } => Err(Error::MissingWrappedKey),
+            #[cfg(feature = "azure")]
+            KeySource::AzureKeyVault { .. } => Err(Error::MissingWrappedKey),
+            #[cfg(feature = "gcp")]
+            KeySource::GcpKms { .. } => Err(Error::MissingWrappedKey),
+            #[cfg(feature = "vault-transit")]
+            KeySource::VaultTransit { .. } => Err(Error::MissingWrappedKey),
+            #[cfg(feature = "recipients")]
+            KeySource::X25519Identity(_) => Err(Error::NotARecipient),
+            KeySource::EnvKeyBase64(var) => {
+                let encoded = Zeroizing::new(std::env::var(var).map_err(Error::EnvVar)?);
+                let decoded = base64::decode(encoded.trim()).map_err(|_| Error::InvalidKeyfile)?;
+                let decoded = Zeroizing::new(decoded);
 
+                KeySource::Buffer(&decoded).extract_keys::<C>(iv, kdf)
+            }
+            KeySource::EnvPassword(var) => {
+                let password = Zeroizing::new(std::env::var(var).map_err(Error::EnvVar)?);
+                KeySource::Password(&password).extract_keys::<C>(iv, kdf)
+            }
+            KeySource::Prompt(prompt) => {
+                let password = Zeroizing::new(prompt()?);
+                KeySource::Password(&password).extract_keys::<C>(iv, kdf)
+            }
+            #[cfg(feature = "age")]
+            KeySource::AgeFile { path, identity } => {
+                let key_data = sources::age::decrypt_keyfile(path, identity)?;
+                KeySource::Buffer(&key_data).extract_keys::<C>(iv, kdf)
+            }
+            #[cfg(feature = "gpg")]
+            KeySource::GpgFile(path) => {
+                let key_data = sources::gpg::decrypt_keyfile(path)?;
+                KeySource::Buffer(&key_data).extract_keys::<C>(iv, kdf)
+            }
+            #[cfg(all(feature = "ssh-agent", unix))]
+            KeySource::SshAgent { identity } => {
+                let iv = iv.as_ref().ok_or(Error::MissingVaultIV)?;
+                let key_data = sources::ssh_agent::derive_keys(identity, iv)?;
+                Keys::import(&key_data[..])
+            }
+            #[cfg(feature = "keychain")]
+            KeySource::OsKeychain { service, account } => {
+                let key_data = sources::keychain::retrieve(service, account)?;
+                KeySource::Buffer(&key_data).extract_keys::<C>(iv, kdf)
+            }
+            #[cfg(feature = "pkcs11")]
+            KeySource::Pkcs11 {
+                module,
+                slot,
+                pin,
+                label,
+            } => {
+                let iv = iv.as_ref().ok_or(Error::MissingVaultIV)?;
+                let key_data = sources::pkcs11::derive_keys(module, slot, pin, label, iv)?;
+                Keys::import(&key_data[..])
+            }
+            #[cfg(feature = "tpm")]
+            KeySource::Tpm { path } => {
+                let key_data = sources::tpm::unseal(path)?;
+                KeySource::Buffer(&key_data).extract_keys::<C>(iv, kdf)
+            }
+            #[cfg(feature = "yubikey")]
+            KeySource::YubikeyChallengeResponse { slot, password } => {
+                let challenge = iv.as_ref().ok_or(Error::MissingVaultIV)?;
+                let response = sources::yubikey::challenge_response(slot, challenge)?;
+                // The response has a fixed length, so the concatenation is unambiguous
+                let secret = Zeroizing::new(format!("{}{}", *response, password.unwrap_or("")));
+                KeySource::Password(&secret).extract_keys::<C>(iv, kdf)
+            }
+            KeySource::Password(password) | KeySource::PasswordWithKdf(password, _) => {
                 let iv = match iv {
                     None => return Err(Error::MissingVaultIV),
                     Some(x) => x,
                 };
 
-                let mut key_data = [0u8; shared::KEY_COUNT * shared::KEY_LENGTH];
-                pbkdf2_hmac(
-                    password.as_bytes(),
-                    iv,
-                    shared::PBKDF2_ROUNDS,
-                    MessageDigest::sha1(),
-                    &mut key_data,
-                )
-                .expect("PBKDF2 key generation failed!");
-
+                let key_data = match kdf {
+                    Some(kdf) => kdf.derive::<C>(password, iv)?,
+                    None => Kdf::legacy().derive::<C>(password, iv)?,
+                };
                 Keys::import(&key_data[..])
             }
         }
@@ -0,0 +1,57 @@
+//! Advisory locking of vaults shared between multiple processes.
+
+use crate::errors::Error;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+/// An exclusive advisory lock on a vault, released when dropped.
+///
+/// Saving a vault replaces the file on-disk, so the lock is taken on a separate lock file
+/// alongside the vault rather than on the vault itself.
+#[derive(Debug)]
+pub(crate) struct VaultLock {
+    _file: File,
+}
+
+impl VaultLock {
+    /// Acquires the lock for the vault at `vault_path`, blocking until it is available.
+    pub fn acquire(vault_path: &Path) -> Result<Self, Error> {
+        let (file, path) = Self::open(vault_path)?;
+        file.lock().map_err(Error::file(&path))?;
+
+        Ok(VaultLock { _file: file })
+    }
+
+    /// Acquires the lock for the vault at `vault_path`, returning [`Error::VaultLocked`] if it
+    /// is currently held by another process or `SecretsManager` instance.
+    pub fn try_acquire(vault_path: &Path) -> Result<Self, Error> {
+        let (file, path) = Self::open(vault_path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(VaultLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(Error::VaultLocked),
+            Err(TryLockError::Error(source)) => Err(Error::File { path, source }),
+        }
+    }
+
+    /// Opens the lock file for the vault at `vault_path`, returning it along with its path.
+    fn open(vault_path: &Path) -> Result<(File, PathBuf), Error> {
+        let path = Self::lock_path(vault_path);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(Error::file(&path))?;
+        Ok((file, path))
+    }
+
+    /// The lock file is never deleted, as doing so would race with other processes that have
+    /// opened but not yet locked it.
+    fn lock_path(vault_path: &Path) -> PathBuf {
+        let name = vault_path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        vault_path.with_file_name(format!(".{}.lock", name))
+    }
+}
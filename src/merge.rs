@@ -0,0 +1,119 @@
+//! Reconciliation of two divergent copies of a vault.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::{SecretsManager, VaultDiff};
+use std::collections::BTreeSet;
+use zeroize::Zeroizing;
+
+/// How [`SecretsManager::merge_from()`] resolves secrets which have different values in the two
+/// vaults being merged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MergeStrategy {
+    /// Keep the value in this vault.
+    Ours,
+    /// Take the value from the other vault.
+    Theirs,
+    /// Only merge secrets which are missing from one of the vaults, failing with
+    /// [`Error::MergeConflict`] if any secret has a different value in each.
+    Union,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Merges the secrets of `other`, e.g. a copy of the same vault from another branch, into
+    /// this vault. Secrets only present in `other` are added, while secrets present in both
+    /// vaults with different values are resolved according to `strategy`. Secrets only present
+    /// in this vault are kept, as it cannot be told whether `other` removed them or this vault
+    /// added them.
+    ///
+    /// Secrets taken from `other` are re-encrypted with the keys of this vault, so the vaults
+    /// may be protected with different keys. This vault is left unchanged if the merge fails.
+    pub fn merge_from(
+        &mut self,
+        other: &SecretsManager<C>,
+        strategy: MergeStrategy,
+    ) -> Result<(), Error> {
+        let diff = self.diff(other)?;
+        let mut names = diff.added;
+        match strategy {
+            MergeStrategy::Ours => {}
+            MergeStrategy::Theirs => names.extend(diff.changed),
+            MergeStrategy::Union if diff.changed.is_empty() => {}
+            MergeStrategy::Union => return Err(Error::MergeConflict(diff.changed)),
+        }
+
+        let scheme = other.vault.scheme();
+        let mut merged = Vec::with_capacity(names.len());
+        for name in names {
+            let blob = &other.vault.secrets[&name];
+            let value = Zeroizing::new(blob.decrypt::<C>(&other.keys, scheme)?);
+            other.emit(Event::SecretRead { name: &name });
+            merged.push((name, value));
+        }
+        for (name, value) in merged {
+            self.set_bytes(&name, &value);
+        }
+
+        Ok(())
+    }
+
+    /// Performs a three-way merge of the secrets of `other` into this vault, where `base` is
+    /// the common ancestor of both copies (as when merging branches with git). Secrets added,
+    /// changed or removed in only one of the vaults since `base` take the value of that vault,
+    /// and secrets changed identically in both are kept as they are.
+    ///
+    /// If any secret was changed differently in each vault (including changed in one and
+    /// removed in the other), fails with [`Error::MergeConflict`] listing those secrets and
+    /// leaves this vault unchanged. Secrets taken from `other` are re-encrypted with the keys
+    /// of this vault.
+    pub fn merge_three_way(
+        &mut self,
+        base: &SecretsManager<C>,
+        other: &SecretsManager<C>,
+    ) -> Result<(), Error> {
+        let ours = changed_names(base.diff(self)?);
+        let theirs = changed_names(base.diff(other)?);
+        let differing = changed_names(self.diff(other)?);
+
+        let conflicts: Vec<String> = ours
+            .intersection(&theirs)
+            .filter(|name| differing.contains(*name))
+            .cloned()
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(Error::MergeConflict(conflicts));
+        }
+
+        let scheme = other.vault.scheme();
+        let mut merged = Vec::new();
+        for name in theirs.difference(&ours) {
+            let value = match other.vault.secrets.get(name) {
+                Some(blob) => {
+                    let value = Zeroizing::new(blob.decrypt::<C>(&other.keys, scheme)?);
+                    other.emit(Event::SecretRead { name });
+                    Some(value)
+                }
+                None => None,
+            };
+            merged.push((name, value));
+        }
+        for (name, value) in merged {
+            match value {
+                Some(value) => self.set_bytes(name, &value),
+                None => self.remove(name)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// All secrets which were added, removed or changed, in sorted order.
+fn changed_names(diff: VaultDiff) -> BTreeSet<String> {
+    diff.added
+        .into_iter()
+        .chain(diff.removed)
+        .chain(diff.changed)
+        .collect()
+}
@@ -0,0 +1,69 @@
+//! Details recorded about each secret besides its value.
+//!
+//! Metadata is stored unencrypted in the vault, keyed by the name of the secret, and is covered
+//! by the vault MAC like every other field, so it cannot be altered without detection.
+
+use crate::clock;
+use crate::crypto::CryptoProvider;
+use crate::SecretsManager;
+use serde_derive::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// The details recorded about a secret, as returned by [`SecretsManager::metadata()`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SecretMetadata {
+    /// When the secret was first added to the vault.
+    #[serde(with = "unix_time")]
+    pub created: SystemTime,
+    /// When the value of the secret was last set.
+    #[serde(with = "unix_time")]
+    pub modified: SystemTime,
+}
+
+impl SecretMetadata {
+    fn new(now: SystemTime) -> Self {
+        SecretMetadata {
+            created: now,
+            modified: now,
+        }
+    }
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// The details recorded about the secret `name`, or `None` if there is no such secret.
+    /// Secrets added by older versions or other SecureStore implementations have no metadata
+    /// until they are next set.
+    pub fn metadata(&self, name: &str) -> Option<&SecretMetadata> {
+        self.vault.metadata.get(name)
+    }
+
+    /// Records that the value of the secret `name` was just set.
+    pub(crate) fn touch(&mut self, name: &str) {
+        // Timestamps are only recorded to the second
+        let now = unix_time::truncate(clock::now());
+        self.vault
+            .metadata
+            .entry(name.to_string())
+            .and_modify(|m| m.modified = now)
+            .or_insert_with(|| SecretMetadata::new(now));
+    }
+}
+
+/// Timestamps are serialized as whole seconds since the Unix epoch.
+mod unix_time {
+    use crate::clock::seconds;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub(super) fn truncate(time: SystemTime) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds(time))
+    }
+
+    pub(super) fn serialize<S: Serializer>(time: &SystemTime, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_u64(seconds(*time))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(de: D) -> Result<SystemTime, D::Error> {
+        u64::deserialize(de).map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+}
@@ -0,0 +1,115 @@
+//! Hierarchical namespaces of secrets, with `/` separating the components of their names.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::serial::{BinaryDeserializable, BinarySerializable};
+use crate::{SecretString, SecretsManager};
+
+/// A view of the secrets in a namespace, returned by [`SecretsManager::scoped()`]. Names
+/// passed to and returned by a `ScopedSecrets` are relative to the namespace, so
+/// `sman.scoped("prod").set("db/password", ...)` sets the secret `prod/db/password`.
+pub struct ScopedSecrets<'a, C: CryptoProvider> {
+    sman: &'a mut SecretsManager<C>,
+    prefix: String,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Returns an iterator over the names of all secrets in the namespace `prefix`, e.g.
+    /// `prod/db/`, in sorted order. The namespace is always a whole number of components, so
+    /// `prod` matches `prod/password` but not `production/password`.
+    pub fn keys_under<'a>(&'a self, prefix: &str) -> impl Iterator<Item = &'a str> {
+        let prefix = namespace(prefix);
+        self.vault
+            .secrets
+            .range(prefix.clone()..)
+            .map(|(name, _)| name.as_str())
+            .take_while(move |name| name.starts_with(&prefix))
+    }
+
+    /// Removes all secrets in the namespace `prefix` (see [`keys_under()`](Self::keys_under)),
+    /// returning the number of secrets removed.
+    pub fn remove_prefix(&mut self, prefix: &str) -> usize {
+        let names: Vec<String> = self.keys_under(prefix).map(str::to_string).collect();
+        for name in &names {
+            // Every name was just found in the store
+            let _ = self.remove(name);
+        }
+        names.len()
+    }
+
+    /// Returns a view of the secrets in the namespace `prefix`, through which every secret is
+    /// read and written relative to the namespace.
+    pub fn scoped(&mut self, prefix: &str) -> ScopedSecrets<'_, C> {
+        ScopedSecrets {
+            sman: self,
+            prefix: namespace(prefix),
+        }
+    }
+}
+
+impl<C: CryptoProvider> ScopedSecrets<'_, C> {
+    /// The namespace of this view, ending with `/` unless it is the root.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// See [`SecretsManager::retrieve()`].
+    pub fn retrieve<T: BinaryDeserializable>(&self, name: &str) -> Result<T, Error> {
+        self.sman.retrieve(&self.path(name))
+    }
+
+    /// See [`SecretsManager::retrieve_secret()`].
+    pub fn retrieve_secret(&self, name: &str) -> Result<SecretString, Error> {
+        self.sman.retrieve_secret(&self.path(name))
+    }
+
+    /// See [`SecretsManager::retrieve_bytes()`].
+    pub fn retrieve_bytes(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.sman.retrieve_bytes(&self.path(name))
+    }
+
+    /// See [`SecretsManager::set()`].
+    pub fn set<T: BinarySerializable>(&mut self, name: &str, value: T) {
+        let path = self.path(name);
+        self.sman.set(&path, value)
+    }
+
+    /// See [`SecretsManager::set_bytes()`].
+    pub fn set_bytes(&mut self, name: &str, value: &[u8]) {
+        let path = self.path(name);
+        self.sman.set_bytes(&path, value)
+    }
+
+    /// See [`SecretsManager::remove()`].
+    pub fn remove(&mut self, name: &str) -> Result<(), Error> {
+        let path = self.path(name);
+        self.sman.remove(&path)
+    }
+
+    /// Returns an iterator over the names of all secrets in the namespace, relative to the
+    /// namespace and in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        let length = self.prefix.len();
+        self.sman
+            .keys_under(&self.prefix)
+            .map(move |name| &name[length..])
+    }
+
+    /// Returns a view of the nested namespace `prefix`.
+    pub fn scoped(&mut self, prefix: &str) -> ScopedSecrets<'_, C> {
+        let prefix = self.path(prefix);
+        self.sman.scoped(&prefix)
+    }
+
+    fn path(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+}
+
+/// Normalizes `prefix` to end with a `/`, unless it is empty (the root namespace).
+fn namespace(prefix: &str) -> String {
+    match prefix.is_empty() || prefix.ends_with('/') {
+        true => prefix.to_string(),
+        false => format!("{}/", prefix),
+    }
+}
@@ -0,0 +1,111 @@
+//! Minimum requirements for the passwords new vaults are protected with.
+
+use crate::errors::Error;
+
+/// Requirements a password must meet to protect a new vault, enforced when set with
+/// [`SecretsManagerBuilder::password_policy()`](crate::SecretsManagerBuilder::password_policy).
+///
+/// The strength of a password is estimated from the classes of characters it contains, with
+/// repeated characters, runs such as `abc` or `321` and a list of commonly used passwords
+/// contributing little or nothing. The estimate is deliberately conservative, but cannot detect
+/// every guessable password (e.g. one reused from another site).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PasswordPolicy {
+    /// The minimum number of characters in the password.
+    pub min_length: usize,
+    /// The minimum estimated strength of the password, in bits of entropy.
+    pub min_entropy_bits: u32,
+}
+
+impl Default for PasswordPolicy {
+    /// Requires at least 12 characters and an estimated 50 bits of entropy.
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 12,
+            min_entropy_bits: 50,
+        }
+    }
+}
+
+/// Passwords which are guessed first, regardless of their apparent strength.
+const COMMON_PASSWORDS: &[&str] = &[
+    "12345678",
+    "123456789012",
+    "1q2w3e4r5t6y",
+    "iloveyou",
+    "iloveyou1234",
+    "letmein",
+    "letmein12345",
+    "password",
+    "password1234",
+    "passw0rd1234",
+    "q1w2e3r4t5y6",
+    "qwerty",
+    "qwertyuiop",
+    "qwertyuiop123",
+    "trustno1",
+    "trustno1trustno1",
+    "welcome",
+];
+
+impl PasswordPolicy {
+    /// Returns [`Error::WeakPassword`] if `password` does not meet this policy.
+    pub fn check(&self, password: &str) -> Result<(), Error> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(Error::WeakPassword(format!(
+                "the password must be at least {} characters long",
+                self.min_length
+            )));
+        }
+
+        let entropy = estimate_entropy(password);
+        if entropy < f64::from(self.min_entropy_bits) {
+            return Err(Error::WeakPassword(format!(
+                "the password has an estimated strength of {} bits, but at least {} are required",
+                entropy as u32, self.min_entropy_bits
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Estimates the entropy of `password` in bits. Each character contributes as much as a
+/// random choice from the classes of characters used in the password, unless it repeats or
+/// continues a run from the previous character, in which case it contributes a single bit.
+fn estimate_entropy(password: &str) -> f64 {
+    if COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+        return 0.0;
+    }
+
+    let has = |f: fn(&char) -> bool| password.chars().any(|c| f(&c));
+    let mut pool = 0u32;
+    if has(char::is_ascii_lowercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_uppercase) {
+        pool += 26;
+    }
+    if has(char::is_ascii_digit) {
+        pool += 10;
+    }
+    if has(|c| c.is_ascii() && !c.is_ascii_alphanumeric()) {
+        pool += 33;
+    }
+    if has(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    let bits_per_char = f64::from(pool.max(2)).log2();
+
+    let mut entropy = 0.0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = match previous {
+            Some(p) => (i64::from(u32::from(c)) - i64::from(u32::from(p))).abs() <= 1,
+            None => false,
+        };
+        entropy += if predictable { 1.0 } else { bits_per_char };
+        previous = Some(c);
+    }
+    entropy
+}
@@ -0,0 +1,92 @@
+//! Import and export of secrets as CSV, e.g. to and from a spreadsheet.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+use std::io::{Read, Write};
+use zeroize::Zeroizing;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Decrypts all secrets and writes them to `writer` as CSV, in sorted order, with a header
+    /// row naming the columns `key_column` (holding the names of the secrets) and
+    /// `value_column` (holding their values).
+    pub fn export_csv<W: Write>(
+        &self,
+        writer: W,
+        key_column: &str,
+        value_column: &str,
+    ) -> Result<(), Error> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer
+            .write_record([key_column, value_column])
+            .map_err(write_failure)?;
+        for name in self.keys() {
+            let value = self.retrieve_secret(name)?;
+            writer
+                .write_record([name, value.expose_secret().as_str()])
+                .map_err(write_failure)?;
+        }
+        writer.flush().map_err(Error::Io)
+    }
+
+    /// Reads CSV with a header row from `reader` and adds a secret for each row, named by the
+    /// column `key_column` and with the value in the column `value_column`, replacing any
+    /// existing secrets with the same names. Other columns are ignored, so an existing
+    /// inventory of secrets with additional columns may be imported as-is. Nothing is added if
+    /// the CSV is malformed or either column is missing.
+    pub fn import_csv<R: Read>(
+        &mut self,
+        reader: R,
+        key_column: &str,
+        value_column: &str,
+    ) -> Result<(), Error> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let headers = reader.headers().map_err(read_failure)?;
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or(Error::MalformedImport {
+                    line: 1,
+                    reason: "the key or value column is missing from the header row",
+                })
+        };
+        let (key, value) = (column(key_column)?, column(value_column)?);
+
+        let mut secrets = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(read_failure)?;
+            let line = record.position().map_or(0, |p| p.line() as usize);
+            match (record.get(key), record.get(value)) {
+                (Some(name), Some(value)) => {
+                    secrets.push((name.to_string(), Zeroizing::new(value.to_string())))
+                }
+                _ => {
+                    return Err(Error::MalformedImport {
+                        line,
+                        reason: "the row has no key or value",
+                    })
+                }
+            }
+        }
+
+        for (name, value) in secrets {
+            self.set(&name, value.as_str());
+        }
+        Ok(())
+    }
+}
+
+fn write_failure(error: csv::Error) -> Error {
+    Error::Io(error.into())
+}
+
+fn read_failure(error: csv::Error) -> Error {
+    match error.kind() {
+        csv::ErrorKind::Io(_) => Error::Io(error.into()),
+        _ => Error::MalformedImport {
+            line: error.position().map_or(0, |p| p.line() as usize),
+            reason: "invalid CSV",
+        },
+    }
+}
@@ -0,0 +1,246 @@
+//! Import and export of secrets as `.env` files, as used by docker-compose and dotenv libraries.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+use std::io::{BufWriter, Write};
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+use zeroize::Zeroizing;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Decrypts all secrets and writes them to a `.env` file at `path` as `NAME=value` lines,
+    /// in sorted order, replacing any existing file. On Unix, the file is only readable by its
+    /// owner.
+    ///
+    /// Each variable is named by applying `name_transform` to the name of the secret, e.g.
+    /// `|name| name.to_uppercase().replace('/', "_")` to turn `db/password` into
+    /// `DB_PASSWORD`; pass `str::to_string` to keep the names as they are. Values are quoted
+    /// where needed so that they are read back verbatim.
+    pub fn export_dotenv<P, F>(&self, path: P, name_transform: F) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        F: Fn(&str) -> String,
+    {
+        let path = path.as_ref();
+        let mut file = BufWriter::new(super::create_private(path)?);
+
+        for name in self.keys() {
+            let value = self.retrieve_secret(name)?;
+            let line = Zeroizing::new(format!(
+                "{}={}\n",
+                name_transform(name),
+                *quote(value.expose_secret())
+            ));
+            file.write_all(line.as_bytes()).map_err(Error::file(path))?;
+        }
+        file.flush().map_err(Error::file(path))
+    }
+
+    /// Reads the variables defined in the `.env` file at `path` and adds each as a secret of
+    /// the same name, replacing any existing secrets with those names.
+    ///
+    /// Lines are of the form `NAME=value`, optionally preceded by `export`. Values may be
+    /// unquoted (ending at the first ` #` comment), single-quoted (read verbatim) or
+    /// double-quoted (with `\n`, `\r`, `\t`, `\"`, `\$` and `\\` escapes), and quoted values
+    /// may span several lines. Blank lines and lines starting with `#` are ignored. Nothing is
+    /// added if the file is malformed, which is reported as [`Error::MalformedImport`].
+    pub fn import_dotenv<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let contents = Zeroizing::new(std::fs::read_to_string(path).map_err(Error::file(path))?);
+
+        for (name, value) in parse(&contents)? {
+            self.set(&name, value.as_str());
+        }
+        Ok(())
+    }
+}
+
+/// Parses the variables defined in the contents of a `.env` file, in order.
+fn parse(contents: &str) -> Result<Vec<(String, Zeroizing<String>)>, Error> {
+    let mut parser = Parser {
+        chars: contents.chars().peekable(),
+        line: 1,
+    };
+    let mut variables = Vec::new();
+
+    loop {
+        match parser.chars.peek() {
+            None => break,
+            Some(c) if c.is_whitespace() => {
+                parser.next();
+                continue;
+            }
+            Some('#') => {
+                parser.skip_comment();
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        let mut name = parser.name();
+        if name == "export" && parser.skip_blanks() {
+            name = parser.name();
+        }
+        parser.skip_blanks();
+        if name.is_empty() || parser.next() != Some('=') {
+            return Err(parser.error("expected a variable name followed by `=`"));
+        }
+        parser.skip_blanks();
+
+        let value = match parser.chars.peek() {
+            Some('\'') => parser.single_quoted()?,
+            Some('"') => parser.double_quoted()?,
+            _ => parser.unquoted(),
+        };
+        parser.skip_blanks();
+        match parser.chars.peek() {
+            None | Some('\n') | Some('\r') => {}
+            Some('#') => parser.skip_comment(),
+            Some(_) => return Err(parser.error("unexpected characters after the value")),
+        }
+
+        variables.push((name, value));
+    }
+
+    Ok(variables)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn error(&self, reason: &'static str) -> Error {
+        Error::MalformedImport {
+            line: self.line,
+            reason,
+        }
+    }
+
+    /// The error for a quoted value starting at `line` which is never closed.
+    fn unterminated(line: usize) -> Error {
+        Error::MalformedImport {
+            line,
+            reason: "unterminated quoted value",
+        }
+    }
+
+    /// Skips spaces and tabs, returning whether any were skipped.
+    fn skip_blanks(&mut self) -> bool {
+        let mut skipped = false;
+        while let Some(' ') | Some('\t') = self.chars.peek() {
+            self.next();
+            skipped = true;
+        }
+        skipped
+    }
+
+    /// Skips the remainder of the current line.
+    fn skip_comment(&mut self) {
+        while !matches!(self.chars.peek(), None | Some('\n')) {
+            self.next();
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            name.push(c);
+            self.next();
+        }
+        name
+    }
+
+    fn unquoted(&mut self) -> Zeroizing<String> {
+        let mut value = Zeroizing::new(String::new());
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' || c == '\r' || (c == '#' && value.ends_with([' ', '\t'])) {
+                break;
+            }
+            value.push(c);
+            self.next();
+        }
+        let trimmed = value.trim_end().len();
+        value.truncate(trimmed);
+        value
+    }
+
+    fn single_quoted(&mut self) -> Result<Zeroizing<String>, Error> {
+        let line = self.line;
+        self.next();
+        let mut value = Zeroizing::new(String::new());
+        loop {
+            match self.next() {
+                None => return Err(Self::unterminated(line)),
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn double_quoted(&mut self) -> Result<Zeroizing<String>, Error> {
+        let line = self.line;
+        self.next();
+        let mut value = Zeroizing::new(String::new());
+        loop {
+            match self.next() {
+                None => return Err(Self::unterminated(line)),
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '$' | '\\')) => value.push(c),
+                    // Unknown escapes are kept as they are, as by most dotenv implementations
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => return Err(Self::unterminated(line)),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+}
+
+/// Quotes `value` for a `.env` file: values made up of only unproblematic characters are
+/// written as-is, other values without single quotes or newlines are single-quoted (which
+/// disables interpolation), and anything else is double-quoted with escapes.
+fn quote(value: &str) -> Zeroizing<String> {
+    let bare = |c: char| c.is_ascii_alphanumeric() || "_-.,:/@+%=".contains(c);
+    if value.chars().all(bare) {
+        Zeroizing::new(value.to_string())
+    } else if !value.contains(['\'', '\n', '\r']) {
+        Zeroizing::new(format!("'{}'", value))
+    } else {
+        let mut quoted = Zeroizing::new(String::with_capacity(value.len() + 2));
+        quoted.push('"');
+        for c in value.chars() {
+            match c {
+                '\\' => quoted.push_str("\\\\"),
+                '"' => quoted.push_str("\\\""),
+                '$' => quoted.push_str("\\$"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
+}
@@ -0,0 +1,64 @@
+//! Import and export of all secrets as a plaintext JSON object, for backups and migrations.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::shared::{to_base64, vec_from_base64};
+use crate::SecretsManager;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// The decrypted value of a secret, written as a JSON string if it is valid UTF-8 and as an
+/// object `{"base64": "..."}` otherwise, so that binary secrets survive the round trip.
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+#[serde(untagged)]
+enum Value {
+    Text(String),
+    Binary {
+        #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+        base64: Vec<u8>,
+    },
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Decrypts all secrets and writes them to `writer` as a pretty-printed JSON object mapping
+    /// the names of the secrets to their values, which may be restored (e.g. into a vault with
+    /// different keys or a different scheme) with [`import_plaintext()`](Self::import_plaintext).
+    ///
+    /// Values which are valid UTF-8 are written as strings, and all others as an object with a
+    /// single field `base64` holding the base64-encoded value.
+    pub fn export_plaintext<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let mut secrets = BTreeMap::new();
+        for name in self.keys() {
+            let value = match String::from_utf8(self.retrieve_bytes(name)?) {
+                Ok(text) => Value::Text(text),
+                Err(e) => Value::Binary {
+                    base64: e.into_bytes(),
+                },
+            };
+            secrets.insert(name, value);
+        }
+
+        serde_json::to_writer_pretty(&mut writer, &secrets).map_err(Error::Serde)?;
+        writer.write_all(b"\n").map_err(Error::Io)?;
+        writer.flush().map_err(Error::Io)
+    }
+
+    /// Reads a JSON object of secrets, as written by
+    /// [`export_plaintext()`](Self::export_plaintext), from `reader` and adds each secret to
+    /// the vault, replacing any existing secrets with the same names. Nothing is added if the
+    /// JSON is malformed.
+    pub fn import_plaintext<R: Read>(&mut self, reader: R) -> Result<(), Error> {
+        let secrets: BTreeMap<String, Value> =
+            serde_json::from_reader(reader).map_err(Error::Serde)?;
+
+        for (name, value) in &secrets {
+            match value {
+                Value::Text(text) => self.set(name, text.as_str()),
+                Value::Binary { base64 } => self.set_bytes(name, base64),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+//! Import and export of decrypted secrets in plaintext formats, for feeding other tools and for
+//! migrating secrets into and out of vaults. Exported files hold the secrets unencrypted and
+//! should be handled accordingly.
+
+#[cfg(feature = "csv")]
+mod csv;
+mod dotenv;
+mod json;
+
+use crate::errors::Error;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+/// Creates the file at `path` to export secrets to, replacing any existing file, readable and
+/// writable only by its owner regardless of the umask or the permissions of an existing file.
+pub(crate) fn create_private(path: &Path) -> Result<File, Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path).map_err(Error::file(path))?;
+    // An existing file keeps its permissions when opened
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(Error::file(path))?;
+    }
+    Ok(file)
+}
@@ -0,0 +1,102 @@
+//! Keyfiles protected by a passphrase.
+//!
+//! The key material is encrypted with ChaCha20-Poly1305 under a key derived from the
+//! passphrase with the default [`Kdf`], salted with a random salt. The KDF, salt, nonce and
+//! ciphertext are stored as JSON so that the KDF parameters can be raised in the future without
+//! breaking existing keyfiles.
+
+use crate::crypto::{Aead, CryptoProvider};
+use crate::errors::Error;
+use crate::kdf::Kdf;
+use crate::shared::{to_base64, vec_from_base64, KeyData, IV_SIZE, NONCE_SIZE, TAG_SIZE};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// The version of the protected keyfile format.
+const VERSION: u32 = 1;
+/// The upper bound on the size of a protected keyfile.
+const MAX_SIZE: u64 = 64 * 1024;
+
+/// The on-disk representation of a passphrase-protected keyfile.
+#[derive(Serialize, Deserialize)]
+struct ProtectedKeyfile {
+    version: u32,
+    kdf: Kdf,
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    salt: Vec<u8>,
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    nonce: Vec<u8>,
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    tag: Vec<u8>,
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    ciphertext: Vec<u8>,
+}
+
+/// Encrypts `key_data` with a key derived from `passphrase` and writes it to `path`.
+pub(crate) fn export<C: CryptoProvider>(
+    key_data: &[u8],
+    path: &Path,
+    passphrase: &str,
+) -> Result<(), Error> {
+    let kdf = Kdf::default();
+    let mut salt = [0u8; IV_SIZE];
+    C::random(&mut salt);
+    let mut nonce = [0u8; NONCE_SIZE];
+    C::random(&mut nonce);
+
+    let key = kdf.derive::<C>(passphrase, &salt)?;
+    let mut tag = [0u8; TAG_SIZE];
+    let ciphertext = C::aead_encrypt(Aead::ChaCha20Poly1305, &key, &nonce, key_data, &mut tag);
+
+    let keyfile = ProtectedKeyfile {
+        version: VERSION,
+        kdf,
+        salt: salt.to_vec(),
+        nonce: nonce.to_vec(),
+        tag: tag.to_vec(),
+        ciphertext,
+    };
+    let mut file = File::create(path).map_err(Error::file(path))?;
+    serde_json::to_writer_pretty(&mut file, &keyfile).map_err(Error::Serde)?;
+    file.write_all(b"\n").map_err(Error::file(path))
+}
+
+/// Reads the protected keyfile at `path` and decrypts it with `passphrase`, returning
+/// [`Error::DecryptionFailure`] if the passphrase is incorrect.
+pub(crate) fn import<C: CryptoProvider>(path: &Path, passphrase: &str) -> Result<KeyData, Error> {
+    let mut contents = Vec::new();
+    File::open(path)
+        .map_err(Error::file(path))?
+        .take(MAX_SIZE)
+        .read_to_end(&mut contents)
+        .map_err(Error::file(path))?;
+    let keyfile: ProtectedKeyfile =
+        serde_json::from_slice(&contents).map_err(|_| Error::InvalidKeyfile)?;
+    if keyfile.version > VERSION {
+        return Err(Error::InvalidKeyfile);
+    }
+
+    let salt: &[u8; IV_SIZE] = keyfile.salt[..]
+        .try_into()
+        .map_err(|_| Error::InvalidKeyfile)?;
+    let nonce = keyfile.nonce[..]
+        .try_into()
+        .map_err(|_| Error::InvalidKeyfile)?;
+    let tag = keyfile.tag[..]
+        .try_into()
+        .map_err(|_| Error::InvalidKeyfile)?;
+
+    let key = keyfile.kdf.derive::<C>(passphrase, salt)?;
+    C::aead_decrypt(
+        Aead::ChaCha20Poly1305,
+        &key,
+        nonce,
+        &keyfile.ciphertext,
+        tag,
+    )
+    .map(Zeroizing::new)
+}
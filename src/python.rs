@@ -0,0 +1,126 @@
+//! Python bindings, built as the `securestore` extension module with
+//! `cargo rustc --release --features python,pyo3/extension-module --crate-type cdylib`, then
+//! installed by copying `libsecurestore.so` to `securestore.so` (or `securestore.pyd` on
+//! Windows) on the Python path.
+//!
+//! ```python
+//! import securestore
+//!
+//! sman = securestore.SecretsManager.load("secrets.json", keyfile="secrets.key")
+//! sman.set("db_password", "hunter2")
+//! sman.save()
+//! print(sman.get("db_password"))
+//! ```
+//!
+//! Secrets which cannot be found raise `KeyError`, and all other failures raise
+//! `securestore.SecureStoreError`.
+
+use crate::errors::Error;
+use crate::{KeySource, SecretsManager};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::path::Path;
+
+create_exception!(securestore, SecureStoreError, PyException);
+
+fn to_py(error: Error) -> PyErr {
+    match error {
+        Error::SecretNotFound(name) => PyKeyError::new_err(name),
+        error => SecureStoreError::new_err(error.to_string()),
+    }
+}
+
+/// Selects the key source from the `password` and `keyfile` keyword arguments, exactly one of
+/// which must be set.
+fn key_source<'a>(password: Option<&'a str>, keyfile: Option<&'a str>) -> PyResult<KeySource<'a>> {
+    match (password, keyfile) {
+        (Some(password), None) => Ok(KeySource::Password(password)),
+        (None, Some(keyfile)) => Ok(KeySource::File(Path::new(keyfile))),
+        _ => Err(PyValueError::new_err(
+            "exactly one of password and keyfile must be given",
+        )),
+    }
+}
+
+/// A secrets vault, as exposed to Python.
+#[pyclass(name = "SecretsManager", module = "securestore")]
+struct PySecretsManager {
+    sman: SecretsManager,
+}
+
+#[pymethods]
+impl PySecretsManager {
+    /// Creates a new vault at `path`, protected by `password` or the keys in `keyfile`.
+    #[staticmethod]
+    #[pyo3(signature = (path, *, password = None, keyfile = None))]
+    fn new(path: &str, password: Option<&str>, keyfile: Option<&str>) -> PyResult<Self> {
+        let sman = SecretsManager::new(path, key_source(password, keyfile)?).map_err(to_py)?;
+        Ok(PySecretsManager { sman })
+    }
+
+    /// Loads the existing vault at `path`, unlocking it with `password` or `keyfile`.
+    #[staticmethod]
+    #[pyo3(signature = (path, *, password = None, keyfile = None))]
+    fn load(path: &str, password: Option<&str>, keyfile: Option<&str>) -> PyResult<Self> {
+        let sman = SecretsManager::load(path, key_source(password, keyfile)?).map_err(to_py)?;
+        Ok(PySecretsManager { sman })
+    }
+
+    /// Decrypts the UTF-8 secret `name`.
+    fn get(&self, name: &str) -> PyResult<String> {
+        self.sman.retrieve(name).map_err(to_py)
+    }
+
+    /// Decrypts the binary secret `name`.
+    fn get_bytes<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyBytes>> {
+        let value = self.sman.retrieve::<Vec<u8>>(name).map_err(to_py)?;
+        Ok(PyBytes::new(py, &value))
+    }
+
+    /// Sets the secret `name` to `value`, which may be `str` or `bytes`.
+    fn set(&mut self, name: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        if let Ok(value) = value.downcast::<PyBytes>() {
+            self.sman.set(name, value.as_bytes());
+        } else {
+            self.sman.set(name, value.extract::<&str>()?);
+        }
+        Ok(())
+    }
+
+    /// Removes the secret `name`.
+    fn remove(&mut self, name: &str) -> PyResult<()> {
+        self.sman.remove(name).map_err(to_py)
+    }
+
+    /// Saves the changes made to the vault.
+    fn save(&self) -> PyResult<()> {
+        self.sman.save().map_err(to_py)
+    }
+
+    /// The names of all secrets in the vault, in sorted order.
+    fn keys(&self) -> Vec<String> {
+        self.sman.keys().map(str::to_string).collect()
+    }
+
+    /// Exports the keys of the vault to `path`, e.g. to unlock it without the password.
+    fn export_keyfile(&self, path: &str) -> PyResult<()> {
+        self.sman.export_keyfile(path).map_err(to_py)
+    }
+
+    fn __len__(&self) -> usize {
+        self.sman.len()
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.sman.keys().any(|key| key == name)
+    }
+}
+
+#[pymodule]
+pub(crate) fn securestore(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySecretsManager>()?;
+    m.add("SecureStoreError", m.py().get_type::<SecureStoreError>())?;
+    Ok(())
+}
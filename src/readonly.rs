@@ -0,0 +1,54 @@
+//! Read-only access to vaults, for consumers which must never modify them.
+
+use crate::crypto::{CryptoProvider, DefaultProvider};
+use crate::errors::Error;
+use crate::secret::SecretString;
+use crate::serial::BinaryDeserializable;
+use crate::SecretsManager;
+
+/// A [`SecretsManager`] which can only retrieve secrets, as returned by
+/// [`SecretsManager::load_readonly()`]. There are no methods to add, remove or change secrets or
+/// keys, nor to save the vault, so a service holding only a `ReadOnlySecretsManager` cannot
+/// rewrite the vault.
+pub struct ReadOnlySecretsManager<C: CryptoProvider = DefaultProvider> {
+    sman: SecretsManager<C>,
+}
+
+impl<C: CryptoProvider> ReadOnlySecretsManager<C> {
+    /// Decrypts and retrieves a single secret. See [`SecretsManager::retrieve()`].
+    pub fn retrieve<T: BinaryDeserializable>(&self, name: &str) -> Result<T, Error> {
+        self.sman.retrieve(name)
+    }
+
+    /// Decrypts and retrieves a single UTF-8 secret. See [`SecretsManager::retrieve_secret()`].
+    pub fn retrieve_secret(&self, name: &str) -> Result<SecretString, Error> {
+        self.sman.retrieve_secret(name)
+    }
+
+    /// Decrypts and retrieves a single binary secret. See [`SecretsManager::retrieve_bytes()`].
+    pub fn retrieve_bytes(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.sman.retrieve_bytes(name)
+    }
+
+    /// Returns an iterator over the names of all secrets in the store, in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.sman.keys()
+    }
+
+    /// Returns the number of secrets in the store.
+    pub fn len(&self) -> usize {
+        self.sman.len()
+    }
+
+    /// Returns `true` if the store contains no secrets.
+    pub fn is_empty(&self) -> bool {
+        self.sman.is_empty()
+    }
+}
+
+impl<C: CryptoProvider> From<SecretsManager<C>> for ReadOnlySecretsManager<C> {
+    /// Gives up the ability to modify the vault loaded in `sman`.
+    fn from(sman: SecretsManager<C>) -> Self {
+        ReadOnlySecretsManager { sman }
+    }
+}
@@ -0,0 +1,65 @@
+//! A wrapper for retrieved secrets which keeps them out of logs and wipes them when dropped.
+
+use crate::errors::Error;
+use crate::serial::{BinaryDeserializable, BinarySerializable};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A secret value which is redacted when formatted with `{:?}` or `{}` and zeroized when
+/// dropped. The wrapped value is only accessible via [`Secret::expose_secret()`].
+///
+/// `Secret<T>` may be used anywhere `T` can be retrieved from or stored in a vault.
+pub struct Secret<T: Zeroize>(T);
+
+/// A UTF-8 secret, as returned by [`SecretsManager::retrieve_secret()`].
+///
+/// [`SecretsManager::retrieve_secret()`]: crate::SecretsManager::retrieve_secret
+pub type SecretString = Secret<String>;
+
+impl<T: Zeroize> Secret<T> {
+    /// Wraps `value` so that it is redacted when formatted and zeroized when dropped.
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    /// Returns a reference to the wrapped secret value.
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Zeroize + BinarySerializable> BinarySerializable for Secret<T> {
+    fn serialize(&self) -> Vec<u8> {
+        self.0.serialize()
+    }
+}
+
+impl<T: Zeroize + BinaryDeserializable> BinaryDeserializable for Secret<T> {
+    fn deserialize(bytes: Vec<u8>) -> Result<Self, Error> {
+        T::deserialize(bytes).map(Secret)
+    }
+}
@@ -1,14 +1,48 @@
 //! This module houses various implementations of [`BinarySerializable`] which are natively
 //! supported payloads for secrets.
 
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
 pub trait BinarySerializable {
     fn serialize(&self) -> Vec<u8>;
 }
 
-pub trait BinaryDeserializable {
-    fn deserialize(bytes: Vec<u8>) -> Self;
+/// Implementations take ownership of the decrypted plaintext and should either reuse the buffer
+/// or wipe it (e.g. via `zeroize`) rather than leave a copy of the secret behind when dropped.
+pub trait BinaryDeserializable: Sized {
+    fn deserialize(bytes: Vec<u8>) -> Result<Self, Error>;
 }
 
+/// A wrapper for retrieving any type implementing serde's `Deserialize` from a secret stored
+/// as JSON with [`SecretsManager::set_json()`].
+///
+/// The wrapper is needed because [`BinaryDeserializable`] cannot be implemented for every
+/// `Deserialize` type without conflicting with the implementations for `String` and `Vec<u8>`,
+/// which return the plaintext as-is rather than parsing it as JSON.
+///
+/// ```no_run
+/// # use securestore::{Error, Json, KeySource, SecretsManager};
+/// # use serde_derive::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct DbConfig {
+///     user: String,
+///     port: u16,
+/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut sman = SecretsManager::load("secrets.json", KeySource::File("secrets.key".as_ref()))?;
+/// sman.set_json("db", &DbConfig { user: "postgres".into(), port: 5432 })?;
+/// let Json(config): Json<DbConfig> = sman.retrieve("db")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Json<T>(pub T);
+
 impl BinarySerializable for String {
     fn serialize(&self) -> Vec<u8> {
         self.as_bytes().to_vec()
@@ -16,8 +50,15 @@ impl BinarySerializable for String {
 }
 
 impl BinaryDeserializable for String {
-    fn deserialize(bytes: Vec<u8>) -> String {
-        String::from_utf8_lossy(&bytes).to_string()
+    fn deserialize(bytes: Vec<u8>) -> Result<String, Error> {
+        // Reuse the plaintext buffer where possible instead of leaving a copy behind
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(e) => {
+                let bytes = Zeroizing::new(e.into_bytes());
+                Ok(String::from_utf8_lossy(&bytes).into_owned())
+            }
+        }
     }
 }
 
@@ -26,3 +67,41 @@ impl BinarySerializable for &str {
         self.as_bytes().to_vec()
     }
 }
+
+impl BinarySerializable for Vec<u8> {
+    fn serialize(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+impl BinaryDeserializable for Vec<u8> {
+    fn deserialize(bytes: Vec<u8>) -> Result<Vec<u8>, Error> {
+        Ok(bytes)
+    }
+}
+
+impl BinarySerializable for &[u8] {
+    fn serialize(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl<T: DeserializeOwned> BinaryDeserializable for Json<T> {
+    fn deserialize(bytes: Vec<u8>) -> Result<Json<T>, Error> {
+        let bytes = Zeroizing::new(bytes);
+        serde_json::from_slice(&bytes)
+            .map(Json)
+            .map_err(Error::Serde)
+    }
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Adds or replaces the secret `name`, serializing `value` to JSON prior to encryption. It
+    /// may be retrieved again with [`Json`]. Returns [`Error::Serde`] if `value` cannot be
+    /// serialized, e.g. a map with non-string keys.
+    pub fn set_json<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), Error> {
+        let json = Zeroizing::new(serde_json::to_vec(value).map_err(Error::Serde)?);
+        self.set_bytes(name, &json);
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+//! Shamir secret sharing of the vault keys over GF(2^8).
+//!
+//! Each byte of the key material is split independently with a random polynomial of degree
+//! `threshold - 1` whose constant term is the key byte; share `x` holds the value of every
+//! polynomial at `x`. Each share is written to its own file, laid out as
+//!
+//! ```text
+//! version (1) | threshold (1) | x (1) | split id (8) | y (KEY_COUNT * KEY_LENGTH)
+//! ```
+//!
+//! where the random split id ties together the shares produced by a single split.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::shared::{KeyData, KEY_COUNT, KEY_LENGTH};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+const SHARE_VERSION: u8 = 1;
+const ID_LENGTH: usize = 8;
+const HEADER_LENGTH: usize = 3 + ID_LENGTH;
+const SHARE_LENGTH: usize = HEADER_LENGTH + KEY_COUNT * KEY_LENGTH;
+
+/// Splits `secret` into `shares` shares of which any `threshold` reconstruct it, writing them
+/// to `share-<x>.key` files in `dir` and returning their paths.
+pub(crate) fn split<C: CryptoProvider>(
+    secret: &[u8; KEY_COUNT * KEY_LENGTH],
+    shares: u8,
+    threshold: u8,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    if threshold == 0 || threshold > shares {
+        return Err(Error::InvalidKeyShares);
+    }
+
+    let mut id = [0u8; ID_LENGTH];
+    C::random(&mut id);
+
+    // coefficients[i][j] is the coefficient of x^(j+1) in the polynomial for byte i
+    let mut coefficients = Zeroizing::new(vec![0u8; secret.len() * (threshold as usize - 1)]);
+    C::random(&mut coefficients);
+
+    std::fs::create_dir_all(dir).map_err(Error::file(dir))?;
+    let mut paths = Vec::with_capacity(shares as usize);
+    for x in 1..=shares {
+        let mut share = Zeroizing::new(Vec::with_capacity(SHARE_LENGTH));
+        share.extend_from_slice(&[SHARE_VERSION, threshold, x]);
+        share.extend_from_slice(&id);
+        for (i, &byte) in secret.iter().enumerate() {
+            let terms = &coefficients[i * (threshold as usize - 1)..][..threshold as usize - 1];
+            // Horner's method, from the highest-degree coefficient down to the secret
+            let y = terms.iter().rev().fold(0, |acc, &c| gf_mul(acc, x) ^ c);
+            share.push(gf_mul(y, x) ^ byte);
+        }
+
+        let path = dir.join(format!("share-{}.key", x));
+        let mut file = File::create(&path).map_err(Error::file(&path))?;
+        file.write_all(&share).map_err(Error::file(&path))?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Reconstructs the secret from the share files at `paths`, which must include at least as
+/// many distinct shares of the same split as its threshold.
+pub(crate) fn combine(paths: &[PathBuf]) -> Result<KeyData, Error> {
+    let mut shares = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mut share = Zeroizing::new(Vec::with_capacity(SHARE_LENGTH));
+        File::open(path)
+            .map_err(Error::file(path))?
+            .take(SHARE_LENGTH as u64 + 1)
+            .read_to_end(&mut share)
+            .map_err(Error::file(path))?;
+        if share.len() != SHARE_LENGTH || share[0] != SHARE_VERSION {
+            return Err(Error::InvalidKeyShares);
+        }
+        shares.push(share);
+    }
+
+    let first = shares.first().ok_or(Error::InvalidKeyShares)?;
+    let (threshold, id) = (first[1], &first[3..HEADER_LENGTH]);
+    let mut selected: Vec<&[u8]> = Vec::with_capacity(threshold as usize);
+    for share in &shares {
+        if share[1] != threshold || &share[3..HEADER_LENGTH] != id {
+            return Err(Error::InvalidKeyShares);
+        }
+        if share[2] == 0 {
+            return Err(Error::InvalidKeyShares);
+        }
+        // Duplicate shares add no information
+        if selected.iter().all(|s| s[2] != share[2]) && selected.len() < threshold as usize {
+            selected.push(share);
+        }
+    }
+    if threshold == 0 || selected.len() < threshold as usize {
+        return Err(Error::InvalidKeyShares);
+    }
+
+    // Lagrange interpolation at x = 0
+    let xs: Vec<u8> = selected.iter().map(|s| s[2]).collect();
+    let mut secret = Zeroizing::new(vec![0u8; KEY_COUNT * KEY_LENGTH]);
+    for (i, share) in selected.iter().enumerate() {
+        let basis = xs
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(1, |acc, (_, &xj)| gf_mul(acc, gf_div(xj, xj ^ xs[i])));
+        for (byte, &y) in secret.iter_mut().zip(&share[HEADER_LENGTH..]) {
+            *byte ^= gf_mul(basis, y);
+        }
+    }
+
+    Ok(secret)
+}
+
+/// Multiplication in GF(2^8) with the AES reduction polynomial, in constant time.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+/// Division in GF(2^8), computing the inverse of `b` as `b^254`.
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1u8;
+    let mut power = b;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            inverse = gf_mul(inverse, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    gf_mul(a, inverse)
+}
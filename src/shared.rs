@@ -1,27 +1,77 @@
 //! This module contains code that must line up between the various implementations of SecureStore
 //! in different languages.
-
+//!
+//! The on-disk vault is a pretty-printed JSON object, ending in a newline, with the fields
+//! `version`, `iv` (base64, or `null` for vaults only ever unlocked with a keyfile) and
+//! `secrets`, a map (sorted by name) of secret names to objects containing the base64-encoded
+//! `iv`, `hmac` and `payload` of each secret. Keys are either read from a 32-byte binary keyfile
+//! (the AES key followed by the HMAC key) or derived from a password via PBKDF2-HMAC-SHA1 salted
+//! with the vault IV. Each secret is encrypted with AES-128-CBC (PKCS#7 padding) under its own
+//! IV and authenticated with HMAC-SHA1 over the IV followed by the ciphertext. This is the layout
+//! written by earlier versions of this crate, referred to below as the legacy layout.
+//!
+//! Vaults now created by this crate additionally record a `sentinel`, an encrypted blob of random
+//! data which is decrypted at load to verify the keys, and a `mac`: the HMAC-SHA1 (keyed with
+//! the HMAC key) over the compact JSON serialization of all other fields, which is verified when
+//! the vault is loaded. Both are required unless loading unauthenticated vaults is explicitly
+//! allowed with
+//! [`allow_unauthenticated()`](crate::SecretsManagerBuilder::allow_unauthenticated), as any
+//! field that might identify an older vault could equally have been removed by whoever tampered
+//! with it. Unauthenticated vaults are given a sentinel when they are loaded, so they are authenticated
+//! from the first time they are saved.
+//!
+//! Vaults may also record a `scheme`, which determines how the key material is used: the
+//! cipher used to encrypt secrets, keyed with either the first key or the full key material,
+//! and the MAC used to authenticate them and the vault.
+
+use crate::compression::{self, Compression};
+use crate::crypto::{Aead, CryptoProvider};
+use crate::description::Description;
 use crate::errors::Error;
-use openssl::rand;
-use serde::{Deserialize, Deserializer, Serializer};
+use crate::files::FileMetadata;
+use crate::format::VaultFormat;
+use crate::history::Version;
+use crate::kdf::Kdf;
+use crate::metadata::SecretMetadata;
+use crate::tombstones::Tombstone;
+use serde::{Deserializer, Serializer};
 use serde_derive::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
 /// The number of keys we require to be derived from source materials
 pub const KEY_COUNT: usize = 2;
-/// The length of each individual key in bytes
+/// The length of each individual key in the key material loaded from a key source, in bytes.
+/// This is fixed for every [`Scheme`], as keyfiles and key sources always hold the same amount
+/// of key material: CBC schemes key the cipher and the MAC with one key each, while AEAD schemes
+/// use the full key material as a single 256-bit key.
 pub const KEY_LENGTH: usize = 128 / 8;
-/// The number of rounds used for PBKDF2 key derivation
-pub const PBKDF2_ROUNDS: usize = 10000usize;
+/// The number of rounds used for PBKDF2 key derivation by vaults that do not record their KDF
+pub const PBKDF2_ROUNDS: u32 = 10000;
 /// The size of an initialization vector in bytes
 pub const IV_SIZE: usize = KEY_LENGTH;
-/// The latest version of the vault schema
-pub const SCHEMA_VERSION: u32 = 1;
+/// The latest version of the vault schema. Version 1 vaults (which stored secrets under `data`)
+/// are still read.
+pub const SCHEMA_VERSION: u32 = 2;
 /// The length of a single HMAC result in bytes
 pub const HMAC_SIZE: usize = 160 / 8; // HMAC-SHA1
+/// The length of a single HMAC-SHA256 result in bytes
+pub const HMAC_SHA256_SIZE: usize = 256 / 8;
+/// The size of a nonce for AEAD schemes in bytes
+pub const NONCE_SIZE: usize = 96 / 8;
+/// The size of an authentication tag for AEAD schemes in bytes
+pub const TAG_SIZE: usize = 128 / 8;
+/// The line preceding the base64-encoded key material in an armored keyfile.
+const ARMOR_BEGIN: &str = "-----BEGIN SECURESTORE KEYS-----";
+/// The line following the base64-encoded key material in an armored keyfile.
+const ARMOR_END: &str = "-----END SECURESTORE KEYS-----";
+/// The upper bound on the size of an armored keyfile.
+pub const MAX_ARMORED_SIZE: u64 = 4096;
 
 /// A representation of the on-disk encrypted secrets store. Read and written via
 /// `[SecretsManager]`.
@@ -30,31 +80,215 @@ pub struct Vault {
     /// The version of the serialized vault
     pub version: u32,
     /// The initialization vector for key derivation
-    #[serde(serialize_with = "nullable_to_base64", deserialize_with = "nullable_iv_from_base64")]
+    #[serde(
+        serialize_with = "nullable_to_base64",
+        deserialize_with = "nullable_iv_from_base64"
+    )]
     pub iv: Option<[u8; IV_SIZE]>,
+    /// Random data encrypted with the vault keys when the vault was created, which is
+    /// decrypted at load to verify that the correct keys were supplied. Vaults created by older
+    /// versions may not have one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentinel: Option<EncryptedBlob>,
+    /// The KDF used to derive keys from a password. Vaults that were not created with a
+    /// password, or that were created by other SecureStore implementations, may not record
+    /// one, in which case [`Kdf::legacy()`] is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<Kdf>,
+    /// The scheme used to encrypt the secrets. Vaults created by other SecureStore
+    /// implementations do not record one, in which case [`Scheme::default()`] is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<Scheme>,
+    /// The vault keys, wrapped by an external key management service, for vaults protected by
+    /// a KMS-backed [`KeySource`](crate::KeySource).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrapped_key: Option<WrappedKey>,
+    /// The vault keys, encrypted to the X25519 public key of each recipient granted access.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipients: Vec<Recipient>,
     /// The secrets we are tasked with protecting, sorted for version control friendliness.
-    pub data: BTreeMap<String, EncryptedBlob>,
+    #[serde(alias = "data")]
+    pub secrets: BTreeMap<String, EncryptedBlob>,
+    /// The original name and permissions of secrets stored from files, by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, FileMetadata>,
+    /// The details recorded about each secret, by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, SecretMetadata>,
+    /// The descriptions of secrets, in the clear or encrypted, by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub descriptions: BTreeMap<String, Description>,
+    /// The tags of each tagged secret, by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, BTreeSet<String>>,
+    /// The number of previous values kept for each secret, if history is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_depth: Option<usize>,
+    /// The previous values of each secret, most recent first, by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub history: BTreeMap<String, Vec<Version>>,
+    /// Secrets which were removed with
+    /// [`SecretsManager::remove_soft()`](crate::SecretsManager::remove_soft), by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tombstones: BTreeMap<String, Tombstone>,
+    /// When each expiring secret expires, in seconds since the Unix epoch, by secret name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub expiry: BTreeMap<String, u64>,
+    /// The HMAC over all other fields of the vault, as of the last time it was saved. Only
+    /// read here; it is computed and written by [`Vault::to_bytes()`].
+    #[serde(
+        default,
+        skip_serializing,
+        deserialize_with = "nullable_vec_from_base64"
+    )]
+    pub mac: Option<Vec<u8>>,
+    /// Whether the vault has changed since it was loaded or last saved. Not serialized.
+    #[serde(skip)]
+    dirty: AtomicBool,
+    /// The format the vault is serialized in, as detected when it was loaded. Not serialized.
+    #[serde(skip)]
+    pub format: VaultFormat,
+}
+
+/// A vault as written to disk, followed by the MAC over its contents.
+#[derive(Serialize)]
+struct AuthenticatedVault<'a> {
+    #[serde(flatten)]
+    vault: &'a Vault,
+    #[serde(serialize_with = "to_base64")]
+    mac: Vec<u8>,
+}
+
+/// The length of the random data encrypted in the sentinel.
+const SENTINEL_LENGTH: usize = 32;
+
+/// Separates the whole-vault MAC from the per-secret HMACs computed with the same key.
+const VAULT_MAC_CONTEXT: &[u8] = b"securestore vault mac v1\0";
+
+/// The scheme used to encrypt and authenticate the secrets in a vault.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scheme {
+    /// AES-128-CBC with a separate HMAC-SHA1 over the IV and ciphertext (encrypt-then-MAC), as
+    /// used by the legacy vault layout.
+    #[default]
+    Aes128CbcHmacSha1,
+    /// AES-128-CBC with a separate HMAC-SHA256 over the IV and ciphertext, which also
+    /// authenticates the vault as a whole.
+    Aes128CbcHmacSha256,
+    /// AES-256-GCM, using the full key material as a single 256-bit key.
+    Aes256Gcm,
+    /// ChaCha20-Poly1305, using the full key material as a single 256-bit key. Faster than
+    /// AES-GCM on processors without hardware AES acceleration.
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
+
+/// The MAC algorithms a [`Scheme`] may authenticate secrets and the vault with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MacAlgorithm {
+    HmacSha1,
+    HmacSha256,
+}
+
+impl MacAlgorithm {
+    /// The length of a MAC computed with this algorithm, in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            MacAlgorithm::HmacSha1 => HMAC_SIZE,
+            MacAlgorithm::HmacSha256 => HMAC_SHA256_SIZE,
+        }
+    }
+
+    /// Computes the MAC of the concatenation of `data` with `key`.
+    pub(crate) fn compute<C: CryptoProvider>(
+        self,
+        key: &[u8; KEY_LENGTH],
+        data: &[&[u8]],
+    ) -> Vec<u8> {
+        match self {
+            MacAlgorithm::HmacSha1 => C::hmac_sha1(key, data).to_vec(),
+            MacAlgorithm::HmacSha256 => C::hmac_sha256(key, data).to_vec(),
+        }
+    }
+}
+
+impl Scheme {
+    /// The MAC the vault is authenticated with, which CBC schemes also authenticate each
+    /// secret with. AEAD schemes keep HMAC-SHA1 for the vault, as vaults using them were
+    /// created before the MAC was part of the scheme.
+    pub fn mac(self) -> MacAlgorithm {
+        match self {
+            Scheme::Aes128CbcHmacSha256 => MacAlgorithm::HmacSha256,
+            _ => MacAlgorithm::HmacSha1,
+        }
+    }
+}
+
+/// Raw key material for [`KEY_COUNT`] keys of [`KEY_LENGTH`] bytes, as loaded from an external
+/// key source, which is wiped from memory when dropped.
+pub type KeyData = Zeroizing<Vec<u8>>;
+
+/// The vault keys encrypted (wrapped) by a key-encryption key held in an external key
+/// management service, which must be asked to unwrap them whenever the vault is loaded.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct WrappedKey {
+    /// The service which wrapped the keys, e.g. `aws-kms`
+    pub provider: String,
+    /// The identifier of the key-encryption key within the service
+    pub key: String,
+    /// The wrapped keys, in the service's own format
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// The vault keys encrypted to the X25519 public key of a single recipient, who may unlock the
+/// vault with the corresponding private key.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Recipient {
+    /// The recipient's X25519 public key
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    pub public_key: Vec<u8>,
+    /// The ephemeral X25519 public key the keys were encrypted with
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    pub ephemeral_key: Vec<u8>,
+    /// The nonce, ciphertext and tag of the keys encrypted with ChaCha20-Poly1305
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    pub ciphertext: Vec<u8>,
 }
 
 /// A single secret, independently encrypted and individually decrypted on-demand.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// The lengths of the fields depend on the vault's [`Scheme`]. For AEAD schemes, `iv` holds the
+/// nonce and `hmac` holds the authentication tag produced by the cipher.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub struct EncryptedBlob {
-    #[serde(serialize_with = "to_base64", deserialize_with = "iv_from_base64")]
-    pub iv: [u8; IV_SIZE],
-    #[serde(serialize_with = "to_base64", deserialize_with = "hmac_from_base64")]
-    pub hmac: [u8; HMAC_SIZE],
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    pub iv: Vec<u8>,
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    pub hmac: Vec<u8>,
     #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
     pub payload: Vec<u8>,
+    /// The algorithm the secret was compressed with before it was encrypted, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    /// For secrets encrypted in chunks, the encrypted chunks in order, with `payload` holding
+    /// the total length of the secret instead.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<EncryptedBlob>,
 }
 
+// Binary data is written as base64 strings in human-readable formats such as JSON, and as raw
+// byte strings in binary formats such as CBOR.
+
 pub fn nullable_to_base64<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
 where
     T: AsRef<[u8]>,
     S: Serializer,
 {
     match value {
-        None => serializer.serialize_str(""),
-        Some(x) => serializer.serialize_str(&base64::encode(x.as_ref()))
+        None => serializer.serialize_none(),
+        Some(x) => to_base64(x, serializer),
     }
 }
 
@@ -63,7 +297,10 @@ where
     T: AsRef<[u8]>,
     S: Serializer,
 {
-    serializer.serialize_str(&base64::encode(value.as_ref()))
+    match serializer.is_human_readable() {
+        true => serializer.serialize_str(&base64::encode(value.as_ref())),
+        false => serializer.serialize_bytes(value.as_ref()),
+    }
 }
 
 pub fn nullable_iv_from_base64<'de, D>(deserializer: D) -> Result<Option<[u8; IV_SIZE]>, D::Error>
@@ -71,95 +308,255 @@ where
     D: Deserializer<'de>,
 {
     use serde::de::Error;
-    let b64: String = Deserialize::deserialize(deserializer)?;
-
-    if b64.len() == 0 {
-        return Ok(None);
-    }
+    // Both `null` and an empty string denote the absence of an IV
+    let bytes: Option<Base64> = serde::Deserialize::deserialize(deserializer)?;
+    let bytes = match bytes {
+        Some(Base64(bytes)) if !bytes.is_empty() => bytes,
+        _ => return Ok(None),
+    };
 
     let mut result = [0u8; IV_SIZE];
-    base64::decode_config_slice(&b64, base64::STANDARD, &mut result)
-        .map_err(|e| Error::custom(e.to_string()))?;
+    if bytes.len() != result.len() {
+        return Err(Error::invalid_length(bytes.len(), &"a 16-byte IV"));
+    }
+    result.copy_from_slice(&bytes);
 
     Ok(Some(result))
 }
 
-pub fn iv_from_base64<'de, D>(deserializer: D) -> Result<[u8; IV_SIZE], D::Error>
+pub fn nullable_vec_from_base64<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    use serde::de::Error;
-    let b64: String = Deserialize::deserialize(deserializer)?;
-
-    let mut result = [0u8; IV_SIZE];
-    base64::decode_config_slice(&b64, base64::STANDARD, &mut result)
-        .map_err(|e| Error::custom(e.to_string()))?;
-
-    Ok(result)
+    let bytes: Option<Base64> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(bytes.map(|b| b.0))
 }
 
-pub fn hmac_from_base64<'de, D>(deserializer: D) -> Result<[u8; HMAC_SIZE], D::Error>
+pub fn vec_from_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    use serde::de::Error;
-    let b64: String = Deserialize::deserialize(deserializer)?;
-
-    let mut result = [0u8; HMAC_SIZE];
-    base64::decode_config_slice(&b64, base64::STANDARD, &mut result)
-        .map_err(|e| Error::custom(e.to_string()))?;
+    let bytes: Base64 = serde::Deserialize::deserialize(deserializer)?;
+    Ok(bytes.0)
+}
 
-    Ok(result)
+/// Binary data deserialized from a base64 string or, in binary formats, a byte string.
+struct Base64(Vec<u8>);
+
+impl<'de> serde::Deserialize<'de> for Base64 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        if deserializer.is_human_readable() {
+            let s: String = serde::Deserialize::deserialize(deserializer)?;
+            return base64::decode(&s)
+                .map(Base64)
+                .map_err(|e| Error::custom(e.to_string()));
+        }
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
 }
 
-pub fn vec_from_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    use serde::de::Error;
-    let s: String = Deserialize::deserialize(deserializer)?;
-    base64::decode(&s).map_err(|e| Error::custom(e.to_string()))
+struct BytesVisitor;
+
+impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+    type Value = Base64;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Base64, E> {
+        Ok(Base64(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Base64, E> {
+        Ok(Base64(v))
+    }
 }
 
 impl Vault {
-    pub fn new() -> Self {
-        let mut iv = [0u8; IV_SIZE];
-        rand::rand_bytes(&mut iv).expect("IV generation failure!");
-
+    pub fn new<C: CryptoProvider>() -> Self {
         Vault {
             version: SCHEMA_VERSION,
-            iv: Some(iv),
-            data: Default::default(),
+            iv: Some(Self::generate_iv::<C>()),
+            sentinel: None,
+            kdf: None,
+            scheme: None,
+            wrapped_key: None,
+            recipients: Vec::new(),
+            secrets: Default::default(),
+            files: Default::default(),
+            metadata: Default::default(),
+            descriptions: Default::default(),
+            tags: Default::default(),
+            history_depth: None,
+            history: Default::default(),
+            tombstones: Default::default(),
+            expiry: Default::default(),
+            mac: None,
+            // A new vault has never been saved
+            dirty: AtomicBool::new(true),
+            format: VaultFormat::default(),
         }
     }
 
+    /// The scheme used to encrypt the secrets in this vault.
+    pub fn scheme(&self) -> Scheme {
+        self.scheme.unwrap_or_default()
+    }
+
+    /// Generates a fresh random initialization vector for key derivation.
+    pub fn generate_iv<C: CryptoProvider>() -> [u8; IV_SIZE] {
+        let mut iv = [0u8; IV_SIZE];
+        C::random(&mut iv);
+
+        iv
+    }
+
+    /// Migrates the vault to [`SCHEMA_VERSION`], returning `true` if it was written in an older
+    /// format.
+    pub(crate) fn migrate(&mut self) -> bool {
+        if self.version == SCHEMA_VERSION {
+            return false;
+        }
+
+        // Version 1 stored secrets under `data`, which is accepted as an alias when
+        // deserializing, so only the version must be bumped for them to be saved as `secrets`.
+        self.version = SCHEMA_VERSION;
+        self.mark_dirty();
+        true
+    }
+
+    /// Records that the vault has changed and must be saved.
+    pub(crate) fn mark_dirty(&mut self) {
+        *self.dirty.get_mut() = true;
+    }
+
+    /// Records that the vault has been saved. Saving takes the vault by shared reference, so
+    /// this does too.
+    pub(crate) fn mark_clean(&self) {
+        self.dirty.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the vault has changed since it was loaded or last saved.
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
     fn validate(vault: Self) -> Result<Self, Error> {
-        if vault.version != SCHEMA_VERSION {
+        if vault.version == 0 || vault.version > SCHEMA_VERSION {
             return Err(Error::UnsupportedVaultVersion);
         }
+        if let Some(kdf) = &vault.kdf {
+            kdf.validate()?;
+        }
 
         Ok(vault)
     }
 
-    pub(crate) fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let path = path.as_ref();
-        let file = File::open(path).map_err(Error::Io)?;
+    pub fn load<R: Read>(mut source: R) -> Result<Self, Error> {
+        let mut contents = Vec::new();
+        source.read_to_end(&mut contents).map_err(Error::Io)?;
+
+        Self::parse(&contents)
+    }
+
+    pub(crate) fn parse(contents: &[u8]) -> Result<Self, Error> {
+        let format = VaultFormat::detect(contents);
+        match format.deserialize::<Vault>(contents) {
+            Ok(mut vault) => {
+                vault.format = format;
+                Self::validate(vault)
+            }
+            Err(e) => Err(Self::diagnose(format, contents, e)),
+        }
+    }
+
+    /// Explains why `contents` could not be deserialized as a vault. Vaults written in newer
+    /// formats or with unknown schemes may not have the structure we expect, so those are
+    /// reported in preference to the position of the error.
+    fn diagnose(format: VaultFormat, contents: &[u8], error: Error) -> Error {
+        if let Ok(value) = format.deserialize::<serde_json::Value>(contents) {
+            let version = value.get("version").and_then(serde_json::Value::as_u64);
+            if matches!(version, Some(v) if v > SCHEMA_VERSION as u64) {
+                return Error::UnsupportedVaultVersion;
+            }
+            if let Some(scheme) = value.get("scheme").and_then(serde_json::Value::as_str) {
+                let known = serde_json::from_value::<Scheme>(scheme.into()).is_ok();
+                if !known {
+                    return Error::UnsupportedSchemeVersion(scheme.to_string());
+                }
+            }
+        }
+
+        error
+    }
 
-        Self::load(file)
+    /// Encrypts a new sentinel with `keys`, for verifying the keys supplied at load.
+    pub(crate) fn seal_sentinel<C: CryptoProvider>(&mut self, keys: &Keys) {
+        let mut sentinel = [0u8; SENTINEL_LENGTH];
+        C::random(&mut sentinel);
+        self.sentinel = Some(EncryptedBlob::encrypt::<C>(keys, self.scheme(), &sentinel));
     }
 
-    pub fn load<R: Read>(source: R) -> Result<Self, Error> {
-        let vault = serde_json::from_reader(source).map_err(|e| Error::Serde(e))?;
+    /// Verifies that `keys` are the keys the vault was created with, by decrypting the
+    /// sentinel. Returns [`Error::InvalidKey`] if they are not, or [`Error::VaultTampered`] if
+    /// the sentinel is missing, unless `allow_unauthenticated` is set.
+    pub(crate) fn verify_sentinel<C: CryptoProvider>(
+        &self,
+        keys: &Keys,
+        allow_unauthenticated: bool,
+    ) -> Result<(), Error> {
+        match &self.sentinel {
+            None if allow_unauthenticated => Ok(()),
+            // Stripped from a vault this crate wrote, e.g. to hide that the keys are wrong
+            None => Err(Error::VaultTampered),
+            Some(sentinel) => match sentinel.decrypt::<C>(keys, self.scheme()) {
+                Ok(_) => Ok(()),
+                Err(Error::MacMismatch) | Err(Error::DecryptionFailure) => Err(Error::InvalidKey),
+                Err(e) => Err(e),
+            },
+        }
+    }
 
-        Self::validate(vault)
+    /// Computes the MAC over the entire vault (excluding any previously computed MAC), which
+    /// prevents secrets from being removed, added or reverted to older values on-disk without
+    /// detection.
+    fn compute_mac<C: CryptoProvider>(&self, keys: &Keys) -> Result<Vec<u8>, Error> {
+        // Fields are serialized in a fixed order and secrets are sorted by name, so this is
+        // deterministic for a given vault.
+        let contents = serde_json::to_vec(self).map_err(Error::Serde)?;
+        let mac = self.scheme().mac();
+        Ok(mac.compute::<C>(&keys.hmac, &[VAULT_MAC_CONTEXT, &contents]))
     }
 
-    pub fn save<P: AsRef<Path>>(&self, dest: P) -> Result<(), Error> {
-        let path = dest.as_ref();
+    /// Verifies the MAC recorded in the vault against `keys`, returning
+    /// [`Error::VaultTampered`] if it does not match, or if it is missing unless
+    /// `allow_unauthenticated` is set.
+    pub(crate) fn verify_mac<C: CryptoProvider>(
+        &self,
+        keys: &Keys,
+        allow_unauthenticated: bool,
+    ) -> Result<(), Error> {
+        match &self.mac {
+            None if allow_unauthenticated => Ok(()),
+            None => Err(Error::VaultTampered),
+            Some(mac) if C::ct_eq(mac, &self.compute_mac::<C>(keys)?) => Ok(()),
+            Some(_) => Err(Error::VaultTampered),
+        }
+    }
 
-        let file = File::create(path).map_err(Error::Io)?;
-        // using `to_writer_pretty()` makes changes to the store play nicer with version control
-        serde_json::to_writer_pretty(file, &self).map_err(Error::Serde)
+    /// Serializes the vault, authenticated with a MAC computed with `keys`.
+    pub fn to_bytes<C: CryptoProvider>(&self, keys: &Keys) -> Result<Vec<u8>, Error> {
+        let vault = AuthenticatedVault {
+            vault: self,
+            mac: self.compute_mac::<C>(keys)?,
+        };
+
+        // Writing one field per line makes changes to the store play nicer with version control.
+        // Fields are always written in the same order and secrets (like recipients) are kept
+        // sorted, so adding a secret only adds its own lines (and changes the MAC).
+        self.format.serialize(&vault)
     }
 }
 
@@ -170,7 +567,9 @@ impl Vault {
 /// implementing authenticated encryption (as AES-CBC and HMAC-SHA1), but out of
 /// an abundance of precaution we create/derive two separate keys entirely for
 /// these two operations.
-#[derive(Debug, Eq, PartialEq)]
+///
+/// The key material is wiped from memory when the `Keys` are dropped.
+#[derive(Debug, Eq, PartialEq, Zeroize, ZeroizeOnDrop)]
 pub struct Keys {
     /// The key used to encrypt the secrets.
     pub encryption: [u8; KEY_LENGTH],
@@ -182,10 +581,42 @@ impl Keys {
     /// Exports the private key(s) resident in memory to a path on-disk. The exact
     /// binary format (including key order) lines up with other implementations.
     pub fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
-        let mut file = File::create(path).map_err(Error::Io)?;
+        let path = path.as_ref();
+        let mut file = File::create(path).map_err(Error::file(path))?;
 
-        file.write_all(&self.encryption).map_err(Error::Io)?;
-        file.write_all(&self.hmac).map_err(Error::Io)
+        file.write_all(&self.encryption)
+            .map_err(Error::file(path))?;
+        file.write_all(&self.hmac).map_err(Error::file(path))
+    }
+
+    /// Exports the private key(s) resident in memory to a path on-disk as base64-encoded text
+    /// between `-----BEGIN SECURESTORE KEYS-----` and `-----END SECURESTORE KEYS-----` lines,
+    /// which is suitable for pasting into emails or CI secrets.
+    pub fn export_armored<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let encoded = Zeroizing::new(base64::encode(&self.combined()[..]));
+        let armored = Zeroizing::new(format!("{}\n{}\n{}\n", ARMOR_BEGIN, *encoded, ARMOR_END));
+
+        let path = path.as_ref();
+        let mut file = File::create(path).map_err(Error::file(path))?;
+        file.write_all(armored.as_bytes())
+            .map_err(Error::file(path))
+    }
+
+    /// Decodes the key material from an armored keyfile, as written by
+    /// [`Keys::export_armored()`]. Returns `None` if `contents` is not armored.
+    pub fn dearmor(contents: &[u8]) -> Option<Result<KeyData, Error>> {
+        let contents = std::str::from_utf8(contents).ok()?.trim();
+        let body = contents
+            .strip_prefix(ARMOR_BEGIN)?
+            .strip_suffix(ARMOR_END)
+            .map(str::trim);
+
+        Some(body.ok_or(Error::InvalidKeyfile).and_then(|body| {
+            let encoded: Zeroizing<String> = Zeroizing::new(body.split_whitespace().collect());
+            base64::decode(&*encoded)
+                .map(Zeroizing::new)
+                .map_err(|_| Error::InvalidKeyfile)
+        }))
     }
 
     /// Imports keys from a bytestream
@@ -195,77 +626,188 @@ impl Keys {
             hmac: [0u8; KEY_LENGTH],
         };
 
-        source
-            .read_exact(&mut keys.encryption)
-            .map_err(|_| Error::InvalidKeyfile)?;
-        source
-            .read_exact(&mut keys.hmac)
-            .map_err(|_| Error::InvalidKeyfile)?;
+        // Read as much as is available, to report how short the key material is
+        let mut buffer = Zeroizing::new([0u8; KEY_COUNT * KEY_LENGTH]);
+        let mut read = 0;
+        while read < buffer.len() {
+            match source.read(&mut buffer[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => return Err(Error::InvalidKeyfile),
+            }
+        }
+        if read < buffer.len() {
+            return Err(Error::KeyfileTooShort {
+                expected: buffer.len(),
+                actual: read,
+            });
+        }
+
+        keys.encryption.copy_from_slice(&buffer[..KEY_LENGTH]);
+        keys.hmac.copy_from_slice(&buffer[KEY_LENGTH..]);
 
         Ok(keys)
     }
-}
 
-use openssl::hash::MessageDigest;
-use openssl::pkey::PKey;
-use openssl::sign::Signer;
-use openssl::symm::{self, Cipher};
+    /// The full key material, for schemes which use a single key for both encryption and
+    /// authentication.
+    pub(crate) fn combined(&self) -> Zeroizing<[u8; KEY_COUNT * KEY_LENGTH]> {
+        let mut key = Zeroizing::new([0u8; KEY_COUNT * KEY_LENGTH]);
+        key[..KEY_LENGTH].copy_from_slice(&self.encryption);
+        key[KEY_LENGTH..].copy_from_slice(&self.hmac);
+
+        key
+    }
+
+    /// Compares two sets of keys in constant time.
+    pub fn ct_eq<C: CryptoProvider>(&self, other: &Keys) -> bool {
+        C::ct_eq(&self.encryption, &other.encryption) & C::ct_eq(&self.hmac, &other.hmac)
+    }
+}
 
 impl EncryptedBlob {
     /// Creates an `EncryptedBlob` from a plaintext secret.
-    pub fn encrypt(keys: &Keys, secret: &[u8]) -> EncryptedBlob {
-        let cipher = Cipher::aes_128_cbc();
-        let mut iv = [0u8; KEY_LENGTH];
+    pub fn encrypt<C: CryptoProvider>(keys: &Keys, scheme: Scheme, secret: &[u8]) -> EncryptedBlob {
+        match scheme {
+            Scheme::Aes128CbcHmacSha1 | Scheme::Aes128CbcHmacSha256 => {
+                Self::encrypt_cbc::<C>(scheme.mac(), keys, secret)
+            }
+            Scheme::Aes256Gcm => Self::encrypt_aead::<C>(Aead::Aes256Gcm, keys, secret),
+            Scheme::ChaCha20Poly1305 => {
+                Self::encrypt_aead::<C>(Aead::ChaCha20Poly1305, keys, secret)
+            }
+        }
+    }
 
-        rand::rand_bytes(&mut iv).expect("Error reading IV bytes from RNG!");
+    /// Decrypts an `EncryptedBlob` object and retrieves the plaintext equivalent
+    /// of `[EncryptedBlob::Data]`.
+    pub fn decrypt<C: CryptoProvider>(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+    ) -> Result<Vec<u8>, Error> {
+        if self.chunks.is_empty() {
+            return self.decrypt_single::<C>(keys, scheme);
+        }
+
+        // Reserve the full length up front, so no partial copies of the secret are left behind
+        let length = self.chunked_length::<C>(keys, scheme)?;
+        let mut plaintext = Vec::with_capacity(usize::try_from(length).unwrap_or(0));
+        self.decrypt_to::<C, _>(keys, scheme, &mut plaintext)?;
+        Ok(plaintext)
+    }
 
-        // Unlike with decryption, we don't expect this to ever fail
-        let payload = symm::encrypt(cipher, &keys.encryption, Some(&iv), secret)
-            .expect("Error encrypting payload!");
+    /// Decrypts the `payload` of this `EncryptedBlob` alone, ignoring any chunks.
+    pub(crate) fn decrypt_single<C: CryptoProvider>(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+    ) -> Result<Vec<u8>, Error> {
+        let plaintext = match scheme {
+            Scheme::Aes128CbcHmacSha1 | Scheme::Aes128CbcHmacSha256 => {
+                self.decrypt_cbc::<C>(scheme.mac(), keys)
+            }
+            Scheme::Aes256Gcm => self.decrypt_aead::<C>(Aead::Aes256Gcm, keys),
+            Scheme::ChaCha20Poly1305 => self.decrypt_aead::<C>(Aead::ChaCha20Poly1305, keys),
+        }?;
+        match self.compression {
+            None => Ok(plaintext),
+            Some(compression) => compression::decompress(compression, &Zeroizing::new(plaintext)),
+        }
+    }
+
+    /// Encrypts `secret` as with [`encrypt()`](Self::encrypt), first compressing it if it is
+    /// larger than `threshold` bytes and compression is supported.
+    pub fn encrypt_compressed<C: CryptoProvider>(
+        keys: &Keys,
+        scheme: Scheme,
+        secret: &[u8],
+        threshold: Option<usize>,
+    ) -> EncryptedBlob {
+        match compression::compress(secret, threshold) {
+            None => Self::encrypt::<C>(keys, scheme, secret),
+            Some((compression, compressed)) => EncryptedBlob {
+                compression: Some(compression),
+                ..Self::encrypt::<C>(keys, scheme, &compressed)
+            },
+        }
+    }
+
+    fn encrypt_cbc<C: CryptoProvider>(
+        mac: MacAlgorithm,
+        keys: &Keys,
+        secret: &[u8],
+    ) -> EncryptedBlob {
+        let mut iv = [0u8; IV_SIZE];
+        C::random(&mut iv);
+
+        let payload = C::aes_128_cbc_encrypt(&keys.encryption, &iv, secret);
 
         EncryptedBlob {
-            hmac: Self::calculate_hmac(&keys.hmac, &iv, &payload),
-            iv,
+            hmac: mac.compute::<C>(&keys.hmac, &[&iv, &payload]),
+            iv: iv.to_vec(),
             payload,
+            compression: None,
+            chunks: Vec::new(),
         }
     }
 
-    /// Decrypts an `EncryptedBlob` object and retrieves the plaintext equivalent
-    /// of `[EncryptedBlob::Data]`.
-    pub fn decrypt(&self, keys: &Keys) -> Result<Vec<u8>, Error> {
-        if !self.authenticate(&keys.hmac) {
+    fn decrypt_cbc<C: CryptoProvider>(
+        &self,
+        mac: MacAlgorithm,
+        keys: &Keys,
+    ) -> Result<Vec<u8>, Error> {
+        let mut iv = [0u8; IV_SIZE];
+        if self.iv.len() != iv.len() {
             return Err(Error::DecryptionFailure);
         }
+        if !self.authenticate::<C>(&keys.hmac, mac) {
+            return Err(Error::MacMismatch);
+        }
+        iv.copy_from_slice(&self.iv);
 
-        let cipher = Cipher::aes_128_cbc();
-        symm::decrypt(cipher, &keys.encryption, Some(&self.iv), &self.payload)
-            .map_err(|_| Error::DecryptionFailure)
+        C::aes_128_cbc_decrypt(&keys.encryption, &iv, &self.payload)
     }
 
-    fn calculate_hmac(
-        &hmac_key: &[u8; KEY_LENGTH],
-        &iv: &[u8; IV_SIZE],
-        encrypted: &[u8],
-    ) -> [u8; HMAC_SIZE] {
-        let key = PKey::hmac(&hmac_key).expect("Failed to load HMAC encryption key!");
-        let mut signer =
-            Signer::new(MessageDigest::sha1(), &key).expect("Failed to create HMAC signer!");
+    fn encrypt_aead<C: CryptoProvider>(aead: Aead, keys: &Keys, secret: &[u8]) -> EncryptedBlob {
+        let mut nonce = [0u8; NONCE_SIZE];
+        let mut tag = [0u8; TAG_SIZE];
+        C::random(&mut nonce);
+
+        let payload = C::aead_encrypt(aead, &keys.combined(), &nonce, secret, &mut tag);
 
-        signer.update(&iv).unwrap();
-        signer.update(&encrypted).unwrap();
+        EncryptedBlob {
+            iv: nonce.to_vec(),
+            hmac: tag.to_vec(),
+            payload,
+            compression: None,
+            chunks: Vec::new(),
+        }
+    }
 
-        let mut hmac = [0u8; HMAC_SIZE];
-        signer
-            .sign(&mut hmac)
-            // this is not the same as the HMAC not matching
-            .expect("Failed to create HMAC signature!");
+    fn decrypt_aead<C: CryptoProvider>(&self, aead: Aead, keys: &Keys) -> Result<Vec<u8>, Error> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        let mut tag = [0u8; TAG_SIZE];
+        if self.iv.len() != nonce.len() || self.hmac.len() != tag.len() {
+            return Err(Error::DecryptionFailure);
+        }
+        nonce.copy_from_slice(&self.iv);
+        tag.copy_from_slice(&self.hmac);
 
-        hmac
+        // Authentication is the only way AEAD decryption of well-formed input can fail
+        C::aead_decrypt(aead, &keys.combined(), &nonce, &self.payload, &tag)
+            .map_err(|_| Error::MacMismatch)
     }
 
-    /// Authenticates the encrypted payload against the provided HMAC key
-    pub fn authenticate(&self, &hmac_key: &[u8; KEY_LENGTH]) -> bool {
-        let hmac = Self::calculate_hmac(&hmac_key, &self.iv, &self.payload);
-        openssl::memcmp::eq(&hmac, &self.hmac)
+    /// Authenticates the encrypted payload against the provided HMAC key with `mac`. Only
+    /// applicable to CBC schemes, as AEAD schemes authenticate as part of decryption.
+    pub fn authenticate<C: CryptoProvider>(
+        &self,
+        hmac_key: &[u8; KEY_LENGTH],
+        mac: MacAlgorithm,
+    ) -> bool {
+        let hmac = mac.compute::<C>(hmac_key, &[&self.iv, &self.payload]);
+        C::ct_eq(&hmac, &self.hmac)
     }
 }
@@ -0,0 +1,34 @@
+//! Loading of keyfiles encrypted with [age](https://age-encryption.org), decrypted in-process
+//! with an age identity file such as those generated by `age-keygen`.
+
+use crate::errors::Error;
+use age::armor::ArmoredReader;
+use age::{DecryptError, Decryptor, Identity, IdentityFile};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Decrypts the (binary or armored) age-encrypted keyfile at `path` with any of the identities
+/// in the identity file at `identity`, returning the decrypted keyfile contents.
+pub(crate) fn decrypt_keyfile(path: &Path, identity: &Path) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let identity = File::open(identity).map_err(Error::Io)?;
+    let identities = IdentityFile::from_buffer(BufReader::new(identity))
+        .map_err(Error::Io)?
+        .into_identities()
+        .map_err(failure)?;
+
+    let file = File::open(path).map_err(Error::Io)?;
+    let decryptor = Decryptor::new(ArmoredReader::new(BufReader::new(file))).map_err(failure)?;
+    let mut reader = decryptor
+        .decrypt(identities.iter().map(|i| i.as_ref() as &dyn Identity))
+        .map_err(failure)?;
+
+    let mut plaintext = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut plaintext).map_err(Error::Io)?;
+    Ok(plaintext)
+}
+
+fn failure(e: DecryptError) -> Error {
+    Error::KeySourceFailure(format!("age failed to decrypt the keyfile: {}", e))
+}
@@ -0,0 +1,144 @@
+//! Signing of requests to AWS APIs with AWS Signature Version 4, shared by the AWS KMS key
+//! source and the S3 vault storage backend.
+
+use crate::errors::Error;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// AWS credentials, as read from the environment.
+pub(crate) struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: Zeroizing<String>,
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Reads credentials from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and (optionally)
+    /// `AWS_SESSION_TOKEN`.
+    pub fn from_env() -> Result<Self, Error> {
+        match (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            (Ok(access_key_id), Ok(secret_access_key)) => Ok(Credentials {
+                access_key_id,
+                secret_access_key: Zeroizing::new(secret_access_key),
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            }),
+            _ => Err(Error::KeySourceFailure(
+                "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set".to_string(),
+            )),
+        }
+    }
+}
+
+/// The region configured by `AWS_REGION` or `AWS_DEFAULT_REGION`.
+pub(crate) fn region_from_env() -> Result<String, Error> {
+    env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .map_err(|_| Error::KeySourceFailure("no AWS region is configured".to_string()))
+}
+
+/// Computes the AWS Signature Version 4 `Authorization` header for a request to `service`
+/// with the specified method, path and (canonical) query string, headers and payload.
+pub(crate) fn sign(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    (method, path, query): (&str, &str, &str),
+    headers: &[(&str, String)],
+    payload: &[u8],
+    timestamp: &str,
+) -> String {
+    let mut headers: Vec<(String, &str)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value.trim()))
+        .collect();
+    headers.sort();
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        method,
+        path,
+        query,
+        canonical_headers,
+        signed_headers,
+        hex(&Sha256::digest(payload))
+    );
+
+    let date = &timestamp[..8];
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let secret = Zeroizing::new(format!("AWS4{}", *credentials.secret_access_key));
+    let key = hmac_sha256(secret.as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hmac_sha256(&key, string_to_sign.as_bytes());
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id,
+        scope,
+        signed_headers,
+        hex(&signature)
+    )
+}
+
+/// Formats `time` as an ISO 8601 basic format UTC timestamp, e.g. `20150830T123600Z`.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the UNIX epoch!")
+        .as_secs();
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // Converts days since the epoch to a civil date (Howard Hinnant's `civil_from_days`)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> Zeroizing<Vec<u8>> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    Zeroizing::new(mac.finalize().into_bytes().to_vec())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,141 @@
+//! Envelope encryption of vault keys with AWS KMS.
+//!
+//! New keys are generated by KMS with `GenerateDataKey`, which also returns them wrapped by the
+//! KMS key. The wrapped keys are stored in the vault and unwrapped with `Decrypt` whenever the
+//! vault is loaded. Requests are signed with AWS Signature Version 4 using credentials from the
+//! `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and (optionally) `AWS_SESSION_TOKEN`
+//! environment variables. The region is taken from the key ARN if possible, or else from
+//! `AWS_REGION` or `AWS_DEFAULT_REGION`.
+
+use super::aws::{self, sign, timestamp, Credentials};
+use super::http;
+use crate::errors::Error;
+use crate::shared::{KeyData, WrappedKey, KEY_COUNT, KEY_LENGTH};
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use std::env;
+use std::time::SystemTime;
+use zeroize::Zeroizing;
+
+/// The provider recorded in [`WrappedKey`] for keys wrapped by AWS KMS.
+pub(crate) const PROVIDER: &str = "aws-kms";
+const SERVICE: &str = "AWS KMS";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GenerateDataKeyResponse {
+    ciphertext_blob: String,
+    key_id: String,
+    plaintext: Zeroizing<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DecryptResponse {
+    plaintext: Zeroizing<String>,
+}
+
+/// Generates new vault keys with the KMS key `key_id`, returning both the plaintext keys and
+/// the keys wrapped by KMS.
+pub(crate) fn generate(key_id: &str) -> Result<(KeyData, WrappedKey), Error> {
+    let request = serde_json::json!({
+        "KeyId": key_id,
+        "NumberOfBytes": KEY_COUNT * KEY_LENGTH,
+    });
+    let response: GenerateDataKeyResponse = call(key_id, "GenerateDataKey", &request)?;
+
+    let wrapped = WrappedKey {
+        provider: PROVIDER.to_string(),
+        key: response.key_id,
+        ciphertext: base64::decode(&response.ciphertext_blob).map_err(|_| malformed())?,
+    };
+    Ok((decode(&response.plaintext)?, wrapped))
+}
+
+/// Unwraps the vault keys in `wrapped` with the KMS key `key_id`.
+pub(crate) fn unwrap(key_id: &str, wrapped: &WrappedKey) -> Result<KeyData, Error> {
+    if wrapped.provider != PROVIDER {
+        return Err(Error::MissingWrappedKey);
+    }
+
+    let request = serde_json::json!({
+        "KeyId": key_id,
+        "CiphertextBlob": base64::encode(&wrapped.ciphertext),
+    });
+    let response: DecryptResponse = call(key_id, "Decrypt", &request)?;
+    decode(&response.plaintext)
+}
+
+/// Invokes the KMS API `action` with the JSON `request`.
+fn call<T: DeserializeOwned>(
+    key_id: &str,
+    action: &str,
+    request: &serde_json::Value,
+) -> Result<T, Error> {
+    let credentials = Credentials::from_env()?;
+    let region = region(key_id)?;
+    let endpoint = env::var("AWS_ENDPOINT_URL_KMS")
+        .or_else(|_| env::var("AWS_ENDPOINT_URL"))
+        .unwrap_or_else(|_| format!("https://kms.{}.amazonaws.com", region));
+    let endpoint = endpoint.trim_end_matches('/');
+    let host = endpoint
+        .split("://")
+        .nth(1)
+        .unwrap_or(endpoint)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+
+    let body = serde_json::to_vec(request).expect("Failed to serialize KMS request!");
+    let timestamp = timestamp(SystemTime::now());
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1".to_string()),
+        ("host", host.to_string()),
+        ("x-amz-date", timestamp.clone()),
+        ("x-amz-target", format!("TrentService.{}", action)),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token", token.clone()));
+    }
+    let authorization = sign(
+        &credentials,
+        &region,
+        "kms",
+        ("POST", "/", ""),
+        &headers,
+        &body,
+        &timestamp,
+    );
+    headers.push(("authorization", authorization));
+
+    // The HTTP client supplies the host header itself
+    let headers: Vec<(&str, &str)> = headers
+        .iter()
+        .filter(|(name, _)| *name != "host")
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+    let response = http::post(SERVICE, &format!("{}/", endpoint), &headers, &body)?;
+    http::parse(SERVICE, &response)
+}
+
+/// The region of the KMS key `key_id`.
+fn region(key_id: &str) -> Result<String, Error> {
+    // arn:aws:kms:<region>:<account>:key/<id>
+    if key_id.starts_with("arn:") {
+        if let Some(region) = key_id.split(':').nth(3).filter(|r| !r.is_empty()) {
+            return Ok(region.to_string());
+        }
+    }
+
+    aws::region_from_env()
+}
+
+fn decode(plaintext: &str) -> Result<KeyData, Error> {
+    base64::decode(plaintext)
+        .map(Zeroizing::new)
+        .map_err(|_| malformed())
+}
+
+fn malformed() -> Error {
+    Error::KeySourceFailure(format!("malformed response from {}", SERVICE))
+}
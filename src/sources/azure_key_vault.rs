@@ -0,0 +1,121 @@
+//! Wrapping of vault keys with an Azure Key Vault or Managed HSM key.
+//!
+//! New keys are generated locally and wrapped with the Key Vault `wrapkey` operation (RSA-OAEP-256);
+//! the wrapped keys and the ID of the key version used are stored in the vault, and unwrapped with
+//! `unwrapkey` whenever the vault is loaded. Requests are authorized with the access token in
+//! `AZURE_ACCESS_TOKEN` if set, or else with a token for the managed identity of the Azure VM
+//! obtained from the instance metadata service.
+
+use super::http;
+use crate::errors::Error;
+use crate::shared::{KeyData, WrappedKey};
+use serde_derive::Deserialize;
+use std::env;
+use zeroize::Zeroizing;
+
+/// The provider recorded in [`WrappedKey`] for keys wrapped by Azure Key Vault.
+pub(crate) const PROVIDER: &str = "azure-key-vault";
+const SERVICE: &str = "Azure Key Vault";
+const API_VERSION: &str = "7.4";
+const ALGORITHM: &str = "RSA-OAEP-256";
+
+#[derive(Deserialize)]
+struct KeyOperationResponse {
+    kid: String,
+    value: Zeroizing<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Zeroizing<String>,
+}
+
+/// Wraps `key_data` with the latest version of the key `key_name` in the Key Vault or Managed
+/// HSM at `vault_url`.
+pub(crate) fn wrap(vault_url: &str, key_name: &str, key_data: &[u8]) -> Result<WrappedKey, Error> {
+    let url = key_url(vault_url, key_name, None);
+    let response = call(vault_url, &format!("{}/wrapkey", url), key_data)?;
+
+    Ok(WrappedKey {
+        provider: PROVIDER.to_string(),
+        key: response.kid,
+        ciphertext: decode(&response.value)?.to_vec(),
+    })
+}
+
+/// Unwraps the vault keys in `wrapped` with the version of the key `key_name` which wrapped
+/// them.
+pub(crate) fn unwrap(
+    vault_url: &str,
+    key_name: &str,
+    wrapped: &WrappedKey,
+) -> Result<KeyData, Error> {
+    if wrapped.provider != PROVIDER {
+        return Err(Error::MissingWrappedKey);
+    }
+
+    // The key ID is of the form https://<vault>/keys/<name>/<version>
+    let version = wrapped.key.rsplit('/').next().filter(|v| !v.is_empty());
+    let url = key_url(vault_url, key_name, version);
+    let response = call(
+        vault_url,
+        &format!("{}/unwrapkey", url),
+        &wrapped.ciphertext,
+    )?;
+    decode(&response.value)
+}
+
+fn key_url(vault_url: &str, key_name: &str, version: Option<&str>) -> String {
+    let mut url = format!("{}/keys/{}", vault_url.trim_end_matches('/'), key_name);
+    if let Some(version) = version {
+        url.push('/');
+        url.push_str(version);
+    }
+    url
+}
+
+/// Performs the key operation at `url` on `value`.
+fn call(vault_url: &str, url: &str, value: &[u8]) -> Result<KeyOperationResponse, Error> {
+    let token = access_token(vault_url)?;
+    let authorization = Zeroizing::new(format!("Bearer {}", *token));
+    let url = format!("{}?api-version={}", url, API_VERSION);
+
+    let value = Zeroizing::new(base64::encode_config(value, base64::URL_SAFE_NO_PAD));
+    let request = serde_json::json!({ "alg": ALGORITHM, "value": value.as_str() });
+    let body = Zeroizing::new(serde_json::to_vec(&request).expect("Failed to serialize request!"));
+    let headers = [
+        ("Authorization", authorization.as_str()),
+        ("Content-Type", "application/json"),
+    ];
+    let response = http::post(SERVICE, &url, &headers, &body)?;
+    http::parse(SERVICE, &response)
+}
+
+/// An access token for the Key Vault (or Managed HSM) at `vault_url`.
+fn access_token(vault_url: &str) -> Result<Zeroizing<String>, Error> {
+    if let Ok(token) = env::var("AZURE_ACCESS_TOKEN") {
+        return Ok(Zeroizing::new(token));
+    }
+
+    let resource = if vault_url.contains(".managedhsm.") {
+        "https://managedhsm.azure.net"
+    } else {
+        "https://vault.azure.net"
+    };
+    let endpoint = env::var("IDENTITY_ENDPOINT")
+        .unwrap_or_else(|_| "http://169.254.169.254/metadata/identity/oauth2/token".into());
+    let url = format!("{}?api-version=2018-02-01&resource={}", endpoint, resource);
+    let response = http::get(
+        "the Azure instance metadata service",
+        &url,
+        &[("Metadata", "true")],
+    )?;
+    let response: TokenResponse = http::parse("the Azure instance metadata service", &response)?;
+    Ok(response.access_token)
+}
+
+fn decode(value: &str) -> Result<KeyData, Error> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::KeySourceFailure(format!("malformed response from {}", SERVICE)))
+}
@@ -0,0 +1,108 @@
+//! Wrapping of vault keys with Google Cloud KMS.
+//!
+//! New keys are generated locally and wrapped with the KMS `encrypt` method; the wrapped keys
+//! are stored in the vault and unwrapped with `decrypt` whenever the vault is loaded. Requests
+//! are authorized with the OAuth access token in `GOOGLE_OAUTH_ACCESS_TOKEN` if set, or else
+//! with a token for the default service account obtained from the GCE metadata server.
+
+use super::http;
+use crate::errors::Error;
+use crate::shared::{KeyData, WrappedKey};
+use serde_derive::Deserialize;
+use std::env;
+use zeroize::Zeroizing;
+
+/// The provider recorded in [`WrappedKey`] for keys wrapped by Google Cloud KMS.
+pub(crate) const PROVIDER: &str = "gcp-kms";
+const SERVICE: &str = "Google Cloud KMS";
+
+#[derive(Deserialize)]
+struct EncryptResponse {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct DecryptResponse {
+    plaintext: Zeroizing<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Zeroizing<String>,
+}
+
+/// Wraps `key_data` with the KMS key `key_name`, of the form
+/// `projects/*/locations/*/keyRings/*/cryptoKeys/*`.
+pub(crate) fn wrap(key_name: &str, key_data: &[u8]) -> Result<WrappedKey, Error> {
+    let request = serde_json::json!({ "plaintext": base64::encode(key_data) });
+    let response: EncryptResponse = call(key_name, "encrypt", &request)?;
+
+    Ok(WrappedKey {
+        provider: PROVIDER.to_string(),
+        key: key_name.to_string(),
+        ciphertext: base64::decode(&response.ciphertext).map_err(|_| malformed())?,
+    })
+}
+
+/// Unwraps the vault keys in `wrapped` with the KMS key `key_name`.
+pub(crate) fn unwrap(key_name: &str, wrapped: &WrappedKey) -> Result<KeyData, Error> {
+    if wrapped.provider != PROVIDER {
+        return Err(Error::MissingWrappedKey);
+    }
+
+    let request = serde_json::json!({ "ciphertext": base64::encode(&wrapped.ciphertext) });
+    let response: DecryptResponse = call(key_name, "decrypt", &request)?;
+    base64::decode(&*response.plaintext)
+        .map(Zeroizing::new)
+        .map_err(|_| malformed())
+}
+
+/// Invokes the KMS method `method` on the key `key_name` with the JSON `request`.
+fn call<T: serde::de::DeserializeOwned>(
+    key_name: &str,
+    method: &str,
+    request: &serde_json::Value,
+) -> Result<T, Error> {
+    let token = access_token()?;
+    let authorization = Zeroizing::new(format!("Bearer {}", *token));
+    let endpoint = env::var("CLOUDSDK_API_ENDPOINT_OVERRIDES_CLOUDKMS")
+        .unwrap_or_else(|_| "https://cloudkms.googleapis.com/".to_string());
+    let url = format!(
+        "{}/v1/{}:{}",
+        endpoint.trim_end_matches('/'),
+        key_name,
+        method
+    );
+
+    let body = serde_json::to_vec(request).expect("Failed to serialize KMS request!");
+    let headers = [
+        ("Authorization", authorization.as_str()),
+        ("Content-Type", "application/json"),
+    ];
+    let response = http::post(SERVICE, &url, &headers, &body)?;
+    http::parse(SERVICE, &response)
+}
+
+/// An OAuth access token for Google Cloud APIs.
+fn access_token() -> Result<Zeroizing<String>, Error> {
+    if let Ok(token) = env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        return Ok(Zeroizing::new(token));
+    }
+
+    let host = env::var("GCE_METADATA_HOST").unwrap_or_else(|_| "metadata.google.internal".into());
+    let url = format!(
+        "http://{}/computeMetadata/v1/instance/service-accounts/default/token",
+        host
+    );
+    let response = http::get(
+        "the GCE metadata server",
+        &url,
+        &[("Metadata-Flavor", "Google")],
+    )?;
+    let response: TokenResponse = http::parse("the GCE metadata server", &response)?;
+    Ok(response.access_token)
+}
+
+fn malformed() -> Error {
+    Error::KeySourceFailure(format!("malformed response from {}", SERVICE))
+}
@@ -0,0 +1,38 @@
+//! Loading of OpenPGP-encrypted keyfiles by shelling out to `gpg`.
+//!
+//! Decryption is performed by the user's existing GnuPG installation (honoring `GNUPGHOME` and
+//! any running `gpg-agent`), so keyfiles encrypted to a team's existing OpenPGP keys can be
+//! used as-is.
+
+use crate::errors::Error;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// Decrypts the keyfile at `path` with `gpg`, returning the decrypted keyfile contents.
+pub(crate) fn decrypt_keyfile(path: &Path) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut child = Command::new("gpg")
+        .args(["--batch", "--quiet", "--decrypt"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::Io)?;
+
+    // Read the plaintext directly into a buffer we control so it can be wiped afterwards
+    let mut plaintext = Zeroizing::new(Vec::new());
+    let mut stdout = child.stdout.take().expect("gpg stdout is piped");
+    stdout.read_to_end(&mut plaintext).map_err(Error::Io)?;
+
+    let output = child.wait_with_output().map_err(Error::Io)?;
+    if !output.status.success() {
+        return Err(Error::KeySourceFailure(format!(
+            "gpg failed to decrypt the keyfile: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(plaintext)
+}
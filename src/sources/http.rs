@@ -0,0 +1,78 @@
+//! A minimal blocking HTTP client for the JSON APIs of key management services.
+
+use crate::errors::Error;
+use serde::de::DeserializeOwned;
+use std::io::Read;
+use zeroize::Zeroizing;
+
+/// The upper bound on the size of a response we are willing to read from a service.
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+/// POSTs `body` to `url` with the specified headers, returning the response body. Errors
+/// reported by the service named `service` are returned as [`Error::KeySourceFailure`].
+pub(crate) fn post(
+    service: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut request = ureq::post(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+
+    read_response(service, request.send_bytes(body))
+}
+
+/// GETs `url` with the specified headers, returning the response body.
+#[cfg(any(feature = "azure", feature = "gcp"))]
+pub(crate) fn get(
+    service: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut request = ureq::get(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+
+    read_response(service, request.call())
+}
+
+/// Parses the JSON response `body` returned by `service`.
+pub(crate) fn parse<T: DeserializeOwned>(service: &str, body: &[u8]) -> Result<T, Error> {
+    serde_json::from_slice(body)
+        .map_err(|e| Error::KeySourceFailure(format!("malformed response from {}: {}", service, e)))
+}
+
+fn read_response(
+    service: &str,
+    response: Result<ureq::Response, ureq::Error>,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(code, response)) => {
+            let message = response.into_string().unwrap_or_default();
+            return Err(Error::KeySourceFailure(format!(
+                "{} returned HTTP {}: {}",
+                service,
+                code,
+                message.trim()
+            )));
+        }
+        Err(e) => {
+            return Err(Error::KeySourceFailure(format!(
+                "{} request failed: {}",
+                service, e
+            )))
+        }
+    };
+
+    let mut body = Zeroizing::new(Vec::new());
+    response
+        .into_reader()
+        .take(MAX_RESPONSE_SIZE)
+        .read_to_end(&mut body)
+        .map_err(Error::Io)?;
+    Ok(body)
+}
@@ -0,0 +1,27 @@
+//! Storage of vault keys in the platform credential store: the macOS Keychain, the Windows
+//! Credential Manager, or the Secret Service (e.g. GNOME Keyring or KWallet) on Linux.
+
+use crate::errors::Error;
+use keyring::Entry;
+use zeroize::Zeroizing;
+
+/// Stores `keys` in the credential store entry identified by `service` and `account`,
+/// replacing any existing secret.
+pub(crate) fn store(service: &str, account: &str, keys: &[u8]) -> Result<(), Error> {
+    Entry::new(service, account)
+        .and_then(|entry| entry.set_secret(keys))
+        .map_err(failure)
+}
+
+/// Retrieves the keys stored in the credential store entry identified by `service` and
+/// `account`.
+pub(crate) fn retrieve(service: &str, account: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+    Entry::new(service, account)
+        .and_then(|entry| entry.get_secret())
+        .map(Zeroizing::new)
+        .map_err(failure)
+}
+
+fn failure(e: keyring::Error) -> Error {
+    Error::KeySourceFailure(format!("OS keychain error: {}", e))
+}
@@ -0,0 +1,36 @@
+//! Key sources backed by external tools, agents and services. Each is gated behind its own
+//! feature so that users only pay for the integrations they use.
+
+#[cfg(feature = "age")]
+pub(crate) mod age;
+#[cfg(any(feature = "aws-kms", feature = "s3"))]
+pub(crate) mod aws;
+#[cfg(feature = "aws-kms")]
+pub(crate) mod aws_kms;
+#[cfg(feature = "azure")]
+pub(crate) mod azure_key_vault;
+#[cfg(feature = "gcp")]
+pub(crate) mod gcp_kms;
+#[cfg(feature = "gpg")]
+pub(crate) mod gpg;
+#[cfg(any(
+    feature = "aws-kms",
+    feature = "azure",
+    feature = "gcp",
+    feature = "vault-transit"
+))]
+mod http;
+#[cfg(feature = "keychain")]
+pub(crate) mod keychain;
+#[cfg(feature = "pkcs11")]
+pub(crate) mod pkcs11;
+#[cfg(all(feature = "ssh-agent", unix))]
+pub(crate) mod ssh_agent;
+#[cfg(feature = "tpm")]
+pub(crate) mod tpm;
+#[cfg(feature = "vault-transit")]
+pub(crate) mod vault_transit;
+#[cfg(feature = "recipients")]
+pub(crate) mod x25519;
+#[cfg(feature = "yubikey")]
+pub(crate) mod yubikey;
@@ -0,0 +1,79 @@
+//! Derivation of vault keys with an HMAC key held in a PKCS#11 token, such as an HSM or
+//! smartcard.
+//!
+//! The key material is the HMAC-SHA256 computed by the token over a challenge which binds the
+//! vault IV, so the HMAC key itself never leaves the token. Any secret key object permitted to
+//! sign with `CKM_SHA256_HMAC` (e.g. a `CKK_GENERIC_SECRET` key) may be used.
+
+use crate::errors::Error;
+use crate::shared::{IV_SIZE, KEY_COUNT, KEY_LENGTH};
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, ObjectClass};
+use cryptoki::session::UserType;
+use cryptoki::types::AuthPin;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const CHALLENGE_PREFIX: &[u8] = b"securestore pkcs11 key derivation v1\0";
+
+/// Derives the raw material for [`KEY_COUNT`] keys with the secret key labelled `label` in the
+/// token in slot `slot` of the PKCS#11 module at `module`, logging in with `pin`.
+pub(crate) fn derive_keys(
+    module: &Path,
+    slot: u64,
+    pin: &str,
+    label: &str,
+    iv: &[u8; IV_SIZE],
+) -> Result<Zeroizing<[u8; KEY_COUNT * KEY_LENGTH]>, Error> {
+    let pkcs11 = Pkcs11::new(module).map_err(failure)?;
+    pkcs11
+        .initialize(CInitializeArgs::OsThreads)
+        .map_err(failure)?;
+
+    let slot = pkcs11
+        .get_slots_with_token()
+        .map_err(failure)?
+        .into_iter()
+        .find(|s| s.id() == slot)
+        .ok_or_else(|| Error::KeySourceFailure(format!("no PKCS#11 token in slot {}", slot)))?;
+
+    let session = pkcs11.open_ro_session(slot).map_err(failure)?;
+    session
+        .login(UserType::User, Some(&AuthPin::new(pin.to_string())))
+        .map_err(failure)?;
+
+    let template = [
+        Attribute::Class(ObjectClass::SECRET_KEY),
+        Attribute::Label(label.as_bytes().to_vec()),
+    ];
+    let key = session
+        .find_objects(&template)
+        .map_err(failure)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            Error::KeySourceFailure(format!("no PKCS#11 secret key labelled {}", label))
+        })?;
+
+    let mut challenge = CHALLENGE_PREFIX.to_vec();
+    challenge.extend_from_slice(iv);
+    let mac = Zeroizing::new(
+        session
+            .sign(&Mechanism::Sha256Hmac, key, &challenge)
+            .map_err(failure)?,
+    );
+    if mac.len() != KEY_COUNT * KEY_LENGTH {
+        return Err(Error::KeySourceFailure(
+            "PKCS#11 token returned a truncated HMAC".to_string(),
+        ));
+    }
+
+    let mut key_data = Zeroizing::new([0u8; KEY_COUNT * KEY_LENGTH]);
+    key_data.copy_from_slice(&mac);
+    Ok(key_data)
+}
+
+fn failure(e: cryptoki::error::Error) -> Error {
+    Error::KeySourceFailure(format!("PKCS#11 error: {}", e))
+}
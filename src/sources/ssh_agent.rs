@@ -0,0 +1,162 @@
+//! Derivation of vault keys from a signature made by a key held in ssh-agent.
+//!
+//! The agent is asked to sign a challenge which binds the vault IV, and the signature is
+//! expanded into the key material with HKDF-SHA256 (salted with the same IV). The same signature
+//! must be produced every time the vault is unlocked, so only key types with deterministic
+//! signatures (Ed25519 and RSA) are supported.
+
+use crate::errors::Error;
+use crate::shared::{IV_SIZE, KEY_COUNT, KEY_LENGTH};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use zeroize::Zeroizing;
+
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_AGENT_RSA_SHA2_256: u32 = 2;
+
+/// The upper bound on the size of a reply we are willing to read from the agent.
+const MAX_MESSAGE_SIZE: usize = 256 * 1024;
+
+const CHALLENGE_PREFIX: &[u8] = b"securestore ssh-agent key derivation v1\0";
+const HKDF_INFO: &[u8] = b"securestore ssh-agent keys";
+
+/// Derives the raw material for [`KEY_COUNT`] keys by having the agent at `SSH_AUTH_SOCK` sign a
+/// challenge for the vault with IV `iv`, using the key whose comment or `SHA256:` fingerprint
+/// matches `identity`.
+pub(crate) fn derive_keys(
+    identity: &str,
+    iv: &[u8; IV_SIZE],
+) -> Result<Zeroizing<[u8; KEY_COUNT * KEY_LENGTH]>, Error> {
+    let socket = std::env::var_os("SSH_AUTH_SOCK")
+        .ok_or_else(|| failure("SSH_AUTH_SOCK is not set".to_string()))?;
+    let mut agent = UnixStream::connect(socket).map_err(Error::Io)?;
+
+    let key = find_identity(&mut agent, identity)?;
+    let flags = match read_string(&mut &key[..])? {
+        b"ssh-ed25519" => 0,
+        b"ssh-rsa" => SSH_AGENT_RSA_SHA2_256,
+        other => {
+            return Err(failure(format!(
+                "ssh-agent key type {} does not produce deterministic signatures",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    };
+
+    let mut challenge = CHALLENGE_PREFIX.to_vec();
+    challenge.extend_from_slice(iv);
+
+    let mut request = vec![SSH_AGENTC_SIGN_REQUEST];
+    put_string(&mut request, &key);
+    put_string(&mut request, &challenge);
+    request.extend_from_slice(&flags.to_be_bytes());
+
+    let response = Zeroizing::new(transact(&mut agent, &request)?);
+    let mut response = &response[..];
+    if read_u8(&mut response)? != SSH_AGENT_SIGN_RESPONSE {
+        return Err(failure(
+            "ssh-agent refused to sign the vault challenge".to_string(),
+        ));
+    }
+    let signature = read_string(&mut response)?;
+
+    let mut key_data = Zeroizing::new([0u8; KEY_COUNT * KEY_LENGTH]);
+    Hkdf::<Sha256>::new(Some(iv), signature)
+        .expand(HKDF_INFO, &mut *key_data)
+        .expect("HKDF output length is within bounds");
+
+    Ok(key_data)
+}
+
+/// Returns the public key blob of the agent identity matching `identity`.
+fn find_identity(agent: &mut UnixStream, identity: &str) -> Result<Vec<u8>, Error> {
+    let response = transact(agent, &[SSH_AGENTC_REQUEST_IDENTITIES])?;
+    let mut response = &response[..];
+    if read_u8(&mut response)? != SSH_AGENT_IDENTITIES_ANSWER {
+        return Err(failure("ssh-agent refused to list identities".to_string()));
+    }
+
+    for _ in 0..read_u32(&mut response)? {
+        let blob = read_string(&mut response)?;
+        let comment = read_string(&mut response)?;
+
+        if comment == identity.as_bytes() || fingerprint(blob) == identity {
+            return Ok(blob.to_vec());
+        }
+    }
+
+    Err(failure(format!(
+        "ssh-agent holds no key matching {}",
+        identity
+    )))
+}
+
+/// The fingerprint of a public key blob, in the format printed by `ssh-add -l`.
+fn fingerprint(blob: &[u8]) -> String {
+    let digest = Sha256::digest(blob);
+    format!(
+        "SHA256:{}",
+        base64::encode_config(&digest, base64::STANDARD_NO_PAD)
+    )
+}
+
+/// Sends a single request to the agent and returns its reply.
+fn transact(agent: &mut UnixStream, request: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut message = Vec::with_capacity(4 + request.len());
+    put_string(&mut message, request);
+    agent.write_all(&message).map_err(Error::Io)?;
+
+    let mut length = [0u8; 4];
+    agent.read_exact(&mut length).map_err(Error::Io)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length == 0 || length > MAX_MESSAGE_SIZE {
+        return Err(malformed());
+    }
+
+    let mut response = vec![0u8; length];
+    agent.read_exact(&mut response).map_err(Error::Io)?;
+    Ok(response)
+}
+
+fn put_string(buffer: &mut Vec<u8>, value: &[u8]) {
+    buffer.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(value);
+}
+
+fn read_u8(buffer: &mut &[u8]) -> Result<u8, Error> {
+    let (&value, rest) = buffer.split_first().ok_or_else(malformed)?;
+    *buffer = rest;
+    Ok(value)
+}
+
+fn read_u32(buffer: &mut &[u8]) -> Result<u32, Error> {
+    if buffer.len() < 4 {
+        return Err(malformed());
+    }
+    let (value, rest) = buffer.split_at(4);
+    *buffer = rest;
+    Ok(u32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+fn read_string<'a>(buffer: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let length = read_u32(buffer)? as usize;
+    if buffer.len() < length {
+        return Err(malformed());
+    }
+    let (value, rest) = buffer.split_at(length);
+    *buffer = rest;
+    Ok(value)
+}
+
+fn malformed() -> Error {
+    failure("malformed reply from ssh-agent".to_string())
+}
+
+fn failure(message: String) -> Error {
+    Error::KeySourceFailure(message)
+}
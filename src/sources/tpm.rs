@@ -0,0 +1,171 @@
+//! Sealing of vault keys to a TPM 2.0, by shelling out to tpm2-tools.
+//!
+//! Keys are sealed as a data object under the owner hierarchy's primary storage key, optionally
+//! bound to a policy over the current values of a set of PCRs. The primary key is recreated from
+//! the TPM's seed each time it is needed, so the sealed keyfile can only be unsealed by the TPM
+//! which sealed it, and only while the selected PCRs retain their values.
+
+use crate::errors::Error;
+use crate::shared::{to_base64, vec_from_base64};
+use serde_derive::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zeroize::Zeroizing;
+
+/// The on-disk representation of keys sealed to a TPM.
+#[derive(Serialize, Deserialize)]
+struct SealedKeyfile {
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    public: Vec<u8>,
+    #[serde(serialize_with = "to_base64", deserialize_with = "vec_from_base64")]
+    private: Vec<u8>,
+    /// The PCR selection (e.g. `sha256:0,7`) the sealed object's policy is bound to, if any
+    #[serde(default)]
+    pcrs: Option<String>,
+}
+
+/// Seals `keys` to the TPM, optionally bound to the current values of the PCRs selected by
+/// `pcrs` (in tpm2-tools syntax, e.g. `sha256:0,7`), and writes the sealed keyfile to `path`.
+pub(crate) fn seal(keys: &[u8], path: &Path, pcrs: Option<&str>) -> Result<(), Error> {
+    let scratch = ScratchDir::new()?;
+    let primary = scratch.path("primary.ctx");
+    let policy = scratch.path("policy.digest");
+    let public = scratch.path("sealed.pub");
+    let private = scratch.path("sealed.priv");
+
+    create_primary(&primary)?;
+
+    let mut create = Command::new("tpm2_create");
+    create
+        .args(["-Q", "-C"])
+        .arg(&primary)
+        .args(["-i", "-", "-u"])
+        .arg(&public)
+        .arg("-r")
+        .arg(&private);
+    if let Some(pcrs) = pcrs {
+        run(Command::new("tpm2_createpolicy")
+            .args(["-Q", "--policy-pcr", "-l", pcrs, "-L"])
+            .arg(&policy))?;
+        // Only the PCR policy may authorize unsealing
+        create
+            .arg("-L")
+            .arg(&policy)
+            .args(["-a", "fixedtpm|fixedparent"]);
+    }
+    run_with_input(&mut create, keys)?;
+
+    let sealed = SealedKeyfile {
+        public: std::fs::read(&public).map_err(Error::file(&public))?,
+        private: std::fs::read(&private).map_err(Error::file(&private))?,
+        pcrs: pcrs.map(str::to_string),
+    };
+    let mut file = File::create(path).map_err(Error::file(path))?;
+    serde_json::to_writer_pretty(&mut file, &sealed).map_err(Error::Serde)?;
+    file.write_all(b"\n").map_err(Error::file(path))
+}
+
+/// Unseals the keys in the sealed keyfile at `path` with the TPM.
+pub(crate) fn unseal(path: &Path) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let file = File::open(path).map_err(Error::file(path))?;
+    let sealed: SealedKeyfile = serde_json::from_reader(file).map_err(|_| Error::InvalidKeyfile)?;
+
+    let scratch = ScratchDir::new()?;
+    let primary = scratch.path("primary.ctx");
+    let public = scratch.path("sealed.pub");
+    let private = scratch.path("sealed.priv");
+    let context = scratch.path("sealed.ctx");
+    std::fs::write(&public, &sealed.public).map_err(Error::file(&public))?;
+    std::fs::write(&private, &sealed.private).map_err(Error::file(&private))?;
+
+    create_primary(&primary)?;
+    run(Command::new("tpm2_load")
+        .args(["-Q", "-C"])
+        .arg(&primary)
+        .arg("-u")
+        .arg(&public)
+        .arg("-r")
+        .arg(&private)
+        .arg("-c")
+        .arg(&context))?;
+
+    let mut unseal = Command::new("tpm2_unseal");
+    unseal.args(["-Q", "-c"]).arg(&context);
+    if let Some(pcrs) = &sealed.pcrs {
+        unseal.arg("-p").arg(format!("pcr:{}", pcrs));
+    }
+    run(&mut unseal)
+}
+
+fn create_primary(context: &Path) -> Result<(), Error> {
+    run(Command::new("tpm2_createprimary")
+        .args(["-Q", "-C", "o", "-c"])
+        .arg(context))
+    .map(|_| ())
+}
+
+fn run(command: &mut Command) -> Result<Zeroizing<Vec<u8>>, Error> {
+    run_with_input(command.stdin(Stdio::null()), &[])
+}
+
+/// Runs `command` with `input` on stdin, returning its stdout.
+fn run_with_input(command: &mut Command, input: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::Io)?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(input).map_err(Error::Io)?;
+    drop(stdin);
+
+    let output = child.wait_with_output().map_err(Error::Io)?;
+    let stdout = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        return Err(Error::KeySourceFailure(format!(
+            "{:?} failed: {}",
+            command.get_program(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(stdout)
+}
+
+/// A private directory for the intermediate files used by tpm2-tools, removed when dropped.
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn new() -> Result<Self, Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "securestore-tpm.{}.{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&path).map_err(Error::file(&path))?;
+
+        Ok(ScratchDir(path))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
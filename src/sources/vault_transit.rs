@@ -0,0 +1,95 @@
+//! Wrapping of vault keys with the transit secrets engine of HashiCorp Vault.
+//!
+//! New keys are generated locally and wrapped with the transit `encrypt` endpoint; the wrapped
+//! keys (in Vault's `vault:v<version>:<ciphertext>` format) are stored in the vault and unwrapped
+//! with the `decrypt` endpoint whenever the vault is loaded.
+
+use super::http;
+use crate::errors::Error;
+use crate::shared::{KeyData, WrappedKey};
+use serde_derive::Deserialize;
+use zeroize::Zeroizing;
+
+/// The provider recorded in [`WrappedKey`] for keys wrapped by Vault transit.
+pub(crate) const PROVIDER: &str = "vault-transit";
+const SERVICE: &str = "Vault";
+
+#[derive(Deserialize)]
+struct Response<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct EncryptResponse {
+    ciphertext: String,
+}
+
+#[derive(Deserialize)]
+struct DecryptResponse {
+    plaintext: Zeroizing<String>,
+}
+
+/// Wraps `key_data` with the transit key `key` of the Vault server at `addr`.
+pub(crate) fn wrap(
+    addr: &str,
+    key: &str,
+    token: &str,
+    key_data: &[u8],
+) -> Result<WrappedKey, Error> {
+    let plaintext = Zeroizing::new(base64::encode(key_data));
+    let request = serde_json::json!({ "plaintext": plaintext.as_str() });
+    let response: EncryptResponse = call(addr, "encrypt", key, token, &request)?;
+
+    Ok(WrappedKey {
+        provider: PROVIDER.to_string(),
+        key: key.to_string(),
+        ciphertext: response.ciphertext.into_bytes(),
+    })
+}
+
+/// Unwraps the vault keys in `wrapped` with the transit key `key` of the Vault server at
+/// `addr`.
+pub(crate) fn unwrap(
+    addr: &str,
+    key: &str,
+    token: &str,
+    wrapped: &WrappedKey,
+) -> Result<KeyData, Error> {
+    if wrapped.provider != PROVIDER {
+        return Err(Error::MissingWrappedKey);
+    }
+
+    let ciphertext = std::str::from_utf8(&wrapped.ciphertext).map_err(|_| {
+        Error::KeySourceFailure("the wrapped vault keys are not Vault ciphertext".to_string())
+    })?;
+    let request = serde_json::json!({ "ciphertext": ciphertext });
+    let response: DecryptResponse = call(addr, "decrypt", key, token, &request)?;
+    base64::decode(&*response.plaintext)
+        .map(Zeroizing::new)
+        .map_err(|_| Error::KeySourceFailure(format!("malformed response from {}", SERVICE)))
+}
+
+/// Invokes the transit endpoint `operation` for the key `key` with the JSON `request`.
+fn call<T: serde::de::DeserializeOwned>(
+    addr: &str,
+    operation: &str,
+    key: &str,
+    token: &str,
+    request: &serde_json::Value,
+) -> Result<T, Error> {
+    let url = format!(
+        "{}/v1/transit/{}/{}",
+        addr.trim_end_matches('/'),
+        operation,
+        key
+    );
+
+    let body = Zeroizing::new(serde_json::to_vec(request).expect("Failed to serialize request!"));
+    let headers = [
+        ("X-Vault-Token", token),
+        ("Content-Type", "application/json"),
+    ];
+    let response = http::post(SERVICE, &url, &headers, &body)?;
+    let response: Response<T> = http::parse(SERVICE, &response)?;
+    Ok(response.data)
+}
@@ -0,0 +1,113 @@
+//! Encryption of the vault keys to the X25519 public keys of recipients.
+//!
+//! The keys are encrypted separately for each recipient, with a ChaCha20-Poly1305 key derived
+//! with HKDF-SHA256 from the Diffie-Hellman shared secret between a fresh ephemeral key and the
+//! recipient's public key. Granting or revoking access therefore only changes the recipient
+//! list, without re-encrypting any secrets.
+
+use crate::crypto::{Aead, AeadKey, CryptoProvider};
+use crate::errors::Error;
+use crate::shared::{KeyData, Recipient, NONCE_SIZE, TAG_SIZE};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::convert::TryInto;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+use zeroize::Zeroizing;
+
+const HKDF_INFO: &[u8] = b"securestore x25519 recipient v1";
+
+/// Encrypts `key_data` to the X25519 public key `public_key`.
+pub(crate) fn wrap<C: CryptoProvider>(
+    public_key: &[u8; 32],
+    key_data: &[u8],
+) -> Result<Recipient, Error> {
+    let mut ephemeral = Zeroizing::new([0u8; 32]);
+    C::random(&mut *ephemeral);
+    let ephemeral = StaticSecret::from(*ephemeral);
+    let ephemeral_key = PublicKey::from(&ephemeral);
+
+    let public_key = PublicKey::from(*public_key);
+    let shared = ephemeral.diffie_hellman(&public_key);
+    let wrapping_key = wrapping_key(shared, &ephemeral_key, &public_key)?;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    C::random(&mut nonce);
+    let mut tag = [0u8; TAG_SIZE];
+    let encrypted = C::aead_encrypt(
+        Aead::ChaCha20Poly1305,
+        &wrapping_key,
+        &nonce,
+        key_data,
+        &mut tag,
+    );
+
+    let mut ciphertext = nonce.to_vec();
+    ciphertext.extend_from_slice(&encrypted);
+    ciphertext.extend_from_slice(&tag);
+    Ok(Recipient {
+        public_key: public_key.as_bytes().to_vec(),
+        ephemeral_key: ephemeral_key.as_bytes().to_vec(),
+        ciphertext,
+    })
+}
+
+/// Decrypts the vault keys with the X25519 private key `secret`, which must correspond to one
+/// of `recipients`.
+pub(crate) fn unwrap<C: CryptoProvider>(
+    secret: &[u8; 32],
+    recipients: &[Recipient],
+) -> Result<KeyData, Error> {
+    let secret = StaticSecret::from(*secret);
+    let public_key = PublicKey::from(&secret);
+    let recipient = recipients
+        .iter()
+        .find(|r| r.public_key == public_key.as_bytes())
+        .ok_or(Error::NotARecipient)?;
+
+    let ephemeral_key: [u8; 32] = recipient.ephemeral_key[..]
+        .try_into()
+        .map_err(|_| Error::DecryptionFailure)?;
+    let ephemeral_key = PublicKey::from(ephemeral_key);
+    if recipient.ciphertext.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(Error::DecryptionFailure);
+    }
+    let (nonce, rest) = recipient.ciphertext.split_at(NONCE_SIZE);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+
+    let shared = secret.diffie_hellman(&ephemeral_key);
+    let wrapping_key = wrapping_key(shared, &ephemeral_key, &public_key)?;
+    C::aead_decrypt(
+        Aead::ChaCha20Poly1305,
+        &wrapping_key,
+        nonce.try_into().unwrap(),
+        ciphertext,
+        tag.try_into().unwrap(),
+    )
+    .map(Zeroizing::new)
+}
+
+/// Computes the X25519 public key corresponding to the private key `secret`.
+pub(crate) fn public_key(secret: &[u8; 32]) -> [u8; 32] {
+    PublicKey::from(&StaticSecret::from(*secret)).to_bytes()
+}
+
+/// Derives the key encrypting the vault keys for `recipient` from the Diffie-Hellman shared
+/// secret `shared`, binding both public keys.
+fn wrapping_key(
+    shared: SharedSecret,
+    ephemeral_key: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<Zeroizing<AeadKey>, Error> {
+    // Low-order public keys produce a shared secret known to everyone
+    if !shared.was_contributory() {
+        return Err(Error::InvalidRecipient);
+    }
+
+    let mut salt = ephemeral_key.as_bytes().to_vec();
+    salt.extend_from_slice(recipient.as_bytes());
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(HKDF_INFO, &mut *key)
+        .expect("HKDF output length is within bounds");
+    Ok(key)
+}
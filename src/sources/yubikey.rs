@@ -0,0 +1,48 @@
+//! HMAC-SHA1 challenge-response with a YubiKey, performed by shelling out to `ykchalresp` from
+//! yubikey-personalization.
+//!
+//! As in KeePassXC, the response to a challenge derived from the vault is combined with an
+//! optional password and fed through the vault's KDF, so that both the YubiKey and the password
+//! are required to unlock the vault.
+
+use crate::errors::Error;
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// Sends `challenge` to the HMAC-SHA1 challenge-response configuration in `slot` (1 or 2) of
+/// the first connected YubiKey, returning the hex-encoded response.
+pub(crate) fn challenge_response(slot: u8, challenge: &[u8]) -> Result<Zeroizing<String>, Error> {
+    if slot != 1 && slot != 2 {
+        return Err(Error::KeySourceFailure(format!(
+            "invalid YubiKey slot {}",
+            slot
+        )));
+    }
+
+    let challenge: String = challenge.iter().map(|b| format!("{:02x}", b)).collect();
+    let output = Command::new("ykchalresp")
+        .arg(format!("-{}", slot))
+        .arg("-x")
+        .arg(challenge)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(Error::Io)?;
+    let stdout = Zeroizing::new(output.stdout);
+
+    if !output.status.success() {
+        return Err(Error::KeySourceFailure(format!(
+            "ykchalresp failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let response = std::str::from_utf8(&stdout)
+        .map(str::trim)
+        .ok()
+        .filter(|r| r.len() == 40 && r.bytes().all(|b| b.is_ascii_hexdigit()))
+        .ok_or_else(|| {
+            Error::KeySourceFailure("ykchalresp returned a malformed response".to_string())
+        })?;
+
+    Ok(Zeroizing::new(response.to_ascii_lowercase()))
+}
@@ -0,0 +1,183 @@
+//! Pluggable persistence of serialized vaults.
+//!
+//! A [`SecretsManager`](crate::SecretsManager) only ever hands its backend the complete,
+//! serialized vault (the same bytes returned by
+//! [`SecretsManager::to_bytes()`](crate::SecretsManager::to_bytes)), so a backend never sees
+//! decrypted secrets and need not understand the vault format.
+
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(feature = "s3")]
+pub use self::s3::S3Storage;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStorage;
+
+use crate::errors::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A location a serialized vault is loaded from and saved to, such as a file, a database row or
+/// an object in an object store.
+///
+/// Implement this trait to persist vaults somewhere other than the local filesystem, then pass
+/// the backend to [`SecretsManagerBuilder::create_with_storage()`] or
+/// [`SecretsManagerBuilder::load_with_storage()`].
+///
+/// [`SecretsManagerBuilder::create_with_storage()`]: crate::SecretsManagerBuilder::create_with_storage
+/// [`SecretsManagerBuilder::load_with_storage()`]: crate::SecretsManagerBuilder::load_with_storage
+pub trait VaultStorage: Send + Sync {
+    /// Reads the serialized vault.
+    fn load(&self) -> Result<Vec<u8>, Error>;
+
+    /// Replaces the serialized vault with `contents`. Implementations should ensure a failed
+    /// store never leaves a partially-written vault behind.
+    fn store(&self, contents: &[u8]) -> Result<(), Error>;
+
+    /// Returns whether a vault has been stored.
+    fn exists(&self) -> Result<bool, Error>;
+
+    /// The path of the file the vault is stored in, for backends which store the vault in the
+    /// local filesystem. Used by the async API to read and write the file with `tokio::fs`.
+    fn file_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// The default [`VaultStorage`], a vault file on the local filesystem.
+#[derive(Clone, Debug)]
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    /// A vault stored in the file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileStorage {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The path of the vault file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Asynchronously and atomically replaces the vault file at `path` with `contents`, in the
+    /// same manner as [`VaultStorage::store()`].
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn store_async(path: &Path, contents: &[u8]) -> Result<(), Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let temp_path = Self::temp_path(path);
+
+        let result = async {
+            let mut file = tokio::fs::File::create(&temp_path)
+                .await
+                .map_err(Error::file(&temp_path))?;
+            file.write_all(contents)
+                .await
+                .map_err(Error::file(&temp_path))?;
+            file.sync_all().await.map_err(Error::file(&temp_path))?;
+            tokio::fs::rename(&temp_path, path)
+                .await
+                .map_err(Error::file(path))?;
+            if cfg!(unix) {
+                let parent = match path.parent() {
+                    Some(p) if !p.as_os_str().is_empty() => p,
+                    _ => Path::new("."),
+                };
+                let dir = tokio::fs::File::open(parent)
+                    .await
+                    .map_err(Error::file(parent))?;
+                dir.sync_all().await.map_err(Error::file(parent))?;
+            }
+            Ok(())
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+        result
+    }
+
+    fn write_to(contents: &[u8], path: &Path) -> Result<(), Error> {
+        let mut file = File::create(path).map_err(Error::file(path))?;
+        file.write_all(contents).map_err(Error::file(path))?;
+        file.sync_all().map_err(Error::file(path))
+    }
+
+    /// Picks a unique, hidden path alongside `path` to stage a save in. The temporary file must
+    /// live in the same directory (and so on the same filesystem) for the rename to be atomic.
+    fn temp_path(path: &Path) -> PathBuf {
+        // Uniqueness (not unpredictability) is all that's required here
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        let temp_name = format!(".{}.{}.{}.tmp", name, std::process::id(), counter);
+        path.with_file_name(temp_name)
+    }
+
+    /// Flushes the directory entry created by the rename so that it survives a crash.
+    #[cfg(unix)]
+    fn sync_parent(path: &Path) -> Result<(), Error> {
+        let parent = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+
+        File::open(parent)
+            .and_then(|dir| dir.sync_all())
+            .map_err(Error::file(parent))
+    }
+
+    #[cfg(not(unix))]
+    fn sync_parent(_path: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl VaultStorage for FileStorage {
+    fn load(&self) -> Result<Vec<u8>, Error> {
+        std::fs::read(&self.path).map_err(Error::file(&self.path))
+    }
+
+    /// The vault is first written and flushed to a temporary file in the same directory, which
+    /// then atomically replaces the vault file, so an interrupted save can never leave a
+    /// partially-written vault in place of a good one.
+    fn store(&self, contents: &[u8]) -> Result<(), Error> {
+        let path = &self.path;
+        let temp_path = Self::temp_path(path);
+
+        let result = Self::write_to(contents, &temp_path).and_then(|_| {
+            std::fs::rename(&temp_path, path).map_err(Error::file(path))?;
+            Self::sync_parent(path)
+        });
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    fn exists(&self) -> Result<bool, Error> {
+        match std::fs::metadata(&self.path) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(Error::file(&self.path)(e)),
+        }
+    }
+
+    fn file_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
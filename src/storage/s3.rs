@@ -0,0 +1,206 @@
+//! Storage of vaults as objects in an S3-compatible object store.
+//!
+//! Requests are signed with AWS Signature Version 4 using the same credentials as the AWS KMS
+//! key source (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and optionally `AWS_SESSION_TOKEN`).
+//! Lost updates are prevented with conditional writes: the ETag of the object is remembered
+//! when the vault is loaded or stored, and the next store only succeeds if the object still has
+//! that ETag (or, for a new vault, if the object does not exist yet).
+
+use super::VaultStorage;
+use crate::errors::Error;
+use crate::sources::aws::{self, hex, sign, timestamp, Credentials};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// A [`VaultStorage`] backend which keeps the vault in the object `key` of an S3 (or
+/// S3-compatible) bucket.
+///
+/// The region is taken from `AWS_REGION` or `AWS_DEFAULT_REGION` unless set with
+/// [`S3Storage::region()`]. Requests are sent to AWS using virtual-hosted style URLs, or to the
+/// endpoint set with [`S3Storage::endpoint()`] (or by `AWS_ENDPOINT_URL_S3` or
+/// `AWS_ENDPOINT_URL`) using path-style URLs, as expected by most S3-compatible stores.
+///
+/// Saving fails with [`Error::VaultConflict`] if the object was changed by anyone else since it
+/// was loaded, or if a vault newly created with this storage would replace an existing object.
+#[derive(Debug)]
+pub struct S3Storage {
+    bucket: String,
+    key: String,
+    region: Option<String>,
+    endpoint: Option<String>,
+    /// The ETag of the object as last loaded or stored, or `None` if it has not been seen
+    etag: Mutex<Option<String>>,
+}
+
+impl S3Storage {
+    /// A vault stored in the object `key` of the bucket `bucket`.
+    pub fn new(bucket: &str, key: &str) -> Self {
+        S3Storage {
+            bucket: bucket.to_string(),
+            key: key.trim_start_matches('/').to_string(),
+            region: None,
+            endpoint: None,
+            etag: Mutex::new(None),
+        }
+    }
+
+    /// Sets the region of the bucket, instead of reading it from the environment.
+    pub fn region(mut self, region: &str) -> Self {
+        self.region = Some(region.to_string());
+        self
+    }
+
+    /// Sets the base URL of an S3-compatible object store, e.g. `http://localhost:9000`.
+    pub fn endpoint(mut self, url: &str) -> Self {
+        self.endpoint = Some(url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Sends a signed request for the object, returning the status code and response.
+    fn request(
+        &self,
+        method: &str,
+        headers: Vec<(&str, String)>,
+        body: &[u8],
+    ) -> Result<(u16, ureq::Response), Error> {
+        let credentials = Credentials::from_env().map_err(storage_failure)?;
+        let region = match &self.region {
+            Some(region) => region.clone(),
+            None => aws::region_from_env().map_err(storage_failure)?,
+        };
+        let endpoint = self.endpoint.clone().or_else(|| {
+            env::var("AWS_ENDPOINT_URL_S3")
+                .or_else(|_| env::var("AWS_ENDPOINT_URL"))
+                .ok()
+                .map(|url| url.trim_end_matches('/').to_string())
+        });
+
+        let key = uri_encode(&self.key);
+        let (base, path) = match endpoint {
+            Some(endpoint) => (endpoint, format!("/{}/{}", uri_encode(&self.bucket), key)),
+            None => (
+                format!("https://{}.s3.{}.amazonaws.com", self.bucket, region),
+                format!("/{}", key),
+            ),
+        };
+        let host = base.split("://").nth(1).unwrap_or(&base).to_string();
+
+        let timestamp = timestamp(SystemTime::now());
+        let mut headers = headers;
+        headers.push(("host", host));
+        headers.push(("x-amz-content-sha256", hex(&Sha256::digest(body))));
+        headers.push(("x-amz-date", timestamp.clone()));
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let authorization = sign(
+            &credentials,
+            &region,
+            "s3",
+            (method, &path, ""),
+            &headers,
+            body,
+            &timestamp,
+        );
+        headers.push(("authorization", authorization));
+
+        let mut request = ureq::request(method, &format!("{}{}", base, path));
+        // The HTTP client supplies the host header itself
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.set(name, value);
+        }
+
+        match request.send_bytes(body) {
+            Ok(response) => Ok((response.status(), response)),
+            Err(ureq::Error::Status(code, response)) => Ok((code, response)),
+            Err(e) => Err(Error::StorageFailure(format!("S3 request failed: {}", e))),
+        }
+    }
+
+    fn location(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.key)
+    }
+
+    fn remember_etag(&self, response: &ureq::Response) {
+        *self.etag.lock().unwrap() = response.header("etag").map(str::to_string);
+    }
+}
+
+impl VaultStorage for S3Storage {
+    fn load(&self) -> Result<Vec<u8>, Error> {
+        let (status, response) = self.request("GET", Vec::new(), b"")?;
+        if status != 200 {
+            return Err(failure(&self.location(), status, response));
+        }
+
+        self.remember_etag(&response);
+        let mut contents = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut contents)
+            .map_err(Error::Io)?;
+        Ok(contents)
+    }
+
+    fn store(&self, contents: &[u8]) -> Result<(), Error> {
+        let condition = match &*self.etag.lock().unwrap() {
+            Some(etag) => ("if-match", etag.clone()),
+            None => ("if-none-match", "*".to_string()),
+        };
+        let headers = vec![("content-type", "application/json".to_string()), condition];
+
+        let (status, response) = self.request("PUT", headers, contents)?;
+        match status {
+            200 => {
+                self.remember_etag(&response);
+                Ok(())
+            }
+            // 409 is returned when a concurrent conditional write to the same object wins
+            409 | 412 => Err(Error::VaultConflict),
+            _ => Err(failure(&self.location(), status, response)),
+        }
+    }
+
+    fn exists(&self) -> Result<bool, Error> {
+        let (status, response) = self.request("HEAD", Vec::new(), b"")?;
+        match status {
+            200 => Ok(true),
+            404 => Ok(false),
+            _ => Err(failure(&self.location(), status, response)),
+        }
+    }
+}
+
+/// Percent-encodes `value` as required for the canonical URI of a signed S3 request, leaving
+/// path separators intact.
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn failure(location: &str, status: u16, response: ureq::Response) -> Error {
+    let message = response.into_string().unwrap_or_default();
+    Error::StorageFailure(format!(
+        "S3 returned HTTP {} for {}: {}",
+        status,
+        location,
+        message.trim()
+    ))
+}
+
+fn storage_failure(error: Error) -> Error {
+    match error {
+        Error::KeySourceFailure(message) => Error::StorageFailure(message),
+        error => error,
+    }
+}
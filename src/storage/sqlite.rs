@@ -0,0 +1,207 @@
+//! Storage of vaults in a SQLite database, with a row per secret.
+//!
+//! Each secret is stored as its own row holding the same IV, HMAC (or tag) and ciphertext that
+//! would be written to a vault file, while the remaining fields of the vault are stored as JSON
+//! in a single row of a separate table. Saving only writes the rows of secrets that were added,
+//! changed or removed, which keeps saves cheap for vaults with many thousands of secrets.
+
+use super::VaultStorage;
+use crate::compression::Compression;
+use crate::errors::Error;
+use crate::format::VaultFormat;
+use crate::shared::EncryptedBlob;
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS securestore_vault (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        header TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS securestore_secrets (
+        name TEXT PRIMARY KEY,
+        iv BLOB NOT NULL,
+        hmac BLOB NOT NULL,
+        payload BLOB NOT NULL,
+        compression TEXT,
+        chunks TEXT
+    );";
+
+/// Columns of `securestore_secrets` added after it was first created, which are added to the
+/// tables of existing databases when they are opened.
+const ADDED_COLUMNS: &[&str] = &["compression", "chunks"];
+
+/// The serialized vault, split into the secrets and all other fields.
+#[derive(Serialize, Deserialize)]
+struct StoredVault {
+    #[serde(flatten)]
+    header: serde_json::Map<String, serde_json::Value>,
+    secrets: BTreeMap<String, EncryptedBlob>,
+}
+
+/// A [`VaultStorage`] backend which keeps the vault in the tables `securestore_vault` and
+/// `securestore_secrets` of a SQLite database, which are created if they do not exist.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Opens (or creates) the SQLite database at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(Connection::open(path).map_err(failure)?)
+    }
+
+    /// Stores the vault in the database open in `connection`, e.g. one shared with the rest of
+    /// an application.
+    pub fn from_connection(connection: Connection) -> Result<Self, Error> {
+        connection.execute_batch(SCHEMA).map_err(failure)?;
+        for column in ADDED_COLUMNS {
+            let select = format!("SELECT {} FROM securestore_secrets", column);
+            if connection.prepare(&select).is_err() {
+                let alter = format!("ALTER TABLE securestore_secrets ADD COLUMN {} TEXT", column);
+                connection.execute_batch(&alter).map_err(failure)?;
+            }
+        }
+        Ok(SqliteStorage {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl VaultStorage for SqliteStorage {
+    fn load(&self) -> Result<Vec<u8>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let header: String = connection
+            .query_row("SELECT header FROM securestore_vault", [], |row| row.get(0))
+            .optional()
+            .map_err(failure)?
+            .ok_or_else(|| Error::StorageFailure("the database holds no vault".to_string()))?;
+
+        let mut statement = connection
+            .prepare("SELECT name, iv, hmac, payload, compression, chunks FROM securestore_secrets")
+            .map_err(failure)?;
+        let secrets = statement
+            .query_map([], |row| {
+                let blob = EncryptedBlob {
+                    iv: row.get(1)?,
+                    hmac: row.get(2)?,
+                    payload: row.get(3)?,
+                    compression: match row.get::<_, Option<String>>(4)? {
+                        None => None,
+                        Some(name) => Some(serde_json::from_value(name.into()).map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(4, Type::Text, Box::new(e))
+                        })?),
+                    },
+                    // The chunks of a secret are stored as a JSON array of encrypted blobs
+                    chunks: match row.get::<_, Option<String>>(5)? {
+                        None => Vec::new(),
+                        Some(chunks) => serde_json::from_str(&chunks).map_err(|e| {
+                            rusqlite::Error::FromSqlConversionFailure(5, Type::Text, Box::new(e))
+                        })?,
+                    },
+                };
+                Ok((row.get(0)?, blob))
+            })
+            .and_then(Iterator::collect)
+            .map_err(failure)?;
+
+        let vault = StoredVault {
+            header: serde_json::from_str(&header).map_err(Error::Serde)?,
+            secrets,
+        };
+        serde_json::to_vec(&vault).map_err(Error::Serde)
+    }
+
+    /// Secrets are re-encrypted under a fresh IV whenever they are changed, so a row is only
+    /// rewritten if the IV of the secret differs from the IV stored in the database.
+    fn store(&self, contents: &[u8]) -> Result<(), Error> {
+        // The vault is stored as JSON, whatever format it is serialized in. CBOR vaults hold
+        // binary data which cannot be represented in JSON, so are not supported.
+        let format = VaultFormat::detect(contents);
+        #[cfg(feature = "cbor")]
+        if format == VaultFormat::Cbor {
+            return Err(Error::StorageFailure(
+                "vaults in the CBOR format cannot be stored in SQLite".to_string(),
+            ));
+        }
+        let vault: StoredVault = format.deserialize(contents)?;
+        let header = serde_json::to_string(&vault.header).map_err(Error::Serde)?;
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(failure)?;
+        let stored: HashMap<String, Vec<u8>> = transaction
+            .prepare("SELECT name, iv FROM securestore_secrets")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .and_then(Iterator::collect)
+            })
+            .map_err(failure)?;
+
+        for name in stored.keys().filter(|n| !vault.secrets.contains_key(*n)) {
+            transaction
+                .execute("DELETE FROM securestore_secrets WHERE name = ?1", [name])
+                .map_err(failure)?;
+        }
+        for (name, blob) in &vault.secrets {
+            if stored.get(name) == Some(&blob.iv) {
+                continue;
+            }
+            let chunks = match blob.chunks.is_empty() {
+                true => None,
+                false => Some(serde_json::to_string(&blob.chunks).map_err(Error::Serde)?),
+            };
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO securestore_secrets
+                     (name, iv, hmac, payload, compression, chunks)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        name,
+                        blob.iv,
+                        blob.hmac,
+                        blob.payload,
+                        compression_name(blob.compression),
+                        chunks
+                    ],
+                )
+                .map_err(failure)?;
+        }
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO securestore_vault (id, header) VALUES (1, ?1)",
+                [header],
+            )
+            .map_err(failure)?;
+
+        transaction.commit().map_err(failure)
+    }
+
+    fn exists(&self) -> Result<bool, Error> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM securestore_vault)",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(failure)
+    }
+}
+
+/// The name a compression algorithm is serialized with, e.g. `zstd`.
+fn compression_name(compression: Option<Compression>) -> Option<String> {
+    match serde_json::to_value(compression?) {
+        Ok(serde_json::Value::String(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn failure(error: rusqlite::Error) -> Error {
+    Error::StorageFailure(format!("SQLite: {}", error))
+}
@@ -0,0 +1,169 @@
+//! Chunked encryption of secrets read from and written to streams.
+//!
+//! Secrets set from a stream are split into chunks of [`CHUNK_SIZE`] bytes, each encrypted
+//! under its own IV exactly as any other secret would be, so that no more than one chunk of the
+//! plaintext is held in memory at once while it is encrypted or decrypted. The secret records the total length of the plaintext,
+//! encrypted in its own payload, which is checked when the secret is decrypted so that dropped
+//! chunks are detected. The order of the chunks is protected (along with everything else in the
+//! vault) by the vault MAC.
+//!
+//! The encrypted chunks are stored in the vault like any other secret, so the ciphertext of
+//! every secret is held in memory while the vault is loaded, and is written out in full each
+//! time it is saved. Chunked secrets are therefore no more suited than any other to artifacts
+//! too large to hold in memory, such as disk images; these are better encrypted separately,
+//! with only their key kept in the vault.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::shared::{EncryptedBlob, Keys, Scheme};
+use crate::SecretsManager;
+use std::io::{ErrorKind, Read, Write};
+use zeroize::Zeroizing;
+
+/// The number of bytes of plaintext encrypted in each chunk.
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Adds a new secret or replaces an existing secret identified by `name`, encrypting the
+    /// contents of `reader` in chunks, so that the plaintext need not be held in memory in full.
+    /// The encrypted chunks are kept in the vault, so the secret must still fit in memory once
+    /// encrypted. Secrets set this way are retrieved as any other secret, or streamed back out
+    /// with [`retrieve_to_writer()`](Self::retrieve_to_writer).
+    ///
+    /// The store is left unchanged if reading from `reader` fails.
+    pub fn set_from_reader<R: Read>(&mut self, name: &str, mut reader: R) -> Result<(), Error> {
+        let scheme = self.vault.scheme();
+        let mut buffer = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+        let mut chunks = Vec::new();
+        let mut length = 0u64;
+        loop {
+            let read = read_chunk(&mut reader, &mut buffer).map_err(Error::Io)?;
+            if read == 0 {
+                break;
+            }
+            chunks.push(EncryptedBlob::encrypt::<C>(
+                &self.keys,
+                scheme,
+                &buffer[..read],
+            ));
+            length += read as u64;
+            if read < CHUNK_SIZE {
+                break;
+            }
+        }
+
+        let blob = EncryptedBlob::encrypt_chunks::<C>(&self.keys, scheme, chunks, length);
+        self.insert(name, blob);
+        Ok(())
+    }
+
+    /// Decrypts the secret identified by `name` and writes it to `writer` one chunk at a time.
+    /// If the secret cannot be found, returns [`Error::SecretNotFound`].
+    ///
+    /// Chunks are written as they are decrypted, so if a chunk fails to decrypt, the chunks
+    /// preceding it will already have been written.
+    pub fn retrieve_to_writer<W: Write>(&self, name: &str, mut writer: W) -> Result<(), Error> {
+        let blob = self
+            .vault
+            .secrets
+            .get(name)
+            .ok_or_else(|| Error::SecretNotFound(name.to_string()))?;
+        self.check_expiry(name)?;
+        blob.decrypt_to::<C, _>(&self.keys, self.vault.scheme(), &mut writer)?;
+        self.emit(Event::SecretRead { name });
+        writer.flush().map_err(Error::Io)
+    }
+}
+
+impl EncryptedBlob {
+    /// Encrypts `secret`, which was held in chunks of up to [`CHUNK_SIZE`] bytes, in chunks
+    /// again, e.g. to re-encrypt it with new keys.
+    pub(crate) fn encrypt_chunked<C: CryptoProvider>(
+        keys: &Keys,
+        scheme: Scheme,
+        secret: &[u8],
+    ) -> EncryptedBlob {
+        let chunks = secret
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| EncryptedBlob::encrypt::<C>(keys, scheme, chunk))
+            .collect();
+        Self::encrypt_chunks::<C>(keys, scheme, chunks, secret.len() as u64)
+    }
+
+    /// Combines the encrypted `chunks` of a secret, recording the total `length` of their
+    /// plaintext.
+    fn encrypt_chunks<C: CryptoProvider>(
+        keys: &Keys,
+        scheme: Scheme,
+        chunks: Vec<EncryptedBlob>,
+        length: u64,
+    ) -> EncryptedBlob {
+        // An empty secret has no chunks, so is stored as any other empty secret
+        if chunks.is_empty() {
+            return EncryptedBlob::encrypt::<C>(keys, scheme, &[]);
+        }
+
+        EncryptedBlob {
+            chunks,
+            ..EncryptedBlob::encrypt::<C>(keys, scheme, &length.to_be_bytes())
+        }
+    }
+
+    /// Decrypts the total length of a secret encrypted in chunks.
+    pub(crate) fn chunked_length<C: CryptoProvider>(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+    ) -> Result<u64, Error> {
+        let header = Zeroizing::new(self.decrypt_single::<C>(keys, scheme)?);
+        let mut length = [0u8; 8];
+        if header.len() != length.len() {
+            return Err(Error::DecryptionFailure);
+        }
+        length.copy_from_slice(&header);
+        Ok(u64::from_be_bytes(length))
+    }
+
+    /// Decrypts the secret, one chunk at a time for secrets encrypted in chunks, writing the
+    /// plaintext to `writer`.
+    pub(crate) fn decrypt_to<C: CryptoProvider, W: Write>(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        if self.chunks.is_empty() {
+            let plaintext = Zeroizing::new(self.decrypt_single::<C>(keys, scheme)?);
+            return writer.write_all(&plaintext).map_err(Error::Io);
+        }
+
+        let length = self.chunked_length::<C>(keys, scheme)?;
+        let mut written = 0u64;
+        for chunk in &self.chunks {
+            let plaintext = Zeroizing::new(chunk.decrypt_single::<C>(keys, scheme)?);
+            writer.write_all(&plaintext).map_err(Error::Io)?;
+            written += plaintext.len() as u64;
+        }
+        match written == length {
+            true => Ok(()),
+            // Chunks were removed (or added) after the secret was encrypted
+            false => Err(Error::DecryptionFailure),
+        }
+    }
+}
+
+/// Reads from `reader` until `buffer` is full or the end of the stream is reached, returning
+/// the number of bytes read.
+fn read_chunk<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
@@ -0,0 +1,48 @@
+//! Tagging of secrets, so that related secrets can be found and operated on together.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::SecretsManager;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Replaces the tags of the existing secret `name` with `tags`, e.g. `&["prod", "aws"]`,
+    /// or removes all its tags if `tags` is empty. Tags are stored unencrypted in the vault and
+    /// covered by its MAC, and are kept when the value of the secret is changed. If the secret
+    /// cannot be found, returns [`Error::SecretNotFound`].
+    pub fn set_tags<S: AsRef<str>>(&mut self, name: &str, tags: &[S]) -> Result<(), Error> {
+        if !self.vault.secrets.contains_key(name) {
+            return Err(Error::SecretNotFound(name.to_string()));
+        }
+        match tags.is_empty() {
+            true => self.vault.tags.remove(name),
+            false => {
+                let tags = tags.iter().map(|t| t.as_ref().to_string()).collect();
+                self.vault.tags.insert(name.to_string(), tags)
+            }
+        };
+        self.vault.mark_dirty();
+        Ok(())
+    }
+
+    /// Returns an iterator over the tags of the secret `name`, in sorted order.
+    pub fn tags(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.vault
+            .tags
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Returns an iterator over the names of all secrets tagged with `tag`, in sorted order.
+    pub fn keys_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a str> {
+        self.vault
+            .tags
+            .iter()
+            // The tags of soft-removed secrets are kept in case they are restored
+            .filter(move |(name, tags)| {
+                tags.contains(tag) && self.vault.secrets.contains_key(*name)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+}
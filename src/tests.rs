@@ -1,3 +1,16 @@
+#[cfg(feature = "tokio")]
+mod async_io;
 mod encrypted_blob;
+mod errors;
+#[cfg(feature = "ffi")]
+mod ffi;
 mod key_management;
+mod legacy;
+mod providers;
+#[cfg(feature = "python")]
+mod python;
 mod secrets;
+mod sources;
+mod storage;
+#[cfg(feature = "notify")]
+mod watch;
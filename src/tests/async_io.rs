@@ -0,0 +1,59 @@
+//! Tests of the async API
+
+use crate::errors::Error;
+use crate::{KeySource, SecretsManager};
+
+/// Verify that vaults created, saved and loaded asynchronously are interchangeable with those
+/// created, saved and loaded with the blocking API.
+#[tokio::test(flavor = "multi_thread")]
+async fn async_create_save_load() {
+    let path = "./async.json";
+
+    let mut sman = SecretsManager::new_async(path, KeySource::Password("mysecret"))
+        .await
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.save_async().await.unwrap();
+
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let mut sman = SecretsManager::load_async(path, KeySource::Password("mysecret"))
+        .await
+        .unwrap();
+    sman.set("foo", "baz");
+    sman.save_async().await.unwrap();
+
+    match SecretsManager::load_async(path, KeySource::Password("wrong")).await {
+        Err(Error::InvalidKey) => {}
+        _ => panic!("Loading a vault asynchronously with the wrong password did not fail!"),
+    }
+
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("baz", sman.retrieve::<String>("foo").unwrap());
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that saving asynchronously from a spawned task works, and that the async API falls
+/// back to blocking calls on a single-threaded runtime.
+#[tokio::test(flavor = "current_thread")]
+async fn async_save_spawned() {
+    let path = "./async_spawned.json";
+
+    let mut sman = SecretsManager::new_async(path, KeySource::Password("mysecret"))
+        .await
+        .unwrap();
+    sman.set("foo", "bar");
+    let sman = tokio::spawn(async move {
+        sman.save_async().await.unwrap();
+        sman
+    })
+    .await
+    .unwrap();
+
+    let sman2 = SecretsManager::load_async(path, KeySource::Password("mysecret"))
+        .await
+        .unwrap();
+    assert_eq!(sman.to_bytes().unwrap(), sman2.to_bytes().unwrap());
+    std::fs::remove_file(path).unwrap();
+}
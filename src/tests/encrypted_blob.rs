@@ -1,5 +1,5 @@
+use crate::crypto::{CryptoProvider, DefaultProvider};
 use crate::shared::*;
-use openssl::rand;
 
 #[cfg(test)]
 impl Default for Keys {
@@ -7,8 +7,8 @@ impl Default for Keys {
         let mut encryption_key = [0u8; KEY_LENGTH];
         let mut hmac_key = [0u8; KEY_LENGTH];
 
-        rand::rand_bytes(&mut encryption_key).unwrap();
-        rand::rand_bytes(&mut hmac_key).unwrap();
+        DefaultProvider::random(&mut encryption_key);
+        DefaultProvider::random(&mut hmac_key);
 
         Keys {
             encryption: encryption_key,
@@ -22,9 +22,11 @@ impl Default for Keys {
 fn basic_encryption_decryption() {
     let keys: Keys = Default::default();
 
-    let foo = EncryptedBlob::encrypt(&keys, b"foo");
+    let foo = EncryptedBlob::encrypt::<DefaultProvider>(&keys, Scheme::default(), b"foo");
     assert_eq!(
-        foo.decrypt(&keys).unwrap().as_slice(),
+        foo.decrypt::<DefaultProvider>(&keys, Scheme::default())
+            .unwrap()
+            .as_slice(),
         b"foo",
         "failed to retrieve same value stored!"
     );
@@ -35,8 +37,8 @@ fn basic_encryption_decryption() {
 fn iv_uniqueness() {
     let keys: Keys = Default::default();
 
-    let foo1 = EncryptedBlob::encrypt(&keys, b"foo");
-    let foo2 = EncryptedBlob::encrypt(&keys, b"foo");
+    let foo1 = EncryptedBlob::encrypt::<DefaultProvider>(&keys, Scheme::default(), b"foo");
+    let foo2 = EncryptedBlob::encrypt::<DefaultProvider>(&keys, Scheme::default(), b"foo");
 
     assert_ne!(
         foo1.payload, foo2.payload,
@@ -53,15 +55,64 @@ fn iv_uniqueness() {
 fn blob_authentication() {
     let keys: Keys = Default::default();
 
-    let mut foo = EncryptedBlob::encrypt(&keys, b"foo");
+    let mut foo = EncryptedBlob::encrypt::<DefaultProvider>(&keys, Scheme::default(), b"foo");
     assert!(
-        foo.authenticate(&keys.hmac),
+        foo.authenticate::<DefaultProvider>(&keys.hmac, MacAlgorithm::HmacSha1),
         "Authentication for known-good blob failed"
     );
 
     foo.payload[0] = !foo.payload[0];
     assert!(
-        !foo.authenticate(&keys.hmac),
+        !foo.authenticate::<DefaultProvider>(&keys.hmac, MacAlgorithm::HmacSha1),
         "Authentication for known-bad blob succeeded"
     );
 }
+
+/// Verify that every scheme round-trips and rejects tampered ciphertext
+#[test]
+fn scheme_encryption_decryption() {
+    let keys: Keys = Default::default();
+
+    for &scheme in &[
+        Scheme::Aes128CbcHmacSha1,
+        Scheme::Aes128CbcHmacSha256,
+        Scheme::Aes256Gcm,
+        Scheme::ChaCha20Poly1305,
+    ] {
+        let mut foo = EncryptedBlob::encrypt::<DefaultProvider>(&keys, scheme, b"foo");
+        assert_eq!(
+            foo.decrypt::<DefaultProvider>(&keys, scheme)
+                .unwrap()
+                .as_slice(),
+            b"foo",
+            "{:?} failed to retrieve same value stored!",
+            scheme
+        );
+
+        foo.payload[0] = !foo.payload[0];
+        assert!(
+            foo.decrypt::<DefaultProvider>(&keys, scheme).is_err(),
+            "{:?} decrypted tampered ciphertext!",
+            scheme
+        );
+    }
+}
+
+/// Verify that a blob from one scheme is rejected rather than misinterpreted by another
+#[test]
+fn scheme_mismatch() {
+    let keys: Keys = Default::default();
+
+    let cbc = EncryptedBlob::encrypt::<DefaultProvider>(&keys, Scheme::Aes128CbcHmacSha1, b"foo");
+    assert!(cbc
+        .decrypt::<DefaultProvider>(&keys, Scheme::Aes256Gcm)
+        .is_err());
+
+    let gcm = EncryptedBlob::encrypt::<DefaultProvider>(&keys, Scheme::Aes256Gcm, b"foo");
+    assert!(gcm
+        .decrypt::<DefaultProvider>(&keys, Scheme::Aes128CbcHmacSha1)
+        .is_err());
+    assert!(gcm
+        .decrypt::<DefaultProvider>(&keys, Scheme::ChaCha20Poly1305)
+        .is_err());
+}
@@ -0,0 +1,82 @@
+//! Tests verifying that errors identify what went wrong, and where.
+
+use crate::shared::*;
+use crate::*;
+use std::error::Error as _;
+
+/// Verify that syntax errors in a vault report their position
+#[test]
+fn malformed_vault_position() {
+    let malformed = "{\n  \"version\": 2,\n  \"iv\": null,\n  \"secrets\": {,}\n}";
+
+    match Vault::load(malformed.as_bytes()) {
+        Err(e @ Error::MalformedVault { line: 4, .. }) => {
+            assert!(e.source().is_some(), "MalformedVault has no source!");
+            assert!(e.to_string().contains("line 4"));
+        }
+        other => panic!("Expected MalformedVault at line 4, got {:?}", other),
+    }
+}
+
+/// Verify that vaults encrypted with an unknown scheme are reported as such
+#[test]
+fn unsupported_scheme() {
+    let vault = r#"{ "version": 2, "iv": null, "scheme": "rot13", "secrets": {} }"#;
+
+    match Vault::load(vault.as_bytes()) {
+        Err(Error::UnsupportedSchemeVersion(scheme)) => assert_eq!("rot13", scheme),
+        other => panic!("Expected UnsupportedSchemeVersion, got {:?}", other),
+    }
+}
+
+/// Verify that I/O errors carry the path of the offending file
+#[test]
+fn file_error_path() {
+    let path = Path::new("./does-not-exist.json");
+
+    match SecretsManager::load(path, KeySource::Generate) {
+        Err(e @ Error::File { .. }) => {
+            if let Error::File { path: p, source } = &e {
+                assert_eq!(path, p);
+                assert_eq!(std::io::ErrorKind::NotFound, source.kind());
+            }
+            assert!(e.to_string().contains("does-not-exist.json"));
+            assert!(e.source().is_some(), "File error has no source!");
+        }
+        other => panic!("Expected a File error, got {:?}", other.err()),
+    }
+
+    // as do failures to create the lock file alongside the vault
+    let path = Path::new("./does-not-exist/vault.json");
+    match SecretsManager::load_locked(path, KeySource::Generate) {
+        Err(Error::File { path: p, .. }) => {
+            assert_eq!(Path::new("./does-not-exist/.vault.json.lock"), p)
+        }
+        other => panic!("Expected a File error, got {:?}", other.err()),
+    }
+}
+
+/// Verify that short keyfiles report how short they are
+#[test]
+fn short_keyfile() {
+    let path = "./short.json";
+    let keyfile = "./short.key";
+    SecretsManager::new(path, KeySource::Generate)
+        .unwrap()
+        .save()
+        .unwrap();
+    std::fs::write(keyfile, [0u8; 20]).unwrap();
+
+    match SecretsManager::load(path, KeySource::File(Path::new(keyfile))) {
+        Err(e @ Error::KeyfileTooShort { .. }) => {
+            assert_eq!(
+                "keyfile too short: expected 32 bytes, found 20",
+                e.to_string()
+            )
+        }
+        other => panic!("Expected KeyfileTooShort, got {:?}", other.err()),
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(keyfile).unwrap();
+}
@@ -0,0 +1,56 @@
+//! Tests of the C interface
+
+use crate::ffi::*;
+use crate::{KeySource, SecretsManager};
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+/// Verify that secrets can be read, written and saved through the C interface, and that
+/// failures are reported through `securestore_last_error()`.
+#[test]
+fn ffi_round_trip() {
+    let path = "./ffi.json";
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let c_path = CString::new(path).unwrap();
+    let password = CString::new("mysecret").unwrap();
+    let foo = CString::new("foo").unwrap();
+    let baz = CString::new("baz").unwrap();
+
+    unsafe {
+        let handle = securestore_open(c_path.as_ptr(), password.as_ptr(), ptr::null());
+        assert!(!handle.is_null());
+
+        let mut value = ptr::null_mut();
+        let mut length = 0;
+        assert_eq!(
+            0,
+            securestore_get(handle, foo.as_ptr(), &mut value, &mut length)
+        );
+        assert_eq!(b"bar", std::slice::from_raw_parts(value, length));
+        securestore_buffer_free(value, length);
+
+        assert_eq!(0, securestore_set(handle, baz.as_ptr(), b"qux".as_ptr(), 3));
+        assert_eq!(0, securestore_save(handle));
+        securestore_free(handle);
+
+        assert_eq!(
+            -1,
+            securestore_get(ptr::null(), foo.as_ptr(), &mut value, &mut length)
+        );
+        let error = CStr::from_ptr(securestore_last_error());
+        assert_eq!("handle is NULL", error.to_str().unwrap());
+
+        let wrong = CString::new("wrong").unwrap();
+        let handle = securestore_open(c_path.as_ptr(), wrong.as_ptr(), ptr::null());
+        assert!(handle.is_null());
+        assert!(!securestore_last_error().is_null());
+    }
+
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("qux", sman.retrieve::<String>("baz").unwrap());
+
+    std::fs::remove_file(path).unwrap();
+}
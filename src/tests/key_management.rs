@@ -1,27 +1,639 @@
 use crate::shared::*;
 use crate::*;
-use openssl::rand;
 
 /// Verify that exporting keys derived from a password results in keys dependent on the IV
 #[test]
 fn key_derivation_iv() {
-    let mut iv1 = [0u8; IV_SIZE];
-    rand::rand_bytes(&mut iv1).unwrap();
+    let iv1 = Vault::generate_iv::<DefaultProvider>();
+    let iv2 = Vault::generate_iv::<DefaultProvider>();
 
-    let mut iv2 = [0u8; IV_SIZE];
-    rand::rand_bytes(&mut iv2).unwrap();
-
-    let derived1 = KeySource::Password("foo").extract_keys(&Some(iv1)).unwrap();
-    let derived2 = KeySource::Password("foo").extract_keys(&Some(iv1)).unwrap();
+    let derived1 = KeySource::Password("foo")
+        .extract_keys::<DefaultProvider>(&Some(iv1), None)
+        .unwrap();
+    let derived2 = KeySource::Password("foo")
+        .extract_keys::<DefaultProvider>(&Some(iv1), None)
+        .unwrap();
 
     assert_eq!(
         derived1, derived2,
         "Two keys derived from same password and same IV differ"
     );
 
-    let derived3 = KeySource::Password("foo").extract_keys(&Some(iv2)).unwrap();
+    let derived3 = KeySource::Password("foo")
+        .extract_keys::<DefaultProvider>(&Some(iv2), None)
+        .unwrap();
     assert_ne!(
         derived1, derived3,
         "Two keys derived from the same password but different IVs don't differ"
     );
 }
+
+/// Verify that rotating keys re-encrypts existing secrets and invalidates the old keys
+#[test]
+fn key_rotation() {
+    let path = "./rotation.json";
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("old")).unwrap();
+    sman.set("foo", "bar");
+    let old_iv = sman.vault.iv;
+
+    sman.rotate_keys(KeySource::Password("new")).unwrap();
+    assert_ne!(old_iv, sman.vault.iv, "Vault IV was not regenerated!");
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    sman.save().unwrap();
+
+    let sman = SecretsManager::load(path, KeySource::Password("new")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    match SecretsManager::load(path, KeySource::Password("old")) {
+        Err(Error::InvalidKey) => {}
+        _ => panic!("Vault still loads with the rotated-out password!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that changing the password requires the old password and persists the change
+#[test]
+fn password_change() {
+    let path = "./password_change.json";
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("old")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    match sman.change_password("wrong", "new") {
+        Err(Error::InvalidPassword) => {}
+        _ => panic!("Password change succeeded with the wrong old password!"),
+    }
+
+    sman.change_password("old", "new").unwrap();
+
+    let sman = SecretsManager::load(path, KeySource::Password("new")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // the password of a store which cannot be saved is left unchanged
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("old")).unwrap();
+    match sman.change_password("old", "new") {
+        Err(Error::InMemoryVault) => {}
+        _ => panic!("The password of an in-memory vault was changed!"),
+    }
+    let keys = KeySource::Password("old")
+        .extract_keys::<DefaultProvider>(&sman.vault.iv, sman.vault.kdf.as_ref())
+        .unwrap();
+    assert_eq!(keys, sman.keys, "The keys were rotated without being saved");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that a vault created with Argon2id records its KDF and is unlocked with it on load
+#[test]
+fn argon2id_kdf() {
+    let path = "./argon2id.json";
+    let kdf = Kdf::Argon2id {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    let mut sman =
+        SecretsManager::new(path, KeySource::PasswordWithKdf("foo", kdf.clone())).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let pbkdf2_keys = KeySource::Password("foo")
+        .extract_keys::<DefaultProvider>(&sman.vault.iv, None)
+        .unwrap();
+    assert_ne!(
+        pbkdf2_keys, sman.keys,
+        "Argon2id-derived keys match PBKDF2-derived keys"
+    );
+
+    let sman = SecretsManager::load(path, KeySource::Password("foo")).unwrap();
+    assert_eq!(Some(kdf), sman.vault.kdf);
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // untrusted parameters are bounded, rather than exhausting memory or time at load
+    let contents = std::fs::read_to_string(path).unwrap();
+    for (field, value) in &[
+        ("memory_kib", u32::MAX),
+        ("iterations", u32::MAX),
+        ("parallelism", 1000),
+    ] {
+        let mut vault: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        vault["kdf"][*field] = (*value).into();
+        std::fs::write(path, vault.to_string()).unwrap();
+        match SecretsManager::load(path, KeySource::Password("foo")) {
+            Err(Error::InvalidKdfParameters) => {}
+            _ => panic!("An out of range Argon2id {} was accepted!", field),
+        }
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that scrypt parameters are recorded in the vault and honored on load
+#[test]
+fn scrypt_kdf() {
+    let path = "./scrypt.json";
+    let kdf = Kdf::Scrypt {
+        log_n: 10,
+        r: 8,
+        p: 1,
+    };
+
+    let mut sman = SecretsManager::builder()
+        .kdf(kdf.clone())
+        .create(path, KeySource::Password("foo"))
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let pbkdf2_keys = KeySource::Password("foo")
+        .extract_keys::<DefaultProvider>(&sman.vault.iv, None)
+        .unwrap();
+    assert_ne!(
+        pbkdf2_keys, sman.keys,
+        "scrypt-derived keys match PBKDF2-derived keys"
+    );
+
+    let sman = SecretsManager::load(path, KeySource::Password("foo")).unwrap();
+    assert_eq!(Some(kdf), sman.vault.kdf);
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let invalid = Kdf::Scrypt {
+        log_n: 0,
+        r: 0,
+        p: 0,
+    };
+    match SecretsManager::new_in_memory(KeySource::PasswordWithKdf("foo", invalid)) {
+        Err(Error::InvalidKdfParameters) => {}
+        _ => panic!("Invalid scrypt parameters were accepted!"),
+    }
+
+    // untrusted parameters are bounded, rather than exhausting memory or time at load
+    let contents = std::fs::read_to_string(path).unwrap();
+    let invalid: &[&[(&str, u32)]] = &[
+        &[("log_n", 21)],
+        &[("r", 1000)],
+        &[("p", u32::MAX)],
+        // each within range, but together using 4 GiB of memory
+        &[("log_n", 20), ("r", 32)],
+        &[("r", 32), ("p", 16)],
+    ];
+    for fields in invalid {
+        let mut vault: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        for (field, value) in *fields {
+            vault["kdf"][*field] = (*value).into();
+        }
+        std::fs::write(path, vault.to_string()).unwrap();
+        match SecretsManager::load(path, KeySource::Password("foo")) {
+            Err(Error::InvalidKdfParameters) => {}
+            _ => panic!("Out of range scrypt parameters {:?} were accepted!", fields),
+        }
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that custom PBKDF2 parameters are recorded in the vault and honored on load
+#[test]
+fn pbkdf2_rounds() {
+    let path = "./pbkdf2_rounds.json";
+    let kdf = Kdf::Pbkdf2 {
+        digest: Digest::Sha1,
+        rounds: 20_000,
+    };
+
+    let mut sman = SecretsManager::builder()
+        .kdf(kdf.clone())
+        .create(path, KeySource::Password("foo"))
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let legacy_keys = KeySource::Password("foo")
+        .extract_keys::<DefaultProvider>(&sman.vault.iv, None)
+        .unwrap();
+    assert_ne!(legacy_keys, sman.keys, "PBKDF2 round count was not honored");
+
+    let sman = SecretsManager::load(path, KeySource::Password("foo")).unwrap();
+    assert_eq!(Some(kdf), sman.vault.kdf);
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // an untrusted round count is bounded, rather than hanging the load
+    let contents = std::fs::read_to_string(path).unwrap();
+    let absurd = contents.replace("20000", &u32::MAX.to_string());
+    std::fs::write(path, absurd).unwrap();
+    match SecretsManager::load(path, KeySource::Password("foo")) {
+        Err(Error::InvalidKdfParameters) => {}
+        _ => panic!("An absurd number of PBKDF2 rounds was accepted!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that vaults using the legacy KDF can be migrated to the default KDF in place
+#[test]
+fn kdf_upgrade() {
+    let path = "./kdf_upgrade.json";
+
+    let mut sman = SecretsManager::builder()
+        .kdf(Kdf::legacy())
+        .create(path, KeySource::Password("foo"))
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let mut sman = SecretsManager::load(path, KeySource::Password("foo")).unwrap();
+    match sman.upgrade_kdf("wrong") {
+        Err(Error::InvalidPassword) => {}
+        _ => panic!("KDF was upgraded with the wrong password!"),
+    }
+    assert_eq!(Some(Kdf::legacy()), sman.vault.kdf);
+
+    assert!(sman.upgrade_kdf("foo").unwrap());
+    assert!(!sman.upgrade_kdf("foo").unwrap());
+    sman.save().unwrap();
+
+    let sman = SecretsManager::load(path, KeySource::Password("foo")).unwrap();
+    assert_eq!(Some(Kdf::default()), sman.vault.kdf);
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that key material is wiped when zeroized and whenever `Keys` are dropped
+#[test]
+fn key_zeroization() {
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+
+    fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+    assert_zeroize_on_drop::<Keys>();
+
+    let mut keys = KeySource::Generate
+        .extract_keys::<DefaultProvider>(&None, None)
+        .unwrap();
+    assert_ne!(keys.encryption, [0u8; KEY_LENGTH]);
+    assert_ne!(keys.hmac, [0u8; KEY_LENGTH]);
+
+    keys.zeroize();
+    assert_eq!(keys.encryption, [0u8; KEY_LENGTH]);
+    assert_eq!(keys.hmac, [0u8; KEY_LENGTH]);
+}
+
+/// Verify that keys can be loaded from an in-memory buffer in the keyfile format
+#[test]
+fn buffer_key_source() {
+    let path = "./buffer.json";
+    let keyfile = "./buffer.key";
+
+    let mut sman = SecretsManager::new(path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    sman.export_keyfile(keyfile).unwrap();
+
+    let key_data = std::fs::read(keyfile).unwrap();
+    let sman = SecretsManager::load(path, KeySource::Buffer(&key_data)).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    match SecretsManager::load(path, KeySource::Buffer(&key_data[1..])) {
+        Err(Error::KeyfileTooShort {
+            expected: 32,
+            actual: 31,
+        }) => {}
+        _ => panic!("Loading keys from a truncated buffer did not return KeyfileTooShort!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(keyfile).unwrap();
+}
+
+/// Verify that keys can be streamed from any reader and that truncated streams are rejected
+#[test]
+fn reader_key_source() {
+    use std::io::Cursor;
+
+    let path = "./reader.json";
+
+    let mut sman = SecretsManager::new(path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let mut key_data = Vec::new();
+    key_data.extend_from_slice(&sman.keys.encryption);
+    key_data.extend_from_slice(&sman.keys.hmac);
+
+    let mut reader = Cursor::new(&key_data);
+    let sman = SecretsManager::load(path, KeySource::Reader(&mut reader)).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let mut truncated = Cursor::new(&key_data[..KEY_LENGTH]);
+    match SecretsManager::load(path, KeySource::Reader(&mut truncated)) {
+        Err(Error::KeyfileTooShort { .. }) => {}
+        _ => panic!("Loading keys from a truncated stream did not return KeyfileTooShort!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that passwords and keys can be read from environment variables
+#[test]
+fn env_key_sources() {
+    let path = "./env.json";
+
+    std::env::set_var("SECURESTORE_TEST_PASSWORD", "hunter2");
+    let mut sman =
+        SecretsManager::new(path, KeySource::EnvPassword("SECURESTORE_TEST_PASSWORD")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert!(sman.vault.kdf.is_some());
+
+    let sman = SecretsManager::load(path, KeySource::Password("hunter2")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let mut key_data = Vec::new();
+    key_data.extend_from_slice(&sman.keys.encryption);
+    key_data.extend_from_slice(&sman.keys.hmac);
+    std::env::set_var("SECURESTORE_TEST_KEY", base64::encode(&key_data));
+    let sman = SecretsManager::load(path, KeySource::EnvKeyBase64("SECURESTORE_TEST_KEY")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::env::set_var("SECURESTORE_TEST_KEY", "not base64!");
+    match SecretsManager::load(path, KeySource::EnvKeyBase64("SECURESTORE_TEST_KEY")) {
+        Err(Error::InvalidKeyfile) => {}
+        _ => panic!("Loading an invalid base64 key did not return InvalidKeyfile!"),
+    }
+
+    match SecretsManager::load(path, KeySource::EnvPassword("SECURESTORE_TEST_UNSET")) {
+        Err(Error::EnvVar(std::env::VarError::NotPresent)) => {}
+        _ => panic!("Loading a password from an unset variable did not return EnvVar!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that a prompt callback is only invoked when a password is needed
+#[test]
+fn prompt_key_source() {
+    use std::cell::Cell;
+
+    let path = "./prompt.json";
+    let prompts = Cell::new(0);
+    let prompt = || {
+        prompts.set(prompts.get() + 1);
+        Ok("hunter2".to_string())
+    };
+
+    let mut sman = SecretsManager::new(path, KeySource::Prompt(Box::new(prompt))).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert_eq!(1, prompts.get());
+
+    let source = KeySource::Prompt(Box::new(prompt));
+    assert_eq!(
+        1,
+        prompts.get(),
+        "Prompt invoked before the vault was unlocked"
+    );
+    let sman = SecretsManager::load(path, source).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    assert_eq!(2, prompts.get());
+
+    // errors returned by the callback are passed through as-is
+    let source = KeySource::Prompt(Box::new(|| Err(Error::InvalidPassword)));
+    match SecretsManager::load(path, source) {
+        Err(Error::InvalidPassword) => {}
+        _ => panic!("Error returned by prompt callback was not passed through!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that any `k` of `n` key shares reconstruct the keys, and fewer than `k` do not
+#[test]
+fn shamir_key_shares() {
+    let path = "./shares.json";
+    let dir = std::path::Path::new("./shares");
+
+    let mut sman = SecretsManager::new(path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let shares = sman.export_keys_split(5, 3, dir).unwrap();
+    assert_eq!(5, shares.len());
+
+    for subset in &[[0, 1, 2], [0, 2, 4], [4, 3, 1]] {
+        let selected: Vec<PathBuf> = subset.iter().map(|&i| shares[i].clone()).collect();
+        let loaded = SecretsManager::load(path, KeySource::Shares(&selected)).unwrap();
+        assert_eq!(sman.keys, loaded.keys);
+        assert_eq!("bar", loaded.retrieve::<String>("foo").unwrap());
+    }
+
+    // duplicated shares do not count towards the threshold
+    let duplicated = vec![shares[0].clone(), shares[1].clone(), shares[1].clone()];
+    match SecretsManager::load(path, KeySource::Shares(&duplicated)) {
+        Err(Error::InvalidKeyShares) => {}
+        _ => panic!("Fewer shares than the threshold did not return InvalidKeyShares!"),
+    }
+
+    // shares of different splits cannot be combined
+    let other = sman.export_keys_split(3, 2, "./shares/other").unwrap();
+    let mixed = vec![shares[0].clone(), other[1].clone(), shares[2].clone()];
+    match SecretsManager::load(path, KeySource::Shares(&mixed)) {
+        Err(Error::InvalidKeyShares) => {}
+        _ => panic!("Shares of different splits did not return InvalidKeyShares!"),
+    }
+
+    match sman.export_keys_split(2, 3, dir) {
+        Err(Error::InvalidKeyShares) => {}
+        _ => panic!("A threshold above the share count did not return InvalidKeyShares!"),
+    }
+
+    let missing = vec![shares[0].clone(), dir.join("share-9.key")];
+    match SecretsManager::load(path, KeySource::Shares(&missing)) {
+        Err(Error::File { path, .. }) => assert_eq!(missing[1], path),
+        _ => panic!("Loading a missing share did not return its path!"),
+    }
+
+    std::fs::remove_dir_all(dir).unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that armored keyfiles can be loaded in place of binary keyfiles
+#[test]
+fn armored_keyfile() {
+    let path = "./armored.json";
+    let keyfile = "./armored.key";
+
+    let mut sman = SecretsManager::new(path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    sman.export_keyfile_armored(keyfile).unwrap();
+
+    let armored = std::fs::read_to_string(keyfile).unwrap();
+    assert!(armored.starts_with("-----BEGIN SECURESTORE KEYS-----\n"));
+    assert!(armored.ends_with("-----END SECURESTORE KEYS-----\n"));
+
+    let loaded = SecretsManager::load(path, KeySource::File(Path::new(keyfile))).unwrap();
+    assert_eq!(sman.keys, loaded.keys);
+    assert_eq!("bar", loaded.retrieve::<String>("foo").unwrap());
+
+    // surrounding whitespace and line breaks within the base64 are ignored
+    let (begin, rest) = armored.split_at(33);
+    let rewrapped = format!("\n{}{}\n{}\n", begin, &rest[..10], &rest[10..]);
+    std::fs::write(keyfile, rewrapped).unwrap();
+    SecretsManager::load(path, KeySource::File(Path::new(keyfile))).unwrap();
+
+    std::fs::write(keyfile, armored.replace("END", "FIN")).unwrap();
+    match SecretsManager::load(path, KeySource::File(Path::new(keyfile))) {
+        Err(Error::InvalidKeyfile) => {}
+        _ => panic!("Loading a malformed armored keyfile did not return InvalidKeyfile!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(keyfile).unwrap();
+}
+
+/// Verify that keyfiles protected by a passphrase can only be loaded with that passphrase
+#[test]
+fn protected_keyfile() {
+    let path = "./protected.json";
+    let keyfile = "./protected.key";
+
+    let mut sman = SecretsManager::new(path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    sman.export_keyfile_with_password(keyfile, "hunter2")
+        .unwrap();
+
+    // the keys are not stored in the clear
+    let contents = std::fs::read(keyfile).unwrap();
+    assert!(!contents
+        .windows(shared::KEY_LENGTH)
+        .any(|w| w == sman.keys.encryption));
+
+    let source = KeySource::ProtectedFile {
+        path: Path::new(keyfile),
+        passphrase: "hunter2",
+    };
+    let loaded = SecretsManager::load(path, source).unwrap();
+    assert_eq!(sman.keys, loaded.keys);
+    assert_eq!("bar", loaded.retrieve::<String>("foo").unwrap());
+
+    let source = KeySource::ProtectedFile {
+        path: Path::new(keyfile),
+        passphrase: "hunter3",
+    };
+    match SecretsManager::load(path, source) {
+        Err(Error::DecryptionFailure) => {}
+        _ => panic!("Loading a protected keyfile with the wrong passphrase did not fail!"),
+    }
+
+    let missing = Path::new("./protected-missing.key");
+    let source = KeySource::ProtectedFile {
+        path: missing,
+        passphrase: "hunter2",
+    };
+    match SecretsManager::load(path, source) {
+        Err(Error::File { path, .. }) => assert_eq!(missing, path),
+        _ => panic!("Loading a missing protected keyfile did not return its path!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(keyfile).unwrap();
+}
+
+/// Verify that the wrong keys are rejected with `InvalidKey` as soon as the vault is loaded
+#[test]
+fn sentinel_key_verification() {
+    let path = "./sentinel.json";
+    let keyfile = "./sentinel.key";
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert!(sman.vault.sentinel.is_some(), "No sentinel was created!");
+
+    match SecretsManager::load(path, KeySource::Password("wrong")) {
+        Err(Error::InvalidKey) => {}
+        _ => panic!("Loading with the wrong password did not return InvalidKey!"),
+    }
+
+    let other = SecretsManager::new("./sentinel-other.json", KeySource::Generate).unwrap();
+    other.export_keyfile(keyfile).unwrap();
+    match SecretsManager::load(path, KeySource::File(Path::new(keyfile))) {
+        Err(Error::InvalidKey) => {}
+        _ => panic!("Loading with the wrong keyfile did not return InvalidKey!"),
+    }
+
+    // the sentinel cannot be stripped to hide that the keys are wrong
+    let mut vault: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let vault = vault.as_object_mut().unwrap();
+    vault.remove("sentinel");
+    vault.remove("mac");
+    std::fs::write(path, serde_json::to_string(vault).unwrap()).unwrap();
+    match SecretsManager::load(path, KeySource::Password("wrong")) {
+        Err(Error::VaultTampered) => {}
+        _ => panic!("A vault without a sentinel did not return VaultTampered!"),
+    }
+
+    // nor stripped together with the scheme to make the vault appear to be a legacy one
+    let generated = "./sentinel-generated.json";
+    let mut sman = SecretsManager::new(generated, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let mut vault: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(generated).unwrap()).unwrap();
+    let vault = vault.as_object_mut().unwrap();
+    vault.remove("sentinel");
+    vault.remove("scheme");
+    vault.remove("mac");
+    std::fs::write(generated, serde_json::to_string(vault).unwrap()).unwrap();
+    match SecretsManager::load(generated, KeySource::File(Path::new(keyfile))) {
+        Err(Error::VaultTampered) => {}
+        _ => panic!("A vault without a sentinel or scheme did not return VaultTampered!"),
+    }
+
+    std::fs::remove_file(generated).unwrap();
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(keyfile).unwrap();
+}
+
+/// Verify that passwords for new vaults are checked against the policy, if one is set
+#[test]
+fn password_policy() {
+    let builder = || SecretsManager::builder().password_policy(Some(PasswordPolicy::default()));
+
+    for weak in &[
+        "a",
+        "hunter2",
+        "aaaaaaaaaaaaaaaa",
+        "abcdefghijklmnop",
+        "Password1234",
+    ] {
+        match builder().create_in_memory(KeySource::Password(weak)) {
+            Err(Error::WeakPassword(_)) => {}
+            _ => panic!("Weak password {:?} was accepted!", weak),
+        }
+    }
+    builder()
+        .create_in_memory(KeySource::Password("correct horse battery staple"))
+        .unwrap();
+
+    // Passwords from a prompt are checked, having only been requested once
+    let prompts = std::cell::Cell::new(0);
+    let prompt = || {
+        prompts.set(prompts.get() + 1);
+        Ok("hunter2".to_string())
+    };
+    assert!(matches!(
+        builder().create_in_memory(KeySource::Prompt(Box::new(prompt))),
+        Err(Error::WeakPassword(_))
+    ));
+    assert_eq!(1, prompts.get());
+
+    // Other key sources and vaults created without a policy are unaffected
+    builder().create_in_memory(KeySource::Generate).unwrap();
+    SecretsManager::new_in_memory(KeySource::Password("a")).unwrap();
+}
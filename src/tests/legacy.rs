@@ -0,0 +1,215 @@
+//! Tests pinning the legacy vault and keyfile layout documented in [`crate::shared`], as written
+//! by earlier versions of this crate, so that such vaults remain readable and are written back
+//! unchanged.
+
+use crate::shared::*;
+use crate::*;
+
+/// A vault protected by the password "compatibility"
+const PASSWORD_VAULT: &str = r#"{
+  "version": 2,
+  "iv": "AAECAwQFBgcICQoLDA0ODw==",
+  "secrets": {
+    "foo": {
+      "iv": "EBESExQVFhcYGRobHB0eHw==",
+      "hmac": "AUg9sr+WGxfFNBbrRfMqVYwNIfI=",
+      "payload": "a+2kEJAp5eW85vKTBNiM0w=="
+    },
+    "unicode": {
+      "iv": "ICEiIyQlJicoKSorLC0uLw==",
+      "hmac": "DDLWQGr30FaetyrjBFouX7IHG3M=",
+      "payload": "cb4YgnrsuDJjD1noJqdn+A=="
+    }
+  }
+}"#;
+
+/// A vault protected by the keyfile [`KEYFILE`], with no IV for key derivation
+const KEYFILE_VAULT: &str = r#"{
+  "version": 2,
+  "iv": null,
+  "secrets": {
+    "foo": {
+      "iv": "MDEyMzQ1Njc4OTo7PD0+Pw==",
+      "hmac": "iAnmyD8CzRENGplOURwJmJZeroY=",
+      "payload": "MiEu/ZdKfTvr0WO4h6Rqpg=="
+    }
+  }
+}"#;
+
+/// The bytes 100 through 131, inclusive
+const KEYFILE: [u8; KEY_COUNT * KEY_LENGTH] = [
+    100, 101, 102, 103, 104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118,
+    119, 120, 121, 122, 123, 124, 125, 126, 127, 128, 129, 130, 131,
+];
+
+fn open(vault: &str, key_source: KeySource) -> SecretsManager {
+    let mut vault = Vault::load(vault.as_bytes()).unwrap();
+    let keys = key_source
+        .extract_keys::<DefaultProvider>(&vault.iv, vault.kdf.as_ref())
+        .unwrap();
+    // as when unlocked, so that the vault remains readable once its scheme is changed
+    if vault.sentinel.is_none() {
+        vault.seal_sentinel::<DefaultProvider>(&keys);
+    }
+    SecretsManager {
+        keys,
+        storage: Some(Box::new(FileStorage::new("./legacy.json"))),
+        vault,
+        lock: None,
+        readonly: false,
+        autosave: None,
+        compression_threshold: None,
+        hash_traced_names: false,
+        hooks: Vec::new(),
+        allow_unauthenticated: true,
+        provider: std::marker::PhantomData,
+    }
+}
+
+/// Verify that a password-protected vault decrypts with the expected PBKDF2 parameters
+#[test]
+fn password_vault() {
+    let sman = open(PASSWORD_VAULT, KeySource::Password("compatibility"));
+
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    assert_eq!("grüße", sman.retrieve::<String>("unicode").unwrap());
+}
+
+/// Verify that a vault without an IV decrypts with a binary keyfile
+#[test]
+fn keyfile_vault() {
+    let path = "./legacy.key";
+    std::fs::write(path, &KEYFILE[..]).unwrap();
+
+    let sman = open(KEYFILE_VAULT, KeySource::File(path.as_ref()));
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that re-serializing a vault reproduces the original file exactly
+#[test]
+fn vault_serialization() {
+    for fixture in &[PASSWORD_VAULT, KEYFILE_VAULT] {
+        let vault = Vault::load(fixture.as_bytes()).unwrap();
+        assert_eq!(*fixture, serde_json::to_string_pretty(&vault).unwrap());
+    }
+}
+
+/// Verify that vaults written before secrets were stored under `secrets` remain readable
+#[test]
+fn legacy_data_field() {
+    let legacy = PASSWORD_VAULT
+        .replace(r#""version": 2"#, r#""version": 1"#)
+        .replace(r#""secrets""#, r#""data""#);
+
+    let sman = open(&legacy, KeySource::Password("compatibility"));
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+}
+
+/// Verify that legacy vaults are migrated to the latest format only when upgraded
+#[test]
+fn legacy_vault_upgrade() {
+    let legacy = PASSWORD_VAULT
+        .replace(r#""version": 2"#, r#""version": 1"#)
+        .replace(r#""secrets""#, r#""data""#);
+
+    let mut sman = open(&legacy, KeySource::Password("compatibility"));
+    assert_eq!(1, sman.vault.version);
+    assert!(sman.upgrade().unwrap());
+    assert!(
+        !sman.upgrade().unwrap(),
+        "Upgrading twice made further changes"
+    );
+
+    // apart from the sentinel added at load, the fields are those of the latest format
+    sman.vault.sentinel = None;
+    assert_eq!(
+        PASSWORD_VAULT,
+        serde_json::to_string_pretty(&sman.vault).unwrap()
+    );
+}
+
+/// Verify that vaults without a MAC are only loaded if explicitly allowed, and require one once
+/// saved
+#[test]
+fn legacy_vault_mac() {
+    let password = || KeySource::Password("compatibility");
+    match SecretsManager::from_bytes(PASSWORD_VAULT.as_bytes(), password()) {
+        Err(Error::VaultTampered) => {}
+        _ => panic!("A vault without a MAC was loaded without being allowed!"),
+    }
+
+    let sman = SecretsManager::builder()
+        .allow_unauthenticated(true)
+        .load_from_bytes(PASSWORD_VAULT.as_bytes(), password())
+        .unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let mut vault: serde_json::Value = serde_json::from_slice(&sman.to_bytes().unwrap()).unwrap();
+    vault.as_object_mut().unwrap().remove("mac");
+    match SecretsManager::from_bytes(vault.to_string().as_bytes(), password()) {
+        Err(Error::VaultTampered) => {}
+        _ => panic!("A saved legacy vault without a MAC did not return VaultTampered!"),
+    }
+}
+
+/// Verify that vaults written in a newer format are rejected rather than misread
+#[test]
+fn newer_vault_version() {
+    let newer = PASSWORD_VAULT.replace(r#""version": 2"#, r#""version": 3"#);
+
+    match Vault::load(newer.as_bytes()) {
+        Err(Error::UnsupportedVaultVersion) => {}
+        _ => panic!("A vault with a newer version did not return UnsupportedVaultVersion!"),
+    }
+}
+
+/// Verify that secrets can be migrated to a different scheme without changing the keys
+#[test]
+fn scheme_upgrade() {
+    let mut sman = open(PASSWORD_VAULT, KeySource::Password("compatibility"));
+    let keys = sman.keys.combined();
+
+    sman.upgrade_scheme(Scheme::ChaCha20Poly1305).unwrap();
+    assert_eq!(Some(Scheme::ChaCha20Poly1305), sman.vault.scheme);
+    assert_eq!(keys, sman.keys.combined());
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    assert_eq!("grüße", sman.retrieve::<String>("unicode").unwrap());
+
+    // the rewritten vault remains readable with the same password
+    let upgraded = serde_json::to_string_pretty(&sman.vault).unwrap();
+    let sman = open(&upgraded, KeySource::Password("compatibility"));
+    assert_eq!("grüße", sman.retrieve::<String>("unicode").unwrap());
+}
+
+/// Verify that the MAC declared by the scheme is used for both the secrets and the vault
+#[test]
+fn scheme_mac() {
+    let mut sman = open(PASSWORD_VAULT, KeySource::Password("compatibility"));
+    sman.upgrade_scheme(Scheme::Aes128CbcHmacSha256).unwrap();
+    assert!(sman
+        .vault
+        .secrets
+        .values()
+        .all(|blob| blob.hmac.len() == MacAlgorithm::HmacSha256.size()));
+
+    let bytes = sman.to_bytes().unwrap();
+    let sman = SecretsManager::from_bytes(&bytes, KeySource::Password("compatibility")).unwrap();
+    assert_eq!(
+        Some(MacAlgorithm::HmacSha256.size()),
+        sman.vault.mac.as_ref().map(Vec::len)
+    );
+    assert_eq!("grüße", sman.retrieve::<String>("unicode").unwrap());
+
+    // the MAC is verified with the declared algorithm, so the vault cannot be downgraded to
+    // HMAC-SHA1 without detection
+    let downgraded = String::from_utf8(bytes)
+        .unwrap()
+        .replace("aes128-cbc-hmac-sha256", "aes128-cbc-hmac-sha1");
+    assert!(SecretsManager::from_bytes(
+        downgraded.as_bytes(),
+        KeySource::Password("compatibility")
+    )
+    .is_err());
+}
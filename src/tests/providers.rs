@@ -0,0 +1,119 @@
+//! Tests for plugging alternative [`CryptoProvider`] implementations into a `SecretsManager`.
+
+use crate::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RANDOM_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// A provider that defers to the default provider, counting requests for random bytes
+struct CountingProvider;
+
+impl CryptoProvider for CountingProvider {
+    fn random(buffer: &mut [u8]) {
+        RANDOM_CALLS.fetch_add(1, Ordering::SeqCst);
+        DefaultProvider::random(buffer)
+    }
+
+    fn pbkdf2(digest: Digest, password: &[u8], salt: &[u8], rounds: u32, output: &mut [u8]) {
+        DefaultProvider::pbkdf2(digest, password, salt, rounds, output)
+    }
+
+    fn hmac_sha1(key: &[u8; 16], data: &[&[u8]]) -> [u8; 20] {
+        DefaultProvider::hmac_sha1(key, data)
+    }
+
+    fn hmac_sha256(key: &[u8; 16], data: &[&[u8]]) -> [u8; 32] {
+        DefaultProvider::hmac_sha256(key, data)
+    }
+
+    fn hmac(digest: Digest, key: &[u8], data: &[u8]) -> Vec<u8> {
+        DefaultProvider::hmac(digest, key, data)
+    }
+
+    fn aes_128_cbc_encrypt(key: &[u8; 16], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+        DefaultProvider::aes_128_cbc_encrypt(key, iv, plaintext)
+    }
+
+    fn aes_128_cbc_decrypt(
+        key: &[u8; 16],
+        iv: &[u8; 16],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        DefaultProvider::aes_128_cbc_decrypt(key, iv, ciphertext)
+    }
+
+    fn aead_encrypt(
+        aead: Aead,
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+        plaintext: &[u8],
+        tag: &mut [u8; 16],
+    ) -> Vec<u8> {
+        DefaultProvider::aead_encrypt(aead, key, nonce, plaintext, tag)
+    }
+
+    fn aead_decrypt(
+        aead: Aead,
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+        ciphertext: &[u8],
+        tag: &[u8; 16],
+    ) -> Result<Vec<u8>, Error> {
+        DefaultProvider::aead_decrypt(aead, key, nonce, ciphertext, tag)
+    }
+
+    fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+        DefaultProvider::ct_eq(a, b)
+    }
+}
+
+/// Verify that a custom provider selected via the builder is used for all operations
+#[test]
+fn custom_provider() {
+    let path = "./custom_provider.json";
+
+    let mut sman = SecretsManager::builder()
+        .provider::<CountingProvider>()
+        .create(path, KeySource::Password("mysecret"))
+        .unwrap();
+    let before = RANDOM_CALLS.load(Ordering::SeqCst);
+    sman.set("foo", "bar");
+    assert!(RANDOM_CALLS.load(Ordering::SeqCst) > before);
+    sman.save().unwrap();
+
+    // the vault remains readable with the default provider
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that vaults written with one built-in provider can be read with the other
+#[cfg(all(feature = "openssl", feature = "rust-crypto"))]
+#[test]
+fn cross_provider() {
+    let path = "./cross_provider.json";
+
+    for &scheme in &[
+        Scheme::Aes128CbcHmacSha1,
+        Scheme::Aes128CbcHmacSha256,
+        Scheme::Aes256Gcm,
+        Scheme::ChaCha20Poly1305,
+    ] {
+        let mut sman = SecretsManager::builder()
+            .provider::<OpenSsl>()
+            .scheme(scheme)
+            .create(path, KeySource::Password("mysecret"))
+            .unwrap();
+        sman.set("foo", "bar");
+        sman.save().unwrap();
+
+        let sman = SecretsManager::builder()
+            .provider::<RustCrypto>()
+            .load(path, KeySource::Password("mysecret"))
+            .unwrap();
+        assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
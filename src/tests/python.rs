@@ -0,0 +1,61 @@
+//! Tests of the Python bindings
+
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::wrap_pymodule;
+
+/// Verify that secrets can be set, saved and loaded through the Python module, and that
+/// failures raise the documented exceptions.
+#[test]
+fn python_round_trip() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let globals = PyDict::new(py);
+        globals
+            .set_item(
+                "securestore",
+                wrap_pymodule!(crate::python::securestore)(py),
+            )
+            .unwrap();
+        py.run(
+            c_str!(
+                r#"
+path = "./python.json"
+sman = securestore.SecretsManager.new(path, password="mysecret")
+sman.set("foo", "bar")
+sman.set("bin", b"\x00\xff")
+sman.save()
+
+sman = securestore.SecretsManager.load(path, password="mysecret")
+assert sman.keys() == ["bin", "foo"]
+assert sman.get("foo") == "bar"
+assert sman.get_bytes("bin") == b"\x00\xff"
+assert len(sman) == 2 and "foo" in sman and "baz" not in sman
+
+try:
+    sman.get("baz")
+    assert False
+except KeyError:
+    pass
+
+try:
+    securestore.SecretsManager.load(path, password="wrong")
+    assert False
+except securestore.SecureStoreError:
+    pass
+
+try:
+    securestore.SecretsManager.load(path)
+    assert False
+except ValueError:
+    pass
+"#
+            ),
+            Some(&globals),
+            None,
+        )
+        .unwrap();
+    });
+    std::fs::remove_file("./python.json").unwrap();
+}
@@ -1,5 +1,6 @@
 //! Highest-level tests for the secure store
 
+use crate::errors::Error;
 use crate::{KeySource, SecretsManager};
 
 /// Verify that basic storage and retrieval of secrets functions correctly.
@@ -24,3 +25,1774 @@ fn basic_store_retrieve() {
     let retrieved: String = sman2.retrieve("foo").unwrap();
     assert_eq!("bar", retrieved);
 }
+
+/// Verify that secrets can be removed and that removing a missing secret is an error.
+#[test]
+fn remove_secret() {
+    let mut sman = SecretsManager::new("./remove.json", KeySource::Generate).unwrap();
+
+    sman.set("foo", "bar");
+    sman.remove("foo").unwrap();
+
+    match sman.retrieve::<String>("foo") {
+        Err(Error::SecretNotFound(name)) => assert_eq!("foo", name),
+        _ => panic!("Retrieving a removed secret did not return SecretNotFound!"),
+    }
+
+    match sman.remove("foo") {
+        Err(Error::SecretNotFound(name)) => assert_eq!("foo", name),
+        _ => panic!("Removing a missing secret did not return SecretNotFound!"),
+    }
+}
+
+/// Verify that the names of stored secrets can be enumerated.
+#[test]
+fn enumerate_keys() {
+    let mut sman = SecretsManager::new("./keys.json", KeySource::Generate).unwrap();
+    assert!(sman.is_empty());
+
+    sman.set("foo", "1");
+    sman.set("bar", "2");
+    sman.set("foo", "3");
+
+    assert_eq!(2, sman.len());
+    assert!(!sman.is_empty());
+    assert_eq!(vec!["bar", "foo"], sman.keys().collect::<Vec<_>>());
+}
+
+/// Verify that binary secrets that are not valid UTF-8 round-trip losslessly.
+#[test]
+fn binary_store_retrieve() {
+    let path = "./binary.json";
+    let secret = [0xffu8, 0x00, 0xfe, 0x80, 0x7f];
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set_bytes("blob", &secret);
+    assert_eq!(&secret[..], sman.retrieve_bytes("blob").unwrap().as_slice());
+    sman.save().unwrap();
+
+    let sman2 = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    let retrieved: Vec<u8> = sman2.retrieve("blob").unwrap();
+    assert_eq!(&secret[..], retrieved.as_slice());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that structured values can be stored and retrieved via serde.
+#[test]
+fn serde_store_retrieve() {
+    use crate::Json;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct DbConfig {
+        user: String,
+        port: u16,
+    }
+
+    let config = DbConfig {
+        user: "postgres".to_string(),
+        port: 5432,
+    };
+
+    let mut sman = SecretsManager::new("./serde.json", KeySource::Generate).unwrap();
+    sman.set_json("db", &config).unwrap();
+
+    let Json(retrieved): Json<DbConfig> = sman.retrieve("db").unwrap();
+    assert_eq!(config, retrieved);
+
+    // a value that can't be represented as JSON is an error, not a panic
+    let unrepresentable: std::collections::HashMap<(u8, u8), u8> = [((1, 2), 3)].into();
+    match sman.set_json("map", &unrepresentable) {
+        Err(Error::Serde(_)) => {}
+        _ => panic!("Serializing a map with non-string keys did not return Error::Serde!"),
+    }
+    assert!(sman.keys().all(|name| name != "map"));
+
+    // a value that isn't valid JSON for the requested type is an error, not a panic
+    sman.set("db", "not json");
+    match sman.retrieve::<Json<DbConfig>>("db") {
+        Err(Error::Serde(_)) => {}
+        _ => panic!("Deserializing a mismatched value did not return Error::Serde!"),
+    }
+}
+
+/// Verify that saving replaces an existing vault without leaving temporary files behind.
+#[test]
+fn atomic_save() {
+    let dir = std::env::temp_dir().join("securestore-atomic-save");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    let path = dir.join("secrets.json");
+
+    let mut sman = SecretsManager::new(&path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    sman.set("foo", "baz");
+    sman.save().unwrap();
+
+    let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(
+        1,
+        entries.len(),
+        "Temporary files were left behind after saving!"
+    );
+
+    let sman = SecretsManager::load(&path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("baz", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that a locked vault cannot be locked again until the lock holder is dropped.
+#[test]
+fn vault_locking() {
+    let path = "./locking.json";
+    SecretsManager::new(path, KeySource::Password("mysecret"))
+        .unwrap()
+        .save()
+        .unwrap();
+
+    let sman = SecretsManager::try_load_locked(path, KeySource::Password("mysecret")).unwrap();
+    assert!(sman.is_locked());
+
+    match SecretsManager::try_load_locked(path, KeySource::Password("mysecret")) {
+        Err(Error::VaultLocked) => {}
+        _ => panic!("Locked a vault that was already locked!"),
+    }
+
+    // unlocked access is unaffected
+    SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+
+    drop(sman);
+    SecretsManager::try_load_locked(path, KeySource::Password("mysecret")).unwrap();
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file("./.locking.json.lock").unwrap();
+}
+
+/// Verify that the encryption scheme selected at creation is recorded and used on load.
+#[test]
+fn aes_gcm_vault() {
+    use crate::Scheme;
+
+    let path = "./aes_gcm.json";
+    let mut sman = SecretsManager::builder()
+        .scheme(Scheme::Aes256Gcm)
+        .create(path, KeySource::Password("mysecret"))
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(Scheme::Aes256Gcm, sman.vault.scheme());
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that secrets retrieved as a `SecretString` are redacted when formatted.
+#[test]
+fn redacted_secret_retrieve() {
+    use crate::{Secret, SecretString};
+
+    let mut sman = SecretsManager::new("./redacted.json", KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.set("baz", Secret::new("qux".to_string()));
+
+    let secret = sman.retrieve_secret("foo").unwrap();
+    assert_eq!("bar", secret.expose_secret());
+    assert_eq!("[REDACTED]", format!("{:?}", secret));
+    assert_eq!("[REDACTED]", format!("{}", secret));
+
+    let secret: SecretString = sman.retrieve("baz").unwrap();
+    assert_eq!("qux", secret.expose_secret());
+
+    // the plain `String` API remains available
+    let plain: String = sman.retrieve("foo").unwrap();
+    assert_eq!("bar", plain);
+}
+
+/// Verify that every stored secret is encrypted under its own IV, across secrets holding the
+/// same value, updates to the same secret, and key rotations, for all schemes.
+#[test]
+fn per_secret_ivs() {
+    use crate::Scheme;
+
+    let path = "./per_secret_ivs.json";
+    for &scheme in &[
+        Scheme::Aes128CbcHmacSha1,
+        Scheme::Aes256Gcm,
+        Scheme::ChaCha20Poly1305,
+    ] {
+        let mut sman = SecretsManager::builder()
+            .scheme(scheme)
+            .create(path, KeySource::Generate)
+            .unwrap();
+
+        let mut blobs = Vec::new();
+        for name in &["a", "b", "a"] {
+            sman.set(name, "same value");
+            let blob = &sman.vault.secrets[*name];
+            blobs.push((blob.iv.clone(), blob.payload.clone()));
+        }
+        sman.rotate_keys(KeySource::Generate).unwrap();
+        for name in &["a", "b"] {
+            let blob = &sman.vault.secrets[*name];
+            blobs.push((blob.iv.clone(), blob.payload.clone()));
+        }
+
+        for (i, (iv, payload)) in blobs.iter().enumerate() {
+            for (other_iv, other_payload) in &blobs[i + 1..] {
+                assert_ne!(iv, other_iv, "IV reused with scheme {:?}", scheme);
+                assert_ne!(
+                    payload, other_payload,
+                    "Ciphertext repeated with {:?}",
+                    scheme
+                );
+            }
+        }
+        assert_eq!("same value", sman.retrieve::<String>("a").unwrap());
+    }
+}
+
+/// Verify that removing, adding or reverting secrets on-disk is detected at load
+#[test]
+fn vault_tampering() {
+    let path = "./tampering.json";
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.set("baz", "qux");
+    sman.save().unwrap();
+    let original = std::fs::read_to_string(path).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&original).unwrap();
+    assert!(saved["mac"].is_string(), "No MAC was written to the vault!");
+
+    sman.set("foo", "updated");
+    sman.save().unwrap();
+    let updated = std::fs::read_to_string(path).unwrap();
+    SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+
+    let mut tampered: Vec<String> = Vec::new();
+    // a secret was deleted
+    let mut vault = serde_json::from_str::<serde_json::Value>(&updated).unwrap();
+    vault["secrets"].as_object_mut().unwrap().remove("baz");
+    tampered.push(vault.to_string());
+    // a secret was reverted to an older (authentic) ciphertext
+    let mut vault = serde_json::from_str::<serde_json::Value>(&updated).unwrap();
+    vault["secrets"]["foo"] = saved["secrets"]["foo"].clone();
+    tampered.push(vault.to_string());
+    // a secret was added to an older copy of the vault
+    let mut vault = saved.clone();
+    vault["secrets"]["extra"] = saved["secrets"]["foo"].clone();
+    tampered.push(vault.to_string());
+
+    for contents in tampered {
+        std::fs::write(path, contents).unwrap();
+        match SecretsManager::load(path, KeySource::Password("mysecret")) {
+            Err(Error::VaultTampered) => {}
+            _ => panic!("A tampered vault did not return VaultTampered!"),
+        }
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that the MAC cannot be stripped from a vault to avoid its verification
+#[test]
+fn vault_mac_required() {
+    let path = "./mac_required.json";
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let mut vault: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    vault.as_object_mut().unwrap().remove("mac");
+    std::fs::write(path, vault.to_string()).unwrap();
+    match SecretsManager::load(path, KeySource::Password("mysecret")) {
+        Err(Error::VaultTampered) => {}
+        _ => panic!("A vault without a MAC did not return VaultTampered!"),
+    }
+
+    // nor can a vault be passed off as one written before vaults were authenticated, by
+    // stripping every field that this crate adds
+    let keyfile = "./mac_required.key";
+    let mut sman = SecretsManager::new(path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.set("baz", "qux");
+    sman.save().unwrap();
+    sman.export_keyfile(keyfile).unwrap();
+    let mut vault: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let fields = vault.as_object_mut().unwrap();
+    for field in &["mac", "sentinel", "scheme"] {
+        fields.remove(*field);
+    }
+    fields["secrets"].as_object_mut().unwrap().remove("baz");
+    std::fs::write(path, vault.to_string()).unwrap();
+    match SecretsManager::load(path, KeySource::File(keyfile.as_ref())) {
+        Err(Error::VaultTampered) => {}
+        _ => panic!("A vault stripped of its MAC, sentinel and scheme was loaded!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(keyfile).unwrap();
+}
+
+/// Verify that a missing secret can be told apart from a secret that fails to authenticate
+#[test]
+fn missing_vs_undecryptable_secret() {
+    use crate::Scheme;
+
+    for &scheme in &[
+        Scheme::Aes128CbcHmacSha1,
+        Scheme::Aes256Gcm,
+        Scheme::ChaCha20Poly1305,
+    ] {
+        let mut sman = SecretsManager::builder()
+            .scheme(scheme)
+            .create("./undecryptable.json", KeySource::Generate)
+            .unwrap();
+        sman.set("foo", "bar");
+
+        match sman.retrieve::<String>("fo") {
+            Err(Error::SecretNotFound(name)) => assert_eq!("fo", name),
+            _ => panic!("Retrieving a misspelled secret did not return SecretNotFound!"),
+        }
+
+        sman.keys = Default::default();
+        match sman.retrieve::<String>("foo") {
+            Err(Error::MacMismatch) => {}
+            _ => panic!("Retrieving with the wrong keys did not return MacMismatch!"),
+        }
+    }
+}
+
+/// Verify that a vault held only in memory round-trips through `to_bytes()`/`from_bytes()`
+/// without ever touching the filesystem.
+#[test]
+fn in_memory_vault() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+
+    match sman.save() {
+        Err(Error::InMemoryVault) => {}
+        _ => panic!("Saving an in-memory vault did not return InMemoryVault!"),
+    }
+
+    let bytes = sman.to_bytes().unwrap();
+    let sman2 = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("bar", sman2.retrieve::<String>("foo").unwrap());
+
+    // The serialized vault is protected just like one on-disk
+    match SecretsManager::from_bytes(&bytes, KeySource::Password("wrong")) {
+        Err(Error::InvalidKey) => {}
+        _ => panic!("Loading an in-memory vault with the wrong password did not fail!"),
+    }
+}
+
+/// Verify that a vault can be written to and read back from arbitrary streams, such as a
+/// section of a larger file.
+#[test]
+fn stream_load_save() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+
+    let mut stream = Vec::new();
+    sman.save_to_writer(&mut stream).unwrap();
+    assert_eq!(sman.to_bytes().unwrap(), stream);
+
+    let sman =
+        SecretsManager::load_from_reader(&stream[..], KeySource::Password("mysecret")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // A vault read from a stream is held only in memory
+    match sman.save() {
+        Err(Error::InMemoryVault) => {}
+        _ => panic!("Saving a vault read from a stream did not return InMemoryVault!"),
+    }
+}
+
+/// Verify that a vault opened read-only exposes its secrets and leaves the vault untouched.
+#[test]
+fn readonly_vault() {
+    let path = "./readonly.json";
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let contents = std::fs::read(path).unwrap();
+
+    let readonly = SecretsManager::load_readonly(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("bar", readonly.retrieve::<String>("foo").unwrap());
+    assert_eq!(
+        "bar",
+        readonly.retrieve_secret("foo").unwrap().expose_secret()
+    );
+    assert_eq!(vec!["foo"], readonly.keys().collect::<Vec<_>>());
+    assert_eq!(1, readonly.len());
+    match readonly.retrieve::<String>("missing") {
+        Err(Error::SecretNotFound(_)) => {}
+        _ => panic!("Retrieving a missing secret did not return SecretNotFound!"),
+    }
+
+    drop(readonly);
+    assert_eq!(contents, std::fs::read(path).unwrap());
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that saving only writes the vault when it has changed.
+#[test]
+fn dirty_tracking() {
+    let path = "./dirty.json";
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    assert!(sman.is_dirty());
+    sman.save().unwrap();
+    assert!(!sman.is_dirty());
+
+    // Were the vault rewritten, it would reappear
+    std::fs::remove_file(path).unwrap();
+    sman.save().unwrap();
+    assert!(!std::path::Path::new(path).exists());
+
+    sman.set("foo", "bar");
+    assert!(sman.is_dirty());
+    sman.save().unwrap();
+    assert!(!sman.is_dirty());
+
+    let mut sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert!(!sman.is_dirty());
+    assert!(sman.remove("missing").is_err());
+    assert!(!sman.is_dirty());
+    sman.remove("foo").unwrap();
+    assert!(sman.is_dirty());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that pending changes are saved on drop when autosave is enabled, and that failures
+/// to save panic when so configured.
+#[test]
+fn autosave_on_drop() {
+    use crate::OnSaveFailure;
+
+    let path = "./autosave.json";
+    {
+        let mut sman = SecretsManager::new(path, KeySource::Password("mysecret"))
+            .unwrap()
+            .with_autosave(OnSaveFailure::Log);
+        sman.set("foo", "bar");
+    }
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    std::fs::remove_file(path).unwrap();
+
+    // An in-memory vault cannot be saved
+    let result = std::panic::catch_unwind(|| {
+        let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret"))
+            .unwrap()
+            .with_autosave(OnSaveFailure::Panic);
+        sman.set("foo", "bar");
+    });
+    assert!(result.is_err());
+}
+
+/// Verify that the builder opens existing vaults with the options set on it.
+#[test]
+fn builder_open() {
+    use crate::{FileStorage, OnSaveFailure};
+
+    let path = "./builder_open.json";
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let mut sman = SecretsManager::builder()
+        .path(path)
+        .key_source(KeySource::Password("mysecret"))
+        .readonly(true)
+        .locked(true)
+        .open()
+        .unwrap();
+    assert!(sman.is_locked());
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    sman.set("foo", "baz");
+    match sman.save() {
+        Err(Error::ReadOnlyVault) => {}
+        _ => panic!("Saving a read-only vault did not return ReadOnlyVault!"),
+    }
+    drop(sman);
+
+    {
+        let mut sman = SecretsManager::builder()
+            .storage(FileStorage::new(path))
+            .key_source(KeySource::Password("mysecret"))
+            .autosave(Some(OnSaveFailure::Panic))
+            .open()
+            .unwrap();
+        sman.set("foo", "qux");
+    }
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!("qux", sman.retrieve::<String>("foo").unwrap());
+
+    match SecretsManager::builder().path(path).open() {
+        Err(Error::IncompleteBuilder("key_source")) => {}
+        _ => panic!("Opening a vault without a key source did not fail!"),
+    }
+    match SecretsManager::builder()
+        .key_source(KeySource::Password("mysecret"))
+        .open()
+    {
+        Err(Error::IncompleteBuilder("path")) => {}
+        _ => panic!("Opening a vault without a path did not fail!"),
+    }
+    match SecretsManager::builder()
+        .path(path)
+        .storage(FileStorage::new("./builder_other.json"))
+        .key_source(KeySource::Password("mysecret"))
+        .open()
+    {
+        Err(Error::ConflictingBuilderOptions("path", "storage")) => {}
+        _ => panic!("Opening a vault with both a path and a storage did not fail!"),
+    }
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file("./.builder_open.json.lock").unwrap();
+}
+
+/// Verify that vaults are serialized deterministically, so that adding a secret to a vault under
+/// version control only adds the lines of that secret (and changes the MAC).
+#[test]
+fn deterministic_serialization() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("zulu", "1");
+    sman.set("alpha", "2");
+    let before = String::from_utf8(sman.to_bytes().unwrap()).unwrap();
+    assert!(before.ends_with("}\n"));
+    assert!(before.find("\"alpha\"").unwrap() < before.find("\"zulu\"").unwrap());
+    assert_eq!(before, String::from_utf8(sman.to_bytes().unwrap()).unwrap());
+
+    sman.set("mike", "3");
+    let after = String::from_utf8(sman.to_bytes().unwrap()).unwrap();
+    let is_mac = |line: &&str| line.trim_start().starts_with("\"mac\"");
+    let mut added = after.lines().filter(|l| !is_mac(l));
+    for line in before.lines().filter(|l| !is_mac(l)) {
+        assert!(
+            added.any(|l| l == line),
+            "Line {:?} changed when adding a secret",
+            line
+        );
+    }
+}
+
+/// Verify that comparing two copies of a vault reports added, removed and changed secrets, but
+/// not secrets that were only re-encrypted.
+#[test]
+fn vault_diff() {
+    let mut ours = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    ours.set("same", "value");
+    ours.set("changed", "old");
+    ours.set("removed", "value");
+    ours.set("reencrypted", "value");
+    let bytes = ours.to_bytes().unwrap();
+
+    let mut theirs = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    assert!(ours.diff(&theirs).unwrap().is_empty());
+
+    theirs.set("changed", "new");
+    theirs.remove("removed").unwrap();
+    theirs.set("added", "value");
+    theirs.set("reencrypted", "value");
+    let diff = ours.diff(&theirs).unwrap();
+    assert_eq!(vec!["added"], diff.added);
+    assert_eq!(vec!["removed"], diff.removed);
+    assert_eq!(vec!["changed"], diff.changed);
+
+    // Vaults protected with different keys can also be compared
+    theirs.rotate_keys(KeySource::Password("other")).unwrap();
+    assert_eq!(diff, ours.diff(&theirs).unwrap());
+}
+
+/// Verify that divergent copies of a vault are merged according to the strategy chosen.
+#[test]
+fn vault_merge() {
+    use crate::MergeStrategy;
+
+    let mut base = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    base.set("shared", "base");
+    let bytes = base.to_bytes().unwrap();
+
+    let load = || SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    let (mut ours, mut theirs) = (load(), load());
+    ours.set("ours", "1");
+    theirs.set("theirs", "2");
+    theirs.rotate_keys(KeySource::Password("other")).unwrap();
+
+    let mut merged = load();
+    merged.merge_from(&ours, MergeStrategy::Union).unwrap();
+    merged.merge_from(&theirs, MergeStrategy::Union).unwrap();
+    assert_eq!(
+        vec!["ours", "shared", "theirs"],
+        merged.keys().collect::<Vec<_>>()
+    );
+    assert_eq!("2", merged.retrieve::<String>("theirs").unwrap());
+
+    ours.set("shared", "ours");
+    theirs.set("shared", "theirs");
+    let before = ours.to_bytes().unwrap();
+    match ours.merge_from(&theirs, MergeStrategy::Union) {
+        Err(Error::MergeConflict(names)) => assert_eq!(vec!["shared"], names),
+        _ => panic!("Merging conflicting secrets did not return MergeConflict!"),
+    }
+    assert_eq!(before, ours.to_bytes().unwrap());
+
+    ours.merge_from(&theirs, MergeStrategy::Ours).unwrap();
+    assert_eq!("ours", ours.retrieve::<String>("shared").unwrap());
+    assert_eq!("2", ours.retrieve::<String>("theirs").unwrap());
+    ours.merge_from(&theirs, MergeStrategy::Theirs).unwrap();
+    assert_eq!("theirs", ours.retrieve::<String>("shared").unwrap());
+    assert_eq!("1", ours.retrieve::<String>("ours").unwrap());
+}
+
+/// Verify that a three-way merge takes the changes made on either side since the common
+/// ancestor, and reports secrets changed differently on both.
+#[test]
+fn vault_merge_three_way() {
+    let mut base = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    base.set("unchanged", "base");
+    base.set("ours", "base");
+    base.set("theirs", "base");
+    base.set("removed", "base");
+    let bytes = base.to_bytes().unwrap();
+
+    let load = || SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    let (mut ours, mut theirs) = (load(), load());
+    ours.set("ours", "ours");
+    ours.set("added", "same");
+    theirs.set("theirs", "theirs");
+    theirs.set("added", "same");
+    theirs.remove("removed").unwrap();
+
+    ours.merge_three_way(&base, &theirs).unwrap();
+    assert_eq!(
+        vec!["added", "ours", "theirs", "unchanged"],
+        ours.keys().collect::<Vec<_>>()
+    );
+    assert_eq!("ours", ours.retrieve::<String>("ours").unwrap());
+    assert_eq!("theirs", ours.retrieve::<String>("theirs").unwrap());
+
+    let mut ours = load();
+    ours.set("ours", "ours");
+    theirs.set("ours", "theirs");
+    theirs.remove("unchanged").unwrap();
+    ours.set("unchanged", "ours");
+    match ours.merge_three_way(&base, &theirs) {
+        Err(Error::MergeConflict(names)) => assert_eq!(vec!["ours", "unchanged"], names),
+        _ => panic!("Conflicting changes did not return MergeConflict!"),
+    }
+    assert!(ours.retrieve::<String>("removed").is_ok());
+}
+
+/// Verify that secrets are exported to `.env` files with their names transformed and their
+/// values quoted as needed.
+#[test]
+fn export_dotenv() {
+    let path = "./export_dotenv.env";
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("db/password", "hunter2");
+    sman.set("greeting", "hello world");
+    sman.set("multiline", "it's\n\"$HOME\"");
+
+    sman.export_dotenv(path, |name| name.to_uppercase().replace('/', "_"))
+        .unwrap();
+    let contents = std::fs::read_to_string(path).unwrap();
+    assert_eq!(
+        "DB_PASSWORD=hunter2\nGREETING='hello world'\nMULTILINE=\"it's\\n\\\"\\$HOME\\\"\"\n",
+        contents
+    );
+
+    // the plaintext is only readable by its owner, even when replacing a readable file
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = |path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(0o600, mode(path));
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        sman.export_dotenv(path, str::to_string).unwrap();
+        assert_eq!(0o600, mode(path));
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that `.env` files are parsed with comments and quoting, and round-trip with
+/// `export_dotenv()`.
+#[test]
+fn import_dotenv() {
+    let path = "./import_dotenv.env";
+    std::fs::write(
+        path,
+        "# database settings\n\
+         DB_HOST=localhost # inline comment\n\
+         export DB_USER = admin\n\
+         DB_PASS='p#ss \"word\"'\n\
+         \n\
+         CERT=\"line one\\nline \\\"two\\\"\n\
+         line three\"\n\
+         EMPTY=\n",
+    )
+    .unwrap();
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.import_dotenv(path).unwrap();
+    assert_eq!(
+        vec!["CERT", "DB_HOST", "DB_PASS", "DB_USER", "EMPTY"],
+        sman.keys().collect::<Vec<_>>()
+    );
+    assert_eq!("localhost", sman.retrieve::<String>("DB_HOST").unwrap());
+    assert_eq!("admin", sman.retrieve::<String>("DB_USER").unwrap());
+    assert_eq!("p#ss \"word\"", sman.retrieve::<String>("DB_PASS").unwrap());
+    assert_eq!(
+        "line one\nline \"two\"\nline three",
+        sman.retrieve::<String>("CERT").unwrap()
+    );
+    assert_eq!("", sman.retrieve::<String>("EMPTY").unwrap());
+
+    sman.set("tricky", "it's\n\"$HOME\" \\");
+    sman.export_dotenv(path, str::to_string).unwrap();
+    let mut imported = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    imported.import_dotenv(path).unwrap();
+    assert!(sman.diff(&imported).unwrap().is_empty());
+
+    std::fs::write(path, "GOOD=1\nBAD='unterminated\n").unwrap();
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    match sman.import_dotenv(path) {
+        Err(Error::MalformedImport { line: 2, .. }) => {}
+        r => panic!(
+            "Importing a malformed .env file did not fail: {:?}",
+            r.err()
+        ),
+    }
+    assert!(sman.is_empty());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that all secrets, including binary secrets, survive a plaintext JSON export and
+/// import into a vault with different keys and scheme.
+#[test]
+fn plaintext_export_import() {
+    use crate::Scheme;
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("text", "hello \"world\"");
+    sman.set_bytes("binary", &[0xff, 0x00, 0xfe]);
+
+    let mut json = Vec::new();
+    sman.export_plaintext(&mut json).unwrap();
+    let parsed: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!("hello \"world\"", parsed["text"]);
+    assert_eq!("/wD+", parsed["binary"]["base64"]);
+
+    let mut imported = SecretsManager::new_in_memory(KeySource::Generate).unwrap();
+    imported.upgrade_scheme(Scheme::Aes256Gcm).unwrap();
+    imported.import_plaintext(&json[..]).unwrap();
+    assert!(sman.diff(&imported).unwrap().is_empty());
+
+    match imported.import_plaintext(&b"{\"truncated\": "[..]) {
+        Err(Error::Serde(_)) => {}
+        _ => panic!("Importing malformed JSON did not fail!"),
+    }
+}
+
+/// Verify that secrets round-trip through CSV, and that CSV with other columns can be imported.
+#[cfg(feature = "csv")]
+#[test]
+fn csv_export_import() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("db/password", "hunter2");
+    sman.set("motd", "hello, \"world\"\nline two");
+
+    let mut csv = Vec::new();
+    sman.export_csv(&mut csv, "key", "secret").unwrap();
+    assert!(csv.starts_with(b"key,secret\ndb/password,hunter2\n"));
+
+    let mut imported = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    imported.import_csv(&csv[..], "key", "secret").unwrap();
+    assert!(sman.diff(&imported).unwrap().is_empty());
+
+    let inventory = "owner,value,name\nalice,s3cr3t,api/token\nbob,,empty\n";
+    let mut imported = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    imported
+        .import_csv(inventory.as_bytes(), "name", "value")
+        .unwrap();
+    assert_eq!("s3cr3t", imported.retrieve::<String>("api/token").unwrap());
+    assert_eq!("", imported.retrieve::<String>("empty").unwrap());
+
+    match imported.import_csv(inventory.as_bytes(), "key", "value") {
+        Err(Error::MalformedImport { line: 1, .. }) => {}
+        _ => panic!("Importing CSV without the key column did not fail!"),
+    }
+    match imported.import_csv(&b"name,value\nshort\n"[..], "name", "value") {
+        Err(Error::MalformedImport { line: 2, .. }) => {}
+        r => panic!("Importing a short row did not fail: {:?}", r.err()),
+    }
+}
+
+/// Verify that vaults created as YAML are saved as YAML, detected when loaded and can be
+/// converted to and from JSON.
+#[cfg(feature = "yaml")]
+#[test]
+fn yaml_format() {
+    use crate::VaultFormat;
+
+    let path = "./yaml_format.yaml";
+    let mut sman = SecretsManager::builder()
+        .format(VaultFormat::Yaml)
+        .create(path, KeySource::Password("mysecret"))
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let contents = std::fs::read_to_string(path).unwrap();
+    assert!(contents.starts_with("version: "));
+    assert!(contents.contains("secrets:\n  foo:\n"));
+
+    let mut sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(VaultFormat::Yaml, sman.format());
+    assert!(!sman.is_dirty());
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    sman.set_format(VaultFormat::Json);
+    sman.save().unwrap();
+    let sman = SecretsManager::load(path, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(VaultFormat::Json, sman.format());
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::write(path, "version: [").unwrap();
+    match SecretsManager::load(path, KeySource::Password("mysecret")) {
+        Err(Error::MalformedVaultFormat(_)) => {}
+        r => panic!("Loading malformed YAML did not fail: {:?}", r.err()),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that vaults created as CBOR hold binary data unencoded, are detected when loaded and
+/// can be converted to and from JSON.
+#[cfg(feature = "cbor")]
+#[test]
+fn cbor_format() {
+    use crate::VaultFormat;
+
+    let mut sman = SecretsManager::builder()
+        .format(VaultFormat::Cbor)
+        .create_in_memory(KeySource::Password("mysecret"))
+        .unwrap();
+    sman.set_bytes("blob", &[0xa5; 3000]);
+    let cbor = sman.to_bytes().unwrap();
+    assert!(cbor.starts_with(&[0xd9, 0xd9, 0xf7]));
+
+    sman.set_format(VaultFormat::Json);
+    let json = sman.to_bytes().unwrap();
+    assert!(cbor.len() * 4 < json.len() * 3);
+
+    let mut sman = SecretsManager::from_bytes(&cbor, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(VaultFormat::Cbor, sman.format());
+    assert_eq!(vec![0xa5; 3000], sman.retrieve_bytes("blob").unwrap());
+    sman.set_format(VaultFormat::Json);
+    assert_eq!(json.len(), sman.to_bytes().unwrap().len());
+
+    match SecretsManager::from_bytes(&cbor[..100], KeySource::Password("mysecret")) {
+        Err(Error::MalformedVaultFormat(_)) => {}
+        r => panic!("Loading truncated CBOR did not fail: {:?}", r.err()),
+    }
+}
+
+/// Verify that secrets above the compression threshold are compressed, and that compressed
+/// secrets are decompressed transparently, including after the keys are rotated.
+#[cfg(feature = "zstd")]
+#[test]
+fn compressed_secrets() {
+    let json = format!(
+        "{{\"accounts\": [{}]}}",
+        vec!["\"service\""; 1000].join(", ")
+    );
+    let mut sman = SecretsManager::builder()
+        .compression_threshold(Some(1024))
+        .create_in_memory(KeySource::Password("mysecret"))
+        .unwrap();
+    sman.set("small", "tiny");
+    sman.set("large", json.as_str());
+
+    assert!(sman.vault.secrets["small"].compression.is_none());
+    assert!(sman.vault.secrets["large"].compression.is_some());
+    assert!(sman.vault.secrets["large"].payload.len() < json.len() / 10);
+
+    let bytes = sman.to_bytes().unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(json, sman.retrieve::<String>("large").unwrap());
+
+    // The threshold is not persisted, so secrets set after loading are stored uncompressed,
+    // while those already compressed remain readable
+    sman.set("large2", json.as_str());
+    assert!(sman.vault.secrets["large2"].compression.is_none());
+    sman.set_compression_threshold(Some(1024));
+    sman.rotate_keys(KeySource::Password("other")).unwrap();
+    assert!(sman.vault.secrets["large2"].compression.is_some());
+    assert_eq!(json, sman.retrieve::<String>("large2").unwrap());
+}
+
+/// Verify that secrets set from a reader are encrypted in chunks, and can be retrieved either
+/// whole or streamed to a writer, including after the keys are rotated.
+#[test]
+fn streamed_secrets() {
+    let data: Vec<u8> = (0..(3 * crate::stream::CHUNK_SIZE + 1000))
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set_from_reader("large", &data[..]).unwrap();
+    sman.set_from_reader("empty", std::io::empty()).unwrap();
+    assert_eq!(4, sman.vault.secrets["large"].chunks.len());
+    assert!(sman.vault.secrets["empty"].chunks.is_empty());
+
+    let mut retrieved = Vec::new();
+    sman.retrieve_to_writer("large", &mut retrieved).unwrap();
+    assert_eq!(data, retrieved);
+    assert_eq!(data, sman.retrieve_bytes("large").unwrap());
+    assert_eq!(Vec::<u8>::new(), sman.retrieve_bytes("empty").unwrap());
+
+    // Secrets set whole can be streamed out too
+    sman.set("small", "value");
+    let mut retrieved = Vec::new();
+    sman.retrieve_to_writer("small", &mut retrieved).unwrap();
+    assert_eq!(b"value", &retrieved[..]);
+    assert!(matches!(
+        sman.retrieve_to_writer("missing", Vec::new()),
+        Err(Error::SecretNotFound(_))
+    ));
+
+    sman.rotate_keys(KeySource::Password("other")).unwrap();
+    let bytes = sman.to_bytes().unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("other")).unwrap();
+    assert_eq!(4, sman.vault.secrets["large"].chunks.len());
+    assert_eq!(data, sman.retrieve_bytes("large").unwrap());
+
+    // Dropping a chunk is detected
+    sman.vault.secrets.get_mut("large").unwrap().chunks.pop();
+    assert!(matches!(
+        sman.retrieve_bytes("large"),
+        Err(Error::DecryptionFailure)
+    ));
+}
+
+/// Verify that files stored in the vault are restored with their original name and permissions.
+#[cfg(unix)]
+#[test]
+fn file_attachments() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("securestore-files-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("out")).unwrap();
+    let source = dir.join("bundle.pem");
+    std::fs::write(&source, "-----BEGIN CERTIFICATE-----\n").unwrap();
+    std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set_file("tls/bundle", &source).unwrap();
+    let bytes = sman.to_bytes().unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    let metadata = sman.file_metadata("tls/bundle").unwrap();
+    assert_eq!("bundle.pem", metadata.filename);
+    assert_eq!(Some(0o640), metadata.mode);
+
+    // Restored into a directory under the original name and permissions
+    let restored = sman
+        .retrieve_to_file("tls/bundle", dir.join("out"), None)
+        .unwrap();
+    assert_eq!(dir.join("out").join("bundle.pem"), restored);
+    assert_eq!(
+        std::fs::read(&source).unwrap(),
+        std::fs::read(&restored).unwrap()
+    );
+    let mode = std::fs::metadata(&restored).unwrap().permissions().mode();
+    assert_eq!(0o640, mode & 0o7777);
+
+    // Or to an explicit path and mode
+    let restored = sman
+        .retrieve_to_file("tls/bundle", dir.join("copy.pem"), Some(0o400))
+        .unwrap();
+    let mode = std::fs::metadata(&restored).unwrap().permissions().mode();
+    assert_eq!(0o400, mode & 0o7777);
+
+    // Secrets not set from files are written privately under their own name
+    sman.set("token", "value");
+    let restored = sman
+        .retrieve_to_file("token", dir.join("out"), None)
+        .unwrap();
+    assert_eq!(dir.join("out").join("token"), restored);
+    let mode = std::fs::metadata(&restored).unwrap().permissions().mode();
+    assert_eq!(0o600, mode & 0o7777);
+
+    // Replacing the secret discards the file details
+    sman.set("tls/bundle", "replaced");
+    assert!(sman.file_metadata("tls/bundle").is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that the creation and modification times of secrets are recorded, persisted and
+/// covered by the vault MAC.
+#[test]
+fn secret_timestamps() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    let before = SystemTime::now() - Duration::from_secs(1);
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    let metadata = sman.metadata("foo").unwrap().clone();
+    assert!(metadata.created >= before && metadata.created <= SystemTime::now());
+    assert_eq!(metadata.created, metadata.modified);
+    assert!(sman.metadata("missing").is_none());
+
+    // Backdate the secret, then check that setting it again only updates the modified time
+    let past = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+    sman.vault.metadata.get_mut("foo").unwrap().created = past;
+    sman.vault.metadata.get_mut("foo").unwrap().modified = past;
+    sman.set("foo", "baz");
+    let metadata = sman.metadata("foo").unwrap().clone();
+    assert_eq!(past, metadata.created);
+    assert!(metadata.modified >= before);
+
+    let bytes = sman.to_bytes().unwrap();
+    let json = String::from_utf8(bytes.clone()).unwrap();
+    assert!(json.contains("\"created\": 1500000000"));
+    let sman = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(Some(&metadata), sman.metadata("foo"));
+
+    let tampered = json.replace("1500000000", "1600000000");
+    assert!(matches!(
+        SecretsManager::from_bytes(tampered.as_bytes(), KeySource::Password("mysecret")),
+        Err(Error::VaultTampered)
+    ));
+
+    let mut sman = sman;
+    sman.remove("foo").unwrap();
+    assert!(sman.metadata("foo").is_none());
+}
+
+/// Verify that descriptions are stored in the clear or encrypted as requested, survive changes
+/// to the secret and rotation of the keys, and are removed with the secret.
+#[test]
+fn secret_descriptions() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set_with_description("db/password", "hunter2", "Primary database, rotated yearly");
+    sman.set("api/token", "abc123");
+    sman.set_encrypted_description("api/token", "Issued by the payments vendor")
+        .unwrap();
+    assert!(matches!(
+        sman.set_description("missing", Some("nothing")),
+        Err(Error::SecretNotFound(_))
+    ));
+
+    let json = String::from_utf8(sman.to_bytes().unwrap()).unwrap();
+    assert!(json.contains("Primary database, rotated yearly"));
+    assert!(!json.contains("payments vendor"));
+
+    let mut sman =
+        SecretsManager::from_bytes(json.as_bytes(), KeySource::Password("mysecret")).unwrap();
+    sman.set("db/password", "correct horse");
+    assert_eq!(
+        Some("Primary database, rotated yearly"),
+        sman.description("db/password").unwrap().as_deref()
+    );
+    sman.rotate_keys(KeySource::Password("other")).unwrap();
+    assert_eq!(
+        Some("Issued by the payments vendor"),
+        sman.description("api/token").unwrap().as_deref()
+    );
+
+    sman.set_description("db/password", None).unwrap();
+    assert_eq!(None, sman.description("db/password").unwrap());
+    sman.remove("api/token").unwrap();
+    assert_eq!(None, sman.description("api/token").unwrap());
+}
+
+/// Verify that secrets can be found by their tags, which are persisted and covered by the MAC.
+#[test]
+fn secret_tags() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("aws/key", "AKIA");
+    sman.set("aws/staging", "AKIB");
+    sman.set("db/password", "hunter2");
+    sman.set_tags("aws/key", &["prod", "aws"]).unwrap();
+    sman.set_tags("aws/staging", &["aws"]).unwrap();
+    sman.set_tags("db/password", &["prod", "database"]).unwrap();
+    assert!(matches!(
+        sman.set_tags("missing", &["prod"]),
+        Err(Error::SecretNotFound(_))
+    ));
+
+    let bytes = sman.to_bytes().unwrap();
+    let json = String::from_utf8(bytes.clone()).unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    assert_eq!(
+        vec!["aws/key", "aws/staging"],
+        sman.keys_with_tag("aws").collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["aws/key", "db/password"],
+        sman.keys_with_tag("prod").collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["aws", "prod"],
+        sman.tags("aws/key").collect::<Vec<_>>()
+    );
+
+    let tampered = json.replacen("\"prod\"", "\"dev\"", 1);
+    assert!(matches!(
+        SecretsManager::from_bytes(tampered.as_bytes(), KeySource::Password("mysecret")),
+        Err(Error::VaultTampered)
+    ));
+
+    sman.set_tags::<&str>("aws/staging", &[]).unwrap();
+    sman.remove("db/password").unwrap();
+    assert_eq!(
+        vec!["aws/key"],
+        sman.keys_with_tag("aws").collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["aws/key"],
+        sman.keys_with_tag("prod").collect::<Vec<_>>()
+    );
+    assert_eq!(0, sman.tags("db/password").count());
+}
+
+/// Verify that secrets can be listed, removed and accessed by namespace.
+#[test]
+fn namespaces() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("prod/db/password", "p1");
+    sman.set("prod/db/user", "admin");
+    sman.set("prod/api", "p2");
+    sman.set("production/api", "p3");
+    sman.set("staging/db/password", "s1");
+    sman.set("staging/api", "s2");
+
+    assert_eq!(
+        vec!["prod/db/password", "prod/db/user"],
+        sman.keys_under("prod/db/").collect::<Vec<_>>()
+    );
+    assert_eq!(3, sman.keys_under("prod").count());
+    assert_eq!(6, sman.keys_under("").count());
+
+    {
+        let mut prod = sman.scoped("prod");
+        assert_eq!(
+            vec!["api", "db/password", "db/user"],
+            prod.keys().collect::<Vec<_>>()
+        );
+        assert_eq!("p2", prod.retrieve::<String>("api").unwrap());
+        prod.set("cache", "p4");
+        let mut db = prod.scoped("db/");
+        assert_eq!("prod/db/", db.prefix());
+        assert_eq!("admin", db.retrieve::<String>("user").unwrap());
+        db.remove("user").unwrap();
+    }
+    assert_eq!("p4", sman.retrieve::<String>("prod/cache").unwrap());
+    assert!(!sman.keys().any(|name| name == "prod/db/user"));
+
+    assert_eq!(2, sman.remove_prefix("staging/"));
+    assert_eq!(
+        vec![
+            "prod/api",
+            "prod/cache",
+            "prod/db/password",
+            "production/api"
+        ],
+        sman.keys().collect::<Vec<_>>()
+    );
+}
+
+/// Verify that secrets can be found with glob patterns.
+#[test]
+fn find_secrets() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    for name in &[
+        "password",
+        "db/prod/password",
+        "db/prod/user",
+        "db/staging/password",
+        "db/staging/replica/password",
+        "api1",
+        "api2",
+        "apiX",
+    ] {
+        sman.set(name, "value");
+    }
+
+    let find = |pattern| sman.find(pattern).unwrap();
+    assert_eq!(
+        vec!["db/prod/password", "db/staging/password"],
+        find("db/*/password")
+    );
+    assert_eq!(
+        vec![
+            "db/prod/password",
+            "db/staging/password",
+            "db/staging/replica/password",
+            "password"
+        ],
+        find("**/password")
+    );
+    assert_eq!(vec!["db/staging/replica/password"], find("db/**/replica/*"));
+    assert_eq!(vec!["api1", "api2", "apiX"], find("api?"));
+    assert_eq!(vec!["api1", "api2"], find("api[0-9]"));
+    assert_eq!(vec!["apiX"], find("api[!0-9]"));
+    assert_eq!(Vec::<&str>::new(), find("db/*"));
+    assert_eq!(vec!["password"], find("password"));
+    assert!(matches!(
+        sman.find("api[0-9"),
+        Err(Error::InvalidPattern(_))
+    ));
+
+    #[cfg(feature = "regex")]
+    {
+        assert_eq!(vec!["api1", "api2"], sman.find_regex(r"^api\d$").unwrap());
+        assert!(matches!(
+            sman.find_regex("("),
+            Err(Error::InvalidPattern(_))
+        ));
+    }
+
+    // patterns with many stars which fail to match do not take exponential time
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set(&"a".repeat(100), "value");
+    for pattern in &[
+        "*a".repeat(30) + "b",
+        "**a".repeat(30) + "b",
+        "**/a".repeat(30),
+    ] {
+        assert!(sman.find(pattern).unwrap().is_empty());
+    }
+    assert_eq!(1, sman.find(&"*a".repeat(30)).unwrap().len());
+}
+
+/// Verify that secrets are only generated if they do not already exist.
+#[test]
+fn retrieve_or_insert_with() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("existing", "old");
+
+    let value: String = sman
+        .retrieve_or_insert_with("existing", || panic!("Existing secret was regenerated!"))
+        .unwrap();
+    assert_eq!("old", value);
+
+    let generated: Vec<u8> = sman
+        .retrieve_or_insert_with("signing-key", || vec![7u8; 32])
+        .unwrap();
+    assert_eq!(vec![7u8; 32], generated);
+    assert_eq!(generated, sman.retrieve_bytes("signing-key").unwrap());
+    let again: Vec<u8> = sman
+        .retrieve_or_insert_with("signing-key", || vec![0u8; 32])
+        .unwrap();
+    assert_eq!(generated, again);
+}
+
+/// Verify that conditional updates only create or only replace secrets.
+#[test]
+fn conditional_updates() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    assert!(sman.set_if_absent("db/password", "first"));
+    assert!(!sman.set_if_absent("db/password", "second"));
+    assert_eq!("first", sman.retrieve::<String>("db/password").unwrap());
+
+    let previous: String = sman.replace("db/password", "third").unwrap();
+    assert_eq!("first", previous);
+    assert_eq!("third", sman.retrieve::<String>("db/password").unwrap());
+    assert!(matches!(
+        sman.replace::<_, String>("missing", "value"),
+        Err(Error::SecretNotFound(_))
+    ));
+    assert_eq!(1, sman.len());
+}
+
+/// Verify that renaming a secret moves its ciphertext and metadata, but never overwrites
+/// another secret.
+#[test]
+fn rename_secret() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set_with_description("old", "value", "A secret");
+    sman.set_tags("old", &["prod"]).unwrap();
+    sman.set("other", "other value");
+    let payload = sman.vault.secrets["old"].payload.clone();
+    let metadata = sman.metadata("old").cloned();
+
+    assert!(matches!(
+        sman.rename("old", "other"),
+        Err(Error::SecretExists(_))
+    ));
+    assert!(matches!(
+        sman.rename("missing", "new"),
+        Err(Error::SecretNotFound(_))
+    ));
+
+    sman.rename("old", "new").unwrap();
+    assert_eq!(payload, sman.vault.secrets["new"].payload);
+    assert_eq!("value", sman.retrieve::<String>("new").unwrap());
+    assert_eq!(metadata.as_ref(), sman.metadata("new"));
+    assert_eq!(
+        Some("A secret".to_string()),
+        sman.description("new").unwrap()
+    );
+    assert_eq!(vec!["new"], sman.keys_with_tag("prod").collect::<Vec<_>>());
+    assert_eq!(vec!["new", "other"], sman.keys().collect::<Vec<_>>());
+    assert!(sman.metadata("old").is_none());
+}
+
+/// Verify that iterating over the store decrypts every secret, reporting failures per secret.
+#[test]
+fn iterate_secrets() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("b", "two");
+    sman.set("a", "one");
+    sman.set("c", "three");
+    sman.vault.secrets.get_mut("c").unwrap().payload[0] ^= 1;
+
+    let secrets: Vec<_> = sman.iter().collect();
+    assert_eq!(3, secrets.len());
+    assert_eq!("a", secrets[0].0);
+    assert_eq!("one", secrets[0].1.as_ref().unwrap());
+    assert_eq!("two", secrets[1].1.as_ref().unwrap());
+    assert!(matches!(secrets[2], (ref name, Err(Error::MacMismatch)) if name == "c"));
+}
+
+/// Verify that all secrets can be decrypted at once.
+#[test]
+fn retrieve_all() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("db/user", "admin");
+    sman.set("db/password", "hunter2");
+    sman.set_bytes("key", &[0, 1, 2]);
+
+    let bytes = sman.retrieve_all_bytes().unwrap();
+    assert_eq!(3, bytes.len());
+    assert_eq!(vec![0, 1, 2], bytes["key"]);
+    sman.remove("key").unwrap();
+    let secrets = sman.retrieve_all().unwrap();
+    assert_eq!(2, secrets.len());
+    assert_eq!("hunter2", secrets["db/password"]);
+
+    sman.vault.secrets.get_mut("db/user").unwrap().payload[0] ^= 1;
+    assert!(matches!(sman.retrieve_all(), Err(Error::MacMismatch)));
+}
+
+/// Verify that previous values of secrets are kept when history is enabled, and can be
+/// retrieved and rolled back to, including after the keys are rotated.
+#[test]
+fn secret_history() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("token", "v1");
+    sman.set("token", "v2");
+    assert!(sman.history("token").is_empty());
+
+    sman.set_history_depth(2);
+    sman.set("token", "v3");
+    sman.set("token", "v4");
+    sman.set("token", "v5");
+    assert_eq!(2, sman.history("token").len());
+    assert_eq!("v4", sman.retrieve_version::<String>("token", 0).unwrap());
+    assert_eq!("v3", sman.retrieve_version::<String>("token", 1).unwrap());
+    assert!(matches!(
+        sman.retrieve_version::<String>("token", 2),
+        Err(Error::VersionNotFound { version: 2, .. })
+    ));
+
+    sman.rotate_keys(KeySource::Password("other")).unwrap();
+    let bytes = sman.to_bytes().unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("other")).unwrap();
+    assert_eq!(2, sman.history_depth());
+    assert_eq!("v3", sman.retrieve_version::<String>("token", 1).unwrap());
+
+    sman.rollback("token").unwrap();
+    assert_eq!("v4", sman.retrieve::<String>("token").unwrap());
+    sman.rollback("token").unwrap();
+    assert_eq!("v3", sman.retrieve::<String>("token").unwrap());
+    assert!(matches!(
+        sman.rollback("token"),
+        Err(Error::VersionNotFound { .. })
+    ));
+
+    sman.set("token", "v6");
+    sman.set_history_depth(0);
+    assert!(sman.history("token").is_empty());
+}
+
+/// Verify that rolling back a secret restores the expiration and file details of its previous
+/// value.
+#[test]
+fn rollback_expiry() {
+    use std::time::{Duration, SystemTime};
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set_history_depth(1);
+    let past = SystemTime::now() - Duration::from_secs(60);
+    let future = SystemTime::now() + Duration::from_secs(3600);
+
+    sman.set_with_expiry("token", "expired", past);
+    sman.set("token", "current");
+    assert!(sman.expiry("token").is_none());
+    sman.rollback("token").unwrap();
+    assert!(sman.expiry("token").is_some());
+    assert!(matches!(
+        sman.retrieve::<String>("token"),
+        Err(Error::SecretExpired(_))
+    ));
+
+    let path = std::env::temp_dir().join(format!("securestore-rollback-{}", std::process::id()));
+    std::fs::write(&path, "contents").unwrap();
+    sman.set_file("cert", &path).unwrap();
+    sman.set_with_expiry("cert", "replacement", future);
+    assert!(sman.file_metadata("cert").is_none());
+    // the rollback survives a round trip through the serialized vault
+    let bytes = sman.to_bytes().unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("mysecret")).unwrap();
+    sman.rollback("cert").unwrap();
+    assert!(sman.expiry("cert").is_none());
+    assert!(sman.file_metadata("cert").is_some());
+    assert_eq!("contents", sman.retrieve::<String>("cert").unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Verify that soft-removed secrets are hidden but can be restored, including after the keys
+/// are rotated, until they are purged.
+#[test]
+fn soft_remove() {
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("prod/db", "hunter2");
+    sman.set_tags("prod/db", &["prod"]).unwrap();
+    sman.set("prod/api", "token");
+    sman.set("staging/db", "staging");
+
+    sman.remove_soft("prod/db").unwrap();
+    sman.remove_soft("staging/db").unwrap();
+    assert_eq!(vec!["prod/api"], sman.keys().collect::<Vec<_>>());
+    assert_eq!(
+        vec!["prod/db", "staging/db"],
+        sman.deleted_keys().collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        sman.retrieve::<String>("prod/db"),
+        Err(Error::SecretNotFound(_))
+    ));
+    assert_eq!(0, sman.keys_with_tag("prod").count());
+
+    sman.rotate_keys(KeySource::Password("other")).unwrap();
+    let bytes = sman.to_bytes().unwrap();
+    let mut sman = SecretsManager::from_bytes(&bytes, KeySource::Password("other")).unwrap();
+    sman.undelete("prod/db").unwrap();
+    assert_eq!("hunter2", sman.retrieve::<String>("prod/db").unwrap());
+    assert_eq!(
+        vec!["prod/db"],
+        sman.keys_with_tag("prod").collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        sman.undelete("prod/db"),
+        Err(Error::SecretNotFound(_))
+    ));
+
+    assert_eq!(1, sman.purge());
+    assert_eq!(0, sman.deleted_keys().count());
+    assert!(matches!(
+        sman.undelete("staging/db"),
+        Err(Error::SecretNotFound(_))
+    ));
+
+    // Reusing the name of a removed secret discards it
+    sman.remove_soft("prod/api").unwrap();
+    sman.set("prod/api", "new token");
+    assert_eq!(0, sman.deleted_keys().count());
+}
+
+/// Verify that hooks are called with each access to the vault.
+#[test]
+fn audit_events() {
+    use crate::Event;
+    use std::sync::{Arc, Mutex};
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let hook = |log: &Arc<Mutex<Vec<String>>>| {
+        let log = Arc::clone(log);
+        move |event: &Event| {
+            let entry = match event {
+                Event::SecretRead { name } => format!("read {}", name),
+                Event::SecretWritten { name } => format!("write {}", name),
+                Event::SecretRemoved { name } => format!("remove {}", name),
+                Event::VaultSaved => "save".to_string(),
+                Event::UnlockFailed { .. } => "unlock failed".to_string(),
+            };
+            log.lock().unwrap().push(entry);
+        }
+    };
+
+    let path = std::env::temp_dir().join(format!("securestore-events-{}", std::process::id()));
+    let mut sman = SecretsManager::builder()
+        .on_event(hook(&log))
+        .create(&path, KeySource::Password("mysecret"))
+        .unwrap();
+    sman.set("foo", "bar");
+    sman.retrieve::<String>("foo").unwrap();
+    let _ = sman.retrieve::<String>("missing");
+    sman.remove("foo").unwrap();
+    sman.save().unwrap();
+    assert_eq!(
+        vec!["write foo", "read foo", "remove foo", "save"],
+        *log.lock().unwrap()
+    );
+
+    log.lock().unwrap().clear();
+    let result = SecretsManager::builder()
+        .on_event(hook(&log))
+        .load(&path, KeySource::Password("wrong"));
+    assert!(matches!(result, Err(Error::InvalidKey)));
+    assert_eq!(vec!["unlock failed"], *log.lock().unwrap());
+
+    // Hooks may also be registered once the vault is loaded
+    log.lock().unwrap().clear();
+    let mut sman = SecretsManager::load(&path, KeySource::Password("mysecret")).unwrap();
+    sman.set("baz", "qux");
+    sman.on_event(hook(&log));
+    sman.retrieve_all().unwrap();
+    assert_eq!(vec!["read baz"], *log.lock().unwrap());
+
+    // as are secrets renamed, compared or merged without being retrieved
+    log.lock().unwrap().clear();
+    sman.rename("baz", "renamed").unwrap();
+    let mut other = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    other.set("renamed", "changed");
+    other.on_event(hook(&log));
+    sman.diff(&other).unwrap();
+    assert_eq!(
+        vec![
+            "remove baz",
+            "write renamed",
+            "read renamed",
+            "read renamed"
+        ],
+        *log.lock().unwrap()
+    );
+
+    log.lock().unwrap().clear();
+    let base =
+        SecretsManager::from_bytes(&sman.to_bytes().unwrap(), KeySource::Password("mysecret"))
+            .unwrap();
+    let mut other =
+        SecretsManager::from_bytes(&sman.to_bytes().unwrap(), KeySource::Password("mysecret"))
+            .unwrap();
+    other.set("new", "value");
+    other.on_event(hook(&log));
+    sman.merge_three_way(&base, &other).unwrap();
+    assert_eq!(vec!["read new", "write new"], *log.lock().unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// Verify that expired secrets cannot be retrieved, and that setting a secret again clears its
+/// expiration.
+#[test]
+fn secret_expiry() {
+    use std::time::{Duration, SystemTime};
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    let past = SystemTime::now() - Duration::from_secs(60);
+    let future = SystemTime::now() + Duration::from_secs(3600);
+    sman.set_with_expiry("old", "stale", past);
+    sman.set_with_expiry("new", "fresh", future);
+    sman.set("forever", "value");
+
+    assert!(matches!(
+        sman.retrieve::<String>("old"),
+        Err(Error::SecretExpired(_))
+    ));
+    assert_eq!("fresh", sman.retrieve::<String>("new").unwrap());
+    assert_eq!(vec!["old"], sman.expired_keys().collect::<Vec<_>>());
+    assert!(sman.expiry("forever").is_none());
+    assert!(sman.retrieve_all().is_err());
+
+    // Setting the secret again clears its expiration
+    sman.set("old", "rotated");
+    assert_eq!("rotated", sman.retrieve::<String>("old").unwrap());
+    assert_eq!(0, sman.expired_keys().count());
+
+    sman.set_expiry("forever", Some(past)).unwrap();
+    assert_eq!(vec!["forever"], sman.expired_keys().collect::<Vec<_>>());
+    sman.set_expiry("forever", None).unwrap();
+    assert_eq!("value", sman.retrieve::<String>("forever").unwrap());
+    assert!(matches!(
+        sman.set_expiry("missing", None),
+        Err(Error::SecretNotFound(_))
+    ));
+}
+
+/// Verify that secrets are layered into `config` configuration, with namespaces as tables.
+#[cfg(feature = "config")]
+#[test]
+fn config_source() {
+    use crate::SecureStoreSource;
+    use std::collections::HashMap;
+
+    let path = "./config_source.json";
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("api_key", "abc123");
+    sman.set("db/user", "admin");
+    sman.set("db/password", "hunter2");
+    sman.save().unwrap();
+
+    let settings = config::Config::builder()
+        .set_default("db.host", "localhost")
+        .unwrap()
+        .add_source(SecureStoreSource::new(path, KeySource::Password("mysecret")).unwrap())
+        .build()
+        .unwrap();
+    assert_eq!("abc123", settings.get_string("api_key").unwrap());
+    assert_eq!("hunter2", settings.get_string("db.password").unwrap());
+    let db: HashMap<String, String> = settings.get("db").unwrap();
+    assert_eq!(3, db.len());
+    assert_eq!("localhost", db["host"]);
+
+    let past = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+    sman.set_expiry("api_key", Some(past)).unwrap();
+    assert!(matches!(
+        SecureStoreSource::from_manager(&sman),
+        Err(Error::SecretExpired(_))
+    ));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that secrets are exported to the environment, and that collisions are resolved
+/// according to the strategy chosen.
+#[test]
+fn export_to_env() {
+    use crate::{env_var_name, EnvCollision};
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set("db/password", "hunter2");
+    sman.set("api-key", "abc123");
+
+    let prefix = "SECURESTORE_TEST_EXPORT_";
+    let variables = sman
+        .export_to_env(prefix, env_var_name, EnvCollision::Fail)
+        .unwrap();
+    assert_eq!(
+        vec![
+            format!("{}API_KEY", prefix),
+            format!("{}DB_PASSWORD", prefix)
+        ],
+        variables
+    );
+    assert_eq!("hunter2", std::env::var(&variables[1]).unwrap());
+
+    // Both variables are now set, and `db_password` maps to the same variable as `db/password`
+    sman.set("api-key", "rotated");
+    sman.set("db_password", "other");
+    match sman.export_to_env(prefix, env_var_name, EnvCollision::Fail) {
+        Err(Error::EnvVarCollision(names)) => assert_eq!(variables, names),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!("abc123", std::env::var(&variables[0]).unwrap());
+
+    sman.export_to_env(prefix, env_var_name, EnvCollision::Keep)
+        .unwrap();
+    assert_eq!("abc123", std::env::var(&variables[0]).unwrap());
+    assert_eq!("hunter2", std::env::var(&variables[1]).unwrap());
+
+    sman.export_to_env(prefix, env_var_name, EnvCollision::Overwrite)
+        .unwrap();
+    assert_eq!("rotated", std::env::var(&variables[0]).unwrap());
+    assert_eq!("other", std::env::var(&variables[1]).unwrap());
+
+    assert!(matches!(
+        sman.export_to_env("", |_| String::new(), EnvCollision::Overwrite),
+        Err(Error::InvalidEnvVar(_))
+    ));
+    for variable in variables {
+        std::env::remove_var(variable);
+    }
+}
+
+/// Verify that generated passwords have the requested length and are drawn from the charset.
+#[test]
+fn generated_passwords() {
+    use crate::generators::{self, Charset};
+
+    for charset in [
+        Charset::Digits,
+        Charset::Hex,
+        Charset::Alphanumeric,
+        Charset::AlphanumericSymbols,
+    ] {
+        let password = generators::password(200, charset);
+        let password = password.expose_secret();
+        assert_eq!(200, password.len());
+        assert!(password.chars().all(|c| charset.chars().contains(c)));
+    }
+
+    // 32 random alphanumeric characters should never repeat
+    let a = generators::password(32, Charset::Alphanumeric);
+    let b = generators::password(32, Charset::Alphanumeric);
+    assert_ne!(a.expose_secret(), b.expose_secret());
+    assert_eq!("", generators::password(0, Charset::Hex).expose_secret());
+}
+
+/// Verify TOTP codes against the test vectors of RFC 6238.
+#[test]
+fn totp_codes() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mut sman = SecretsManager::new_in_memory(KeySource::Password("mysecret")).unwrap();
+    sman.set_totp(
+        "sha1",
+        "otpauth://totp/Example:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8",
+    )
+    .unwrap();
+    sman.set_totp(
+        "sha256",
+        "otpauth://totp/Example?issuer=Example&algorithm=SHA256&digits=8&secret=\
+         GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA%3D%3D%3D%3D",
+    )
+    .unwrap();
+    // A bare seed, with the default 6 digits
+    sman.set("bare", "gezd gnbv gy3t qojq gezd gnbv gy3t qojq");
+
+    let at = |seconds| UNIX_EPOCH + Duration::from_secs(seconds);
+    assert_eq!("94287082", sman.totp_code("sha1", at(59)).unwrap());
+    assert_eq!("07081804", sman.totp_code("sha1", at(1111111109)).unwrap());
+    assert_eq!("46119246", sman.totp_code("sha256", at(59)).unwrap());
+    assert_eq!(
+        "68084774",
+        sman.totp_code("sha256", at(1111111109)).unwrap()
+    );
+    assert_eq!("287082", sman.totp_code("bare", at(59)).unwrap());
+
+    assert!(matches!(
+        sman.set_totp("bad", "otpauth://totp/Example?secret=GEZD&algorithm=MD5"),
+        Err(Error::InvalidTotp(_))
+    ));
+    assert!(matches!(
+        sman.set_totp("bad", "otpauth://totp/Example?issuer=Example"),
+        Err(Error::InvalidTotp(_))
+    ));
+    assert!(!sman.keys().any(|name| name == "bad"));
+    sman.set("password", "hunter2!");
+    assert!(matches!(
+        sman.totp_code("password", at(59)),
+        Err(Error::InvalidTotp(_))
+    ));
+}
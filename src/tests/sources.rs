@@ -0,0 +1,776 @@
+//! Tests for key sources backed by external tools, which are skipped if the tool is missing.
+// Helpers and imports are only used by the tests for enabled features
+#![allow(dead_code, unused_imports)]
+
+use crate::errors::Error;
+use crate::{KeySource, SecretsManager};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Returns `true` if `program` can be executed.
+fn available(program: &str) -> bool {
+    Command::new(program)
+        .arg("--help")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Creates an empty scratch directory unique to the named test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("securestore-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Verify that keys can be derived from an Ed25519 key held in ssh-agent
+#[cfg(all(feature = "ssh-agent", unix))]
+#[test]
+fn ssh_agent_key_source() {
+    if !available("ssh-agent") || !available("ssh-keygen") {
+        eprintln!("skipping ssh_agent_key_source: OpenSSH is not installed");
+        return;
+    }
+
+    let dir = scratch_dir("ssh-agent");
+    let socket = dir.join("agent.sock");
+    let key = dir.join("id_ed25519");
+    let path = dir.join("secrets.json");
+
+    let mut agent = Command::new("ssh-agent")
+        .arg("-D")
+        .arg("-a")
+        .arg(&socket)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    while !socket.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", ""])
+        .args(["-C", "securestore-test", "-f"])
+        .arg(&key)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new("ssh-add")
+        .arg("-q")
+        .arg(&key)
+        .env("SSH_AUTH_SOCK", &socket)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::env::set_var("SSH_AUTH_SOCK", &socket);
+
+    let identity = "securestore-test";
+    let mut sman = SecretsManager::new(&path, KeySource::SshAgent { identity }).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let sman = SecretsManager::load(&path, KeySource::SshAgent { identity }).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    match SecretsManager::load(
+        &path,
+        KeySource::SshAgent {
+            identity: "missing",
+        },
+    ) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("Loading with a missing ssh-agent identity did not return KeySourceFailure!"),
+    }
+
+    agent.kill().unwrap();
+    agent.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that keys can be loaded from a keyfile encrypted to an OpenPGP key
+#[cfg(feature = "gpg")]
+#[test]
+fn gpg_key_source() {
+    if !available("gpg") {
+        eprintln!("skipping gpg_key_source: GnuPG is not installed");
+        return;
+    }
+
+    let dir = scratch_dir("gpg");
+    let home = dir.join("gnupg");
+    let keyfile = dir.join("secrets.key");
+    let encrypted = dir.join("secrets.key.gpg");
+    let path = dir.join("secrets.json");
+    std::fs::create_dir(&home).unwrap();
+    std::env::set_var("GNUPGHOME", &home);
+
+    let gpg = |args: &[&str]| {
+        let status = Command::new("gpg")
+            .args(["--batch", "--quiet", "--passphrase", ""])
+            .args(args)
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "gpg {:?} failed", args);
+    };
+    gpg(&[
+        "--quick-gen-key",
+        "securestore@example.com",
+        "default",
+        "default",
+    ]);
+
+    let mut sman = SecretsManager::new(&path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    sman.export_keyfile(&keyfile).unwrap();
+
+    gpg(&[
+        "--trust-model",
+        "always",
+        "--recipient",
+        "securestore@example.com",
+        "--output",
+        encrypted.to_str().unwrap(),
+        "--encrypt",
+        keyfile.to_str().unwrap(),
+    ]);
+
+    let sman = SecretsManager::load(&path, KeySource::GpgFile(&encrypted)).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // an unencrypted keyfile is rejected by gpg
+    match SecretsManager::load(&path, KeySource::GpgFile(&keyfile)) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("Loading a non-OpenPGP keyfile did not return KeySourceFailure!"),
+    }
+
+    let _ = Command::new("gpgconf")
+        .args(["--kill", "gpg-agent"])
+        .status();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that keys can be loaded from a keyfile encrypted to an age identity
+#[cfg(feature = "age")]
+#[test]
+fn age_key_source() {
+    use age::secrecy::ExposeSecret;
+
+    let dir = scratch_dir("age");
+    let identity_path = dir.join("identity.txt");
+    let encrypted = dir.join("secrets.key.age");
+    let path = dir.join("secrets.json");
+
+    let identity = age::x25519::Identity::generate();
+    std::fs::write(&identity_path, identity.to_string().expose_secret()).unwrap();
+
+    let mut sman = SecretsManager::new(&path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    let mut key_data = Vec::new();
+    key_data.extend_from_slice(&sman.keys.encryption);
+    key_data.extend_from_slice(&sman.keys.hmac);
+    let ciphertext = age::encrypt(&identity.to_public(), &key_data).unwrap();
+    std::fs::write(&encrypted, ciphertext).unwrap();
+
+    let source = KeySource::AgeFile {
+        path: &encrypted,
+        identity: &identity_path,
+    };
+    let sman = SecretsManager::load(&path, source).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // a keyfile encrypted to a different identity cannot be decrypted
+    let other = age::x25519::Identity::generate();
+    let ciphertext = age::encrypt(&other.to_public(), &key_data).unwrap();
+    std::fs::write(&encrypted, ciphertext).unwrap();
+
+    let source = KeySource::AgeFile {
+        path: &encrypted,
+        identity: &identity_path,
+    };
+    match SecretsManager::load(&path, source) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("Loading with the wrong age identity did not return KeySourceFailure!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that failing to load a PKCS#11 module is reported as a key source failure
+#[cfg(feature = "pkcs11")]
+#[test]
+fn pkcs11_missing_module() {
+    let dir = scratch_dir("pkcs11");
+    let path = dir.join("secrets.json");
+
+    let source = KeySource::Pkcs11 {
+        module: Path::new("/nonexistent/libpkcs11.so"),
+        slot: 0,
+        pin: "1234",
+        label: "securestore",
+    };
+    match SecretsManager::new(&path, source) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("Loading a missing PKCS#11 module did not return KeySourceFailure!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that YubiKey challenge-response is combined with the password, using a stand-in for
+/// `ykchalresp` which answers with the SHA1 of the challenge
+#[cfg(all(feature = "yubikey", unix))]
+#[test]
+fn yubikey_key_source() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if !available("sha1sum") {
+        eprintln!("skipping yubikey_key_source: sha1sum is not installed");
+        return;
+    }
+
+    let dir = scratch_dir("yubikey");
+    let path = dir.join("secrets.json");
+    let ykchalresp = dir.join("ykchalresp");
+    std::fs::write(
+        &ykchalresp,
+        "#!/bin/sh\nprintf '%s' \"$1$3\" | sha1sum | cut -d' ' -f1\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&ykchalresp, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let search_path = std::env::var_os("PATH").unwrap_or_default();
+    let mut paths = vec![dir.clone()];
+    paths.extend(std::env::split_paths(&search_path));
+    std::env::set_var("PATH", std::env::join_paths(paths).unwrap());
+
+    let source = |slot, password| KeySource::YubikeyChallengeResponse { slot, password };
+    let mut sman = SecretsManager::new(&path, source(2, Some("hunter2"))).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert!(sman.vault.kdf.is_some());
+
+    let sman = SecretsManager::load(&path, source(2, Some("hunter2"))).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    for (slot, password) in [(2, None), (2, Some("hunter3")), (1, Some("hunter2"))] {
+        match SecretsManager::load(&path, source(slot, password)) {
+            Err(Error::InvalidKey) => {}
+            _ => panic!("Unlocking with the wrong YubiKey slot or password succeeded!"),
+        }
+    }
+
+    match SecretsManager::load(&path, source(3, None)) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("Loading with an invalid YubiKey slot did not return KeySourceFailure!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that keys sealed to the TPM can be unsealed, if a TPM and tpm2-tools are available
+#[cfg(feature = "tpm")]
+#[test]
+fn tpm_key_source() {
+    let dir = scratch_dir("tpm");
+    let path = dir.join("secrets.json");
+    let sealed = dir.join("secrets.key.tpm");
+
+    let mut sman = SecretsManager::new(&path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    // a malformed sealed keyfile is rejected before the TPM is involved
+    std::fs::write(&sealed, "not a sealed keyfile").unwrap();
+    match SecretsManager::load(&path, KeySource::Tpm { path: &sealed }) {
+        Err(Error::InvalidKeyfile) => {}
+        _ => panic!("Loading a malformed sealed keyfile did not return InvalidKeyfile!"),
+    }
+
+    let missing = dir.join("missing.key.tpm");
+    match SecretsManager::load(&path, KeySource::Tpm { path: &missing }) {
+        Err(Error::File { path, .. }) => assert_eq!(missing, path),
+        _ => panic!("Loading a missing sealed keyfile did not return its path!"),
+    }
+
+    if !available("tpm2_createprimary") || sman.export_keyfile_tpm(&sealed, None).is_err() {
+        eprintln!("skipping tpm_key_source: no TPM or tpm2-tools available");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    let sman = SecretsManager::load(&path, KeySource::Tpm { path: &sealed }).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that keys stored in the OS keychain can be loaded, if a keychain is available
+#[cfg(feature = "keychain")]
+#[test]
+fn keychain_key_source() {
+    let dir = scratch_dir("keychain");
+    let path = dir.join("secrets.json");
+    let service = "securestore-rs tests";
+    let account = format!("keychain-{}", std::process::id());
+
+    let mut sman = SecretsManager::new(&path, KeySource::Generate).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+
+    if sman.export_keys_to_keychain(service, &account).is_err() {
+        eprintln!("skipping keychain_key_source: no OS keychain available");
+        std::fs::remove_dir_all(&dir).unwrap();
+        return;
+    }
+
+    let source = KeySource::OsKeychain {
+        service,
+        account: &account,
+    };
+    let sman = SecretsManager::load(&path, source).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let _ = keyring::Entry::new(service, &account).and_then(|e| e.delete_credential());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A request received by [`mock_server()`], with lowercased header names.
+#[cfg(feature = "ureq")]
+pub(super) struct MockRequest {
+    pub request_line: String,
+    pub headers: std::collections::HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Serves HTTP requests on a random local port by passing each to `handler`, which returns the
+/// status code and JSON body of the response. Returns the base URL of the server.
+#[cfg(feature = "ureq")]
+pub(super) fn mock_server<F>(handler: F) -> String
+where
+    F: Fn(&MockRequest) -> (u16, String) + Send + 'static,
+{
+    mock_server_with_headers(move |request| {
+        let (status, body) = handler(request);
+        (status, Vec::new(), body)
+    })
+}
+
+/// Like [`mock_server()`], but `handler` also returns additional headers for the response.
+#[cfg(feature = "ureq")]
+pub(super) fn mock_server_with_headers<F>(handler: F) -> String
+where
+    F: Fn(&MockRequest) -> (u16, Vec<(&'static str, String)>, String) + Send + 'static,
+{
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut headers = std::collections::HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                match line.trim_end().split_once(':') {
+                    Some((name, value)) => {
+                        headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+                    }
+                    None => break,
+                }
+            }
+            let length = headers
+                .get("content-length")
+                .map_or(0, |l| l.parse().unwrap());
+            let mut body = vec![0u8; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let request = MockRequest {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body,
+            };
+            let (status, headers, body) = handler(&request);
+            let headers: String = headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                .collect();
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\n{}\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                headers,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    url
+}
+
+/// Verify the AWS Signature Version 4 implementation against the example in the AWS docs
+#[cfg(feature = "aws-kms")]
+#[test]
+fn aws_sigv4_signature() {
+    use crate::sources::aws::{sign, timestamp, Credentials};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let timestamp = timestamp(UNIX_EPOCH + Duration::from_secs(1440938160));
+    assert_eq!("20150830T123600Z", timestamp);
+
+    let credentials = Credentials {
+        access_key_id: "AKIDEXAMPLE".to_string(),
+        secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY"
+            .to_string()
+            .into(),
+        session_token: None,
+    };
+    let headers = [
+        (
+            "Content-Type",
+            "application/x-www-form-urlencoded; charset=utf-8".to_string(),
+        ),
+        ("Host", "iam.amazonaws.com".to_string()),
+        ("X-Amz-Date", timestamp.clone()),
+    ];
+    let authorization = sign(
+        &credentials,
+        "us-east-1",
+        "iam",
+        ("GET", "/", "Action=ListUsers&Version=2010-05-08"),
+        &headers,
+        b"",
+        &timestamp,
+    );
+
+    assert_eq!(
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+         SignedHeaders=content-type;host;x-amz-date, \
+         Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7",
+        authorization
+    );
+}
+
+/// Verify envelope encryption with AWS KMS against a mock KMS endpoint
+#[cfg(feature = "aws-kms")]
+#[test]
+fn aws_kms_key_source() {
+    let key_id = "arn:aws:kms:us-east-1:111122223333:key/securestore";
+    let endpoint = mock_server(move |request| {
+        let authorization = &request.headers["authorization"];
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        assert!(authorization.contains("/us-east-1/kms/aws4_request"));
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(key_id, body["KeyId"]);
+        match request.headers["x-amz-target"].as_str() {
+            "TrentService.GenerateDataKey" => {
+                assert_eq!(32, body["NumberOfBytes"]);
+                let response = serde_json::json!({
+                    "CiphertextBlob": base64::encode(&[[0xAA; 4], [0x42; 4]].concat()),
+                    "KeyId": key_id,
+                    "Plaintext": base64::encode(&[0x42; 32]),
+                });
+                (200, response.to_string())
+            }
+            "TrentService.Decrypt" => {
+                let blob = base64::decode(body["CiphertextBlob"].as_str().unwrap()).unwrap();
+                if blob != [[0xAA; 4], [0x42; 4]].concat() {
+                    return (
+                        400,
+                        r#"{"__type":"InvalidCiphertextException"}"#.to_string(),
+                    );
+                }
+                let response = serde_json::json!({ "Plaintext": base64::encode(&[0x42; 32]) });
+                (200, response.to_string())
+            }
+            target => panic!("Unexpected KMS request {} {}", request.request_line, target),
+        }
+    });
+    std::env::set_var("AWS_ENDPOINT_URL_KMS", &endpoint);
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+    std::env::set_var(
+        "AWS_SECRET_ACCESS_KEY",
+        "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+    );
+
+    let dir = scratch_dir("aws-kms");
+    let path = dir.join("secrets.json");
+
+    let mut sman = SecretsManager::new(&path, KeySource::AwsKms { key_id }).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let wrapped = sman.vault.wrapped_key.as_ref().unwrap();
+    assert_eq!("aws-kms", wrapped.provider);
+    assert_eq!(key_id, wrapped.key);
+    assert_eq!([0x42; 16], sman.keys.encryption);
+
+    let sman = SecretsManager::load(&path, KeySource::AwsKms { key_id }).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // a vault without wrapped keys cannot be unlocked with KMS
+    let sman = SecretsManager::new(&path, KeySource::Generate).unwrap();
+    sman.save().unwrap();
+    match SecretsManager::load(&path, KeySource::AwsKms { key_id }) {
+        Err(Error::MissingWrappedKey) => {}
+        _ => panic!("Unlocking a vault without wrapped keys did not return MissingWrappedKey!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify wrapping of the vault keys with Google Cloud KMS against a mock KMS endpoint
+#[cfg(feature = "gcp")]
+#[test]
+fn gcp_kms_key_source() {
+    let key_name = "projects/securestore/locations/global/keyRings/tests/cryptoKeys/vault";
+    let endpoint = mock_server(move |request| {
+        assert_eq!("Bearer ya29.securestore", request.headers["authorization"]);
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        let (method, path) = match request.request_line.split(' ').collect::<Vec<_>>()[..] {
+            [method, path, _] => (method.to_string(), path.to_string()),
+            _ => panic!("Malformed request line {}", request.request_line),
+        };
+        assert_eq!("POST", method);
+
+        // the mock "wraps" keys by reversing them
+        let reverse = |field: &str| {
+            let mut data = base64::decode(body[field].as_str().unwrap()).unwrap();
+            data.reverse();
+            base64::encode(&data)
+        };
+        if path == format!("/v1/{}:encrypt", key_name) {
+            let response =
+                serde_json::json!({ "name": key_name, "ciphertext": reverse("plaintext") });
+            (200, response.to_string())
+        } else if path == format!("/v1/{}:decrypt", key_name) {
+            (
+                200,
+                serde_json::json!({ "plaintext": reverse("ciphertext") }).to_string(),
+            )
+        } else {
+            (404, r#"{"error":{"code":404}}"#.to_string())
+        }
+    });
+    std::env::set_var("CLOUDSDK_API_ENDPOINT_OVERRIDES_CLOUDKMS", &endpoint);
+    std::env::set_var("GOOGLE_OAUTH_ACCESS_TOKEN", "ya29.securestore");
+
+    let dir = scratch_dir("gcp-kms");
+    let path = dir.join("secrets.json");
+
+    let mut sman = SecretsManager::new(&path, KeySource::GcpKms { key_name }).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let wrapped = sman.vault.wrapped_key.as_ref().unwrap();
+    assert_eq!("gcp-kms", wrapped.provider);
+    assert_eq!(key_name, wrapped.key);
+    let mut unwrapped = wrapped.ciphertext.clone();
+    unwrapped.reverse();
+    assert_eq!(&sman.keys.encryption[..], &unwrapped[..16]);
+
+    let sman = SecretsManager::load(&path, KeySource::GcpKms { key_name }).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // errors returned by KMS are reported as key source failures
+    let source = KeySource::GcpKms {
+        key_name: "projects/missing",
+    };
+    match SecretsManager::load(&path, source) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("An error returned by KMS did not return KeySourceFailure!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify wrapping of the vault keys with Azure Key Vault against a mock Key Vault
+#[cfg(feature = "azure")]
+#[test]
+fn azure_key_vault_key_source() {
+    let endpoint = mock_server(move |request| {
+        assert_eq!(
+            "Bearer eyJ0eXAi.securestore",
+            request.headers["authorization"]
+        );
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!("RSA-OAEP-256", body["alg"]);
+        let mut value =
+            base64::decode_config(body["value"].as_str().unwrap(), base64::URL_SAFE_NO_PAD)
+                .unwrap();
+        // the mock "wraps" keys by reversing them
+        value.reverse();
+        let response = |kid: &str| {
+            let value = base64::encode_config(&value, base64::URL_SAFE_NO_PAD);
+            (
+                200,
+                serde_json::json!({ "kid": kid, "value": value }).to_string(),
+            )
+        };
+
+        match request.request_line.as_str() {
+            "POST /keys/vault/wrapkey?api-version=7.4 HTTP/1.1" => {
+                response("https://securestore.vault.azure.net/keys/vault/0123abcd")
+            }
+            "POST /keys/vault/0123abcd/unwrapkey?api-version=7.4 HTTP/1.1" => {
+                response("https://securestore.vault.azure.net/keys/vault/0123abcd")
+            }
+            _ => (404, r#"{"error":{"code":"KeyNotFound"}}"#.to_string()),
+        }
+    });
+    std::env::set_var("AZURE_ACCESS_TOKEN", "eyJ0eXAi.securestore");
+
+    let dir = scratch_dir("azure-key-vault");
+    let path = dir.join("secrets.json");
+    let source = || KeySource::AzureKeyVault {
+        vault_url: &endpoint,
+        key_name: "vault",
+    };
+
+    let mut sman = SecretsManager::new(&path, source()).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let wrapped = sman.vault.wrapped_key.as_ref().unwrap();
+    assert_eq!("azure-key-vault", wrapped.provider);
+    assert_eq!(
+        "https://securestore.vault.azure.net/keys/vault/0123abcd",
+        wrapped.key
+    );
+    let mut unwrapped = wrapped.ciphertext.clone();
+    unwrapped.reverse();
+    assert_eq!(&sman.keys.encryption[..], &unwrapped[..16]);
+
+    let sman = SecretsManager::load(&path, source()).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // errors returned by Key Vault are reported as key source failures
+    let source = KeySource::AzureKeyVault {
+        vault_url: &endpoint,
+        key_name: "missing",
+    };
+    match SecretsManager::load(&path, source) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("An error returned by Key Vault did not return KeySourceFailure!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify wrapping of the vault keys with Vault transit against a mock Vault server
+#[cfg(feature = "vault-transit")]
+#[test]
+fn vault_transit_key_source() {
+    let addr = mock_server(move |request| {
+        if request.headers["x-vault-token"] != "hvs.securestore" {
+            return (403, r#"{"errors":["permission denied"]}"#.to_string());
+        }
+
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        match request.request_line.as_str() {
+            "POST /v1/transit/encrypt/vault HTTP/1.1" => {
+                let plaintext = body["plaintext"].as_str().unwrap();
+                let response = serde_json::json!({
+                    "data": { "ciphertext": format!("vault:v1:{}", plaintext) }
+                });
+                (200, response.to_string())
+            }
+            "POST /v1/transit/decrypt/vault HTTP/1.1" => {
+                let ciphertext = body["ciphertext"].as_str().unwrap();
+                let response = serde_json::json!({
+                    "data": { "plaintext": ciphertext.trim_start_matches("vault:v1:") }
+                });
+                (200, response.to_string())
+            }
+            _ => (404, r#"{"errors":[]}"#.to_string()),
+        }
+    });
+
+    let dir = scratch_dir("vault-transit");
+    let path = dir.join("secrets.json");
+    let source = |token| KeySource::VaultTransit {
+        addr: &addr,
+        key: "vault",
+        token,
+    };
+
+    let mut sman = SecretsManager::new(&path, source("hvs.securestore")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    let wrapped = sman.vault.wrapped_key.as_ref().unwrap();
+    assert_eq!("vault-transit", wrapped.provider);
+    assert!(wrapped.ciphertext.starts_with(b"vault:v1:"));
+
+    let sman = SecretsManager::load(&path, source("hvs.securestore")).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    match SecretsManager::load(&path, source("hvs.invalid")) {
+        Err(Error::KeySourceFailure(_)) => {}
+        _ => panic!("An unauthorized Vault token did not return KeySourceFailure!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Verify that recipients can be granted and revoked access without re-encrypting the secrets
+#[cfg(feature = "recipients")]
+#[test]
+fn x25519_recipients() {
+    let dir = scratch_dir("recipients");
+    let path = dir.join("secrets.json");
+    let alice = [0x11u8; 32];
+    let bob = [0x22u8; 32];
+    let bob_public =
+        *x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(bob)).as_bytes();
+
+    let mut sman = SecretsManager::new(&path, KeySource::X25519Identity(&alice)).unwrap();
+    sman.set("foo", "bar");
+    let ciphertext = sman.vault.secrets["foo"].payload.clone();
+    sman.add_recipient(&bob_public).unwrap();
+    sman.save().unwrap();
+    assert_eq!(2, sman.recipients().count());
+    assert_eq!(ciphertext, sman.vault.secrets["foo"].payload);
+
+    let mut sman = SecretsManager::load(&path, KeySource::X25519Identity(&bob)).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    let alice_public =
+        *x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(alice)).as_bytes();
+    assert!(sman.recipients().any(|r| r == alice_public));
+    assert!(sman.remove_recipient(&alice_public));
+    assert!(!sman.remove_recipient(&alice_public));
+    sman.save().unwrap();
+    match SecretsManager::load(&path, KeySource::X25519Identity(&alice)) {
+        Err(Error::NotARecipient) => {}
+        _ => panic!("A removed recipient did not return NotARecipient!"),
+    }
+
+    // the remaining recipients retain access when the keys are rotated
+    sman.rotate_keys(KeySource::Generate).unwrap();
+    sman.save().unwrap();
+    let sman = SecretsManager::load(&path, KeySource::X25519Identity(&bob)).unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    // low-order points would give everyone access
+    let mut sman = sman;
+    match sman.add_recipient(&[0u8; 32]) {
+        Err(Error::InvalidRecipient) => {}
+        _ => panic!("A low-order public key did not return InvalidRecipient!"),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
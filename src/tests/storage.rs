@@ -0,0 +1,237 @@
+//! Tests of pluggable vault storage backends
+
+use crate::errors::Error;
+use crate::{FileStorage, KeySource, SecretsManager, VaultStorage};
+use std::sync::{Arc, Mutex};
+
+/// A `VaultStorage` which keeps the vault in a shared buffer, standing in for a database.
+#[derive(Clone, Default)]
+struct MemoryStorage(Arc<Mutex<Option<Vec<u8>>>>);
+
+impl VaultStorage for MemoryStorage {
+    fn load(&self) -> Result<Vec<u8>, Error> {
+        let contents = self.0.lock().unwrap();
+        contents
+            .clone()
+            .ok_or(Error::SecretNotFound("vault".to_string()))
+    }
+
+    fn store(&self, contents: &[u8]) -> Result<(), Error> {
+        *self.0.lock().unwrap() = Some(contents.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self) -> Result<bool, Error> {
+        Ok(self.0.lock().unwrap().is_some())
+    }
+}
+
+/// Verify that a vault can be created, saved and loaded through a custom storage backend.
+#[test]
+fn custom_storage() {
+    let storage = MemoryStorage::default();
+    let mut sman = SecretsManager::builder()
+        .create_with_storage(storage.clone(), KeySource::Password("mysecret"))
+        .unwrap();
+    assert!(!storage.exists().unwrap());
+
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert!(storage.exists().unwrap());
+
+    let sman = SecretsManager::builder()
+        .load_with_storage(storage.clone(), KeySource::Password("mysecret"))
+        .unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+    assert_eq!(storage.load().unwrap(), sman.to_bytes().unwrap());
+}
+
+/// Verify that the filesystem backend reads vaults saved by path and vice versa.
+#[test]
+fn file_storage() {
+    let path = "./file_storage.json";
+    let storage = FileStorage::new(path);
+    let _ = std::fs::remove_file(path);
+    assert!(!storage.exists().unwrap());
+
+    let mut sman = SecretsManager::new(path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert!(storage.exists().unwrap());
+
+    let sman = SecretsManager::builder()
+        .load_with_storage(storage, KeySource::Password("mysecret"))
+        .unwrap();
+    assert_eq!("bar", sman.retrieve::<String>("foo").unwrap());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Verify that vaults can be stored in an S3 bucket (here a mock S3 endpoint), and that the
+/// ETag preconditions prevent saves from overwriting changes made by someone else.
+#[cfg(feature = "s3")]
+#[test]
+fn s3_storage() {
+    use super::sources::mock_server_with_headers;
+    use crate::S3Storage;
+
+    // The object and the generation of the object, which serves as its ETag
+    let object = Arc::new(Mutex::new(None::<(String, u32)>));
+    let bucket = object.clone();
+    let endpoint = mock_server_with_headers(move |request| {
+        let authorization = &request.headers["authorization"];
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        assert!(authorization.contains("/us-east-1/s3/aws4_request"));
+        assert!(authorization.contains("x-amz-content-sha256"));
+
+        let mut object = bucket.lock().unwrap();
+        let etag = object
+            .as_ref()
+            .map(|(_, generation)| format!("\"{}\"", generation));
+        match request.request_line.as_str() {
+            "HEAD /bucket/vaults/secrets.json HTTP/1.1" => match etag {
+                Some(etag) => (200, vec![("ETag", etag)], String::new()),
+                None => (404, Vec::new(), String::new()),
+            },
+            "GET /bucket/vaults/secrets.json HTTP/1.1" => match (etag, &*object) {
+                (Some(etag), Some((contents, _))) => (200, vec![("ETag", etag)], contents.clone()),
+                _ => (404, Vec::new(), "NoSuchKey".to_string()),
+            },
+            "PUT /bucket/vaults/secrets.json HTTP/1.1" => {
+                let precondition = match (request.headers.get("if-match"), &etag) {
+                    (Some(expected), Some(etag)) => expected == etag,
+                    (Some(_), None) => false,
+                    (None, _) => {
+                        request.headers.get("if-none-match").map(String::as_str) == Some("*")
+                            && etag.is_none()
+                    }
+                };
+                if !precondition {
+                    return (412, Vec::new(), "PreconditionFailed".to_string());
+                }
+
+                let generation = object.as_ref().map_or(1, |(_, generation)| generation + 1);
+                let contents = String::from_utf8(request.body.clone()).unwrap();
+                *object = Some((contents, generation));
+                (
+                    200,
+                    vec![("ETag", format!("\"{}\"", generation))],
+                    String::new(),
+                )
+            }
+            line => panic!("Unexpected S3 request {}", line),
+        }
+    });
+    std::env::set_var("AWS_ACCESS_KEY_ID", "AKIDEXAMPLE");
+    std::env::set_var(
+        "AWS_SECRET_ACCESS_KEY",
+        "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+    );
+    let storage = || {
+        S3Storage::new("bucket", "vaults/secrets.json")
+            .endpoint(&endpoint)
+            .region("us-east-1")
+    };
+
+    let mut sman = SecretsManager::builder()
+        .create_with_storage(storage(), KeySource::Password("mysecret"))
+        .unwrap();
+    assert!(!storage().exists().unwrap());
+    sman.set("foo", "bar");
+    sman.save().unwrap();
+    assert!(storage().exists().unwrap());
+
+    // Successive saves by the same instance track the ETag of the object
+    let mut sman2 = SecretsManager::builder()
+        .load_with_storage(storage(), KeySource::Password("mysecret"))
+        .unwrap();
+    assert_eq!("bar", sman2.retrieve::<String>("foo").unwrap());
+    sman.set("foo", "baz");
+    sman.save().unwrap();
+    sman.set("other", "secret");
+    sman.save().unwrap();
+
+    // ... but an instance loaded before those saves must not overwrite them
+    sman2.set("foo", "qux");
+    match sman2.save() {
+        Err(Error::VaultConflict) => {}
+        _ => panic!("Saving a stale vault to S3 did not return VaultConflict!"),
+    }
+
+    // ... and neither may a new vault replace the existing one
+    let sman3 = SecretsManager::builder()
+        .create_with_storage(storage(), KeySource::Password("mysecret"))
+        .unwrap();
+    match sman3.save() {
+        Err(Error::VaultConflict) => {}
+        _ => panic!("Creating a vault over an existing S3 object did not return VaultConflict!"),
+    }
+
+    let sman = SecretsManager::builder()
+        .load_with_storage(storage(), KeySource::Password("mysecret"))
+        .unwrap();
+    assert_eq!("baz", sman.retrieve::<String>("foo").unwrap());
+    assert_eq!(3, object.lock().unwrap().as_ref().unwrap().1);
+}
+
+/// Verify that vaults can be stored in a SQLite database, and that saving only rewrites the
+/// rows of secrets which changed.
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_storage() {
+    use crate::SqliteStorage;
+    use rusqlite::Connection;
+
+    let path = "./sqlite_storage.db";
+    let _ = std::fs::remove_file(path);
+
+    let storage = SqliteStorage::open(path).unwrap();
+    assert!(!storage.exists().unwrap());
+    let mut sman = SecretsManager::builder()
+        .create_with_storage(storage, KeySource::Password("mysecret"))
+        .unwrap();
+    for name in &["foo", "bar", "baz"] {
+        sman.set(name, *name);
+    }
+    sman.save().unwrap();
+
+    // Record every row written from here on
+    let db = Connection::open(path).unwrap();
+    db.execute_batch(
+        "CREATE TABLE written (name TEXT);
+         CREATE TRIGGER log_writes AFTER INSERT ON securestore_secrets
+         BEGIN INSERT INTO written VALUES (new.name); END;",
+    )
+    .unwrap();
+
+    sman.set("foo", "updated");
+    sman.remove("bar").unwrap();
+    sman.save().unwrap();
+
+    let written: Vec<String> = db
+        .prepare("SELECT name FROM written")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(vec!["foo".to_string()], written);
+    let rows: u32 = db
+        .query_row("SELECT COUNT(*) FROM securestore_secrets", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(2, rows);
+
+    let storage = SqliteStorage::open(path).unwrap();
+    assert!(storage.exists().unwrap());
+    let sman = SecretsManager::builder()
+        .load_with_storage(storage, KeySource::Password("mysecret"))
+        .unwrap();
+    assert_eq!("updated", sman.retrieve::<String>("foo").unwrap());
+    assert_eq!("baz", sman.retrieve::<String>("baz").unwrap());
+    assert!(sman.retrieve::<String>("bar").is_err());
+
+    drop(db);
+    std::fs::remove_file(path).unwrap();
+}
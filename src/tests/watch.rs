@@ -0,0 +1,53 @@
+//! Tests of reloading vaults changed on-disk
+
+use crate::errors::Error;
+use crate::{KeySource, SecretsManager};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Verify that a watched vault is reloaded when another instance saves changes to it, and
+/// that changes which fail to verify are rejected.
+#[test]
+fn watch_reload() {
+    let dir = std::path::Path::new("./watch");
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir(dir).unwrap();
+    let path = dir.join("secrets.json");
+
+    let mut sman = SecretsManager::new(&path, KeySource::Password("mysecret")).unwrap();
+    sman.set("foo", "bar");
+    sman.set("unchanged", "value");
+    sman.save().unwrap();
+
+    let (sender, receiver) = mpsc::channel();
+    let watched = sman
+        .watch(move |changed| sender.send(changed).unwrap())
+        .unwrap();
+
+    let mut other = SecretsManager::load(&path, KeySource::Password("mysecret")).unwrap();
+    other.set("foo", "baz");
+    other.set("new", "secret");
+    other.save().unwrap();
+
+    let changed = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(vec!["foo".to_string(), "new".to_string()], changed.unwrap());
+    assert_eq!("baz", watched.read().retrieve::<String>("foo").unwrap());
+    assert_eq!("secret", watched.read().retrieve::<String>("new").unwrap());
+
+    // A vault replaced with one protected by other keys is rejected
+    let mut imposter = SecretsManager::new(&path, KeySource::Password("other")).unwrap();
+    imposter.set("foo", "evil");
+    imposter.save().unwrap();
+
+    match receiver.recv_timeout(Duration::from_secs(10)).unwrap() {
+        Err(Error::InvalidKey) => {}
+        result => panic!(
+            "Reloading a vault with different keys returned {:?}",
+            result
+        ),
+    }
+    assert_eq!("baz", watched.read().retrieve::<String>("foo").unwrap());
+
+    drop(watched);
+    std::fs::remove_dir_all(dir).unwrap();
+}
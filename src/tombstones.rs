@@ -0,0 +1,103 @@
+//! Soft removal of secrets, which can be undone until the removed secrets are purged.
+//!
+//! A soft-removed secret is moved out of the secrets of the vault, so it is no longer listed
+//! or retrieved, into a tombstone holding its encrypted value. Its metadata, description, tags
+//! and history are kept as they are until it is restored or purged.
+
+use crate::clock;
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::events::Event;
+use crate::shared::{EncryptedBlob, Keys, Scheme};
+use crate::SecretsManager;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A soft-removed secret, as stored in the vault.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Tombstone {
+    /// When the secret was removed, in seconds since the Unix epoch.
+    pub removed: u64,
+    pub value: EncryptedBlob,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Removes the secret identified by `name`, keeping its encrypted value in the vault so
+    /// that the removal can be undone with [`undelete()`](Self::undelete) (e.g. after an
+    /// accidental removal was committed) until [`purge()`](Self::purge) is called. If the
+    /// secret cannot be found, returns [`Error::SecretNotFound`].
+    ///
+    /// Adding a new secret with the same name discards the removed secret.
+    pub fn remove_soft(&mut self, name: &str) -> Result<(), Error> {
+        let value = self
+            .vault
+            .secrets
+            .remove(name)
+            .ok_or_else(|| Error::SecretNotFound(name.to_string()))?;
+        let removed = clock::seconds(clock::now());
+        self.vault
+            .tombstones
+            .insert(name.to_string(), Tombstone { removed, value });
+        self.vault.mark_dirty();
+        self.emit(Event::SecretRemoved { name });
+        Ok(())
+    }
+
+    /// Restores the secret `name` removed with [`remove_soft()`](Self::remove_soft). If no
+    /// such secret was removed, returns [`Error::SecretNotFound`].
+    pub fn undelete(&mut self, name: &str) -> Result<(), Error> {
+        let tombstone = self
+            .vault
+            .tombstones
+            .remove(name)
+            .ok_or_else(|| Error::SecretNotFound(name.to_string()))?;
+        self.vault.secrets.insert(name.to_string(), tombstone.value);
+        self.vault.mark_dirty();
+        self.emit(Event::SecretWritten { name });
+        Ok(())
+    }
+
+    /// Returns an iterator over the names of all soft-removed secrets, in sorted order.
+    pub fn deleted_keys(&self) -> impl Iterator<Item = &str> {
+        self.vault.tombstones.keys().map(String::as_str)
+    }
+
+    /// Permanently removes all soft-removed secrets from the vault, returning the number of
+    /// secrets purged.
+    pub fn purge(&mut self) -> usize {
+        let names: Vec<String> = self.vault.tombstones.keys().cloned().collect();
+        for name in &names {
+            self.discard_tombstone(name);
+        }
+        if !names.is_empty() {
+            self.vault.mark_dirty();
+        }
+        names.len()
+    }
+
+    /// Discards the tombstone of the soft-removed secret `name`, if any, along with everything
+    /// else recorded about it.
+    pub(crate) fn discard_tombstone(&mut self, name: &str) {
+        if self.vault.tombstones.remove(name).is_some() {
+            self.forget(name);
+        }
+    }
+
+    /// Decrypts every soft-removed secret with the current keys and encrypts it again with
+    /// `keys` and `scheme`, without modifying the vault.
+    pub(crate) fn reencrypt_tombstones(
+        &self,
+        keys: &Keys,
+        scheme: Scheme,
+    ) -> Result<BTreeMap<String, Tombstone>, Error> {
+        let mut tombstones = BTreeMap::new();
+        for (name, tombstone) in &self.vault.tombstones {
+            let tombstone = Tombstone {
+                removed: tombstone.removed,
+                value: self.reencrypt_blob(&tombstone.value, keys, scheme)?,
+            };
+            tombstones.insert(name.clone(), tombstone);
+        }
+        Ok(tombstones)
+    }
+}
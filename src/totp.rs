@@ -0,0 +1,147 @@
+//! Time-based one-time passwords (RFC 6238), generated from seeds stored as secrets, so that 2FA
+//! seeds can be kept in the same vault as the passwords they protect.
+//!
+//! Seeds are stored as ordinary secrets holding an `otpauth://totp/` URI, as encoded in the QR
+//! codes shown when enrolling an authenticator, e.g.
+//! `otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example`. The `algorithm`
+//! (`SHA1` or `SHA256`), `digits` (6 to 8) and `period` (in seconds) parameters default to
+//! `SHA1`, 6 and 30. A secret holding just the base32-encoded seed is also accepted.
+
+use crate::clock;
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::kdf::Digest;
+use crate::SecretsManager;
+use std::time::SystemTime;
+use zeroize::Zeroizing;
+
+const SCHEME: &str = "otpauth://totp/";
+
+/// The parameters of a TOTP seed.
+struct Totp {
+    key: Zeroizing<Vec<u8>>,
+    digest: Digest,
+    digits: u32,
+    period: u64,
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Validates the TOTP seed `uri` (see the module documentation) and adds it to the store
+    /// as the secret `name`, returning [`Error::InvalidTotp`] if it is malformed.
+    pub fn set_totp(&mut self, name: &str, uri: &str) -> Result<(), Error> {
+        parse(uri)?;
+        self.set(name, uri);
+        Ok(())
+    }
+
+    /// Generates the one-time password valid at `time` (usually [`SystemTime::now()`]) from the
+    /// TOTP seed stored as the secret `name`. Returns [`Error::InvalidTotp`] if the secret is
+    /// not a valid seed.
+    pub fn totp_code(&self, name: &str, time: SystemTime) -> Result<String, Error> {
+        let uri = Zeroizing::new(self.retrieve::<String>(name)?);
+        let totp = parse(&uri)?;
+
+        let counter = clock::seconds(time) / totp.period;
+        let hmac = Zeroizing::new(C::hmac(totp.digest, &totp.key, &counter.to_be_bytes()));
+
+        // Dynamic truncation, as in RFC 4226
+        let offset = usize::from(hmac[hmac.len() - 1] & 0xf);
+        let mut truncated = [0u8; 4];
+        truncated.copy_from_slice(&hmac[offset..offset + 4]);
+        let code = u32::from_be_bytes(truncated) & 0x7fff_ffff;
+        Ok(format!(
+            "{:0width$}",
+            code % 10u32.pow(totp.digits),
+            width = totp.digits as usize
+        ))
+    }
+}
+
+fn parse(uri: &str) -> Result<Totp, Error> {
+    let invalid = |reason: &str| Error::InvalidTotp(reason.to_string());
+    let mut totp = Totp {
+        key: Zeroizing::new(Vec::new()),
+        digest: Digest::Sha1,
+        digits: 6,
+        period: 30,
+    };
+
+    let uri = uri.trim();
+    let is_uri = uri.len() >= SCHEME.len() && uri[..SCHEME.len()].eq_ignore_ascii_case(SCHEME);
+    if !is_uri {
+        totp.key = base32(uri).ok_or_else(|| invalid("the seed is not valid base32"))?;
+        return Ok(totp);
+    }
+
+    let query = uri.split_once('?').map_or("", |(_, query)| query);
+    let mut secret = None;
+    for parameter in query.split('&') {
+        let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = Zeroizing::new(percent_decode(value).ok_or_else(|| invalid("bad escape"))?);
+        match key {
+            "secret" => secret = Some(base32(&value).ok_or_else(|| invalid("bad secret"))?),
+            "algorithm" => {
+                totp.digest = match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => Digest::Sha1,
+                    "SHA256" => Digest::Sha256,
+                    _ => return Err(invalid("unsupported algorithm")),
+                }
+            }
+            "digits" => match value.parse() {
+                Ok(digits @ 6..=8) => totp.digits = digits,
+                _ => return Err(invalid("digits must be between 6 and 8")),
+            },
+            "period" => match value.parse() {
+                Ok(period) if period > 0 => totp.period = period,
+                _ => return Err(invalid("period must be a positive number of seconds")),
+            },
+            // Labels such as the issuer don't affect the codes
+            _ => {}
+        }
+    }
+
+    totp.key = secret.ok_or_else(|| invalid("missing secret"))?;
+    Ok(totp)
+}
+
+/// Decodes RFC 4648 base32, ignoring case, spaces and padding. Returns `None` if `s` is empty or
+/// contains any other character.
+fn base32(s: &str) -> Option<Zeroizing<Vec<u8>>> {
+    let mut bytes = Zeroizing::new(Vec::with_capacity(s.len() * 5 / 8));
+    let mut buffer = 0u64;
+    let mut bits = 0;
+    for c in s.chars().filter(|c| !matches!(c, ' ' | '=')) {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u64 - 'A' as u64,
+            c @ '2'..='7' => c as u64 - '2' as u64 + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    match bytes.is_empty() {
+        true => None,
+        false => Some(bytes),
+    }
+}
+
+/// Decodes the `%XX` escapes in a URI query value.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Zeroizing::new(Vec::with_capacity(s.len()));
+    let mut input = s.bytes();
+    while let Some(b) = input.next() {
+        match b {
+            b'%' => {
+                let hex = [input.next()?, input.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes.to_vec()).ok()
+}
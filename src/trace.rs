@@ -0,0 +1,71 @@
+//! Instrumentation of vault operations with `tracing`, when the `tracing` feature is enabled.
+//!
+//! Spans are entered for loading, unlocking and saving vaults and for deriving keys, and an
+//! event is logged for each [`Event`](crate::Event). Neither the values of secrets nor any key
+//! material are ever recorded, and the names of secrets may be replaced with keyed hashes (see
+//! [`SecretsManager::set_hash_traced_names()`](crate::SecretsManager::set_hash_traced_names)).
+
+use crate::crypto::CryptoProvider;
+use crate::events::Event;
+use crate::SecretsManager;
+
+/// Enters a span named `$name`, with any fields following it, until the returned guard is
+/// dropped. Expands to a no-op guard without the `tracing` feature.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($($args:tt)*) => {
+        tracing::info_span!($($args)*).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($args:tt)*) => {
+        $crate::trace::NoSpan
+    };
+}
+
+pub(crate) use span;
+
+/// The guard returned by [`span!`] without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Records secret names in traces as keyed hashes (the first 8 bytes of an HMAC with the
+    /// vault's HMAC key, in hex) rather than as they are, if `hash` is `true`. The hashes of a
+    /// name are the same across runs for the same vault, so accesses to a secret can still be
+    /// correlated. Defaults to `false`.
+    #[cfg(feature = "tracing")]
+    pub fn set_hash_traced_names(&mut self, hash: bool) {
+        self.hash_traced_names = hash;
+    }
+
+    /// Logs `event` as a `tracing` event.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn trace(&self, event: &Event) {
+        let traced = |name: &str| match self.hash_traced_names {
+            true => {
+                let hash = C::hmac_sha1(&self.keys.hmac, &[b"securestore trace", name.as_bytes()]);
+                hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+            }
+            false => name.to_string(),
+        };
+        match event {
+            Event::SecretRead { name } => tracing::debug!(secret = %traced(name), "secret read"),
+            Event::SecretWritten { name } => {
+                tracing::debug!(secret = %traced(name), "secret written")
+            }
+            Event::SecretRemoved { name } => {
+                tracing::debug!(secret = %traced(name), "secret removed")
+            }
+            Event::VaultSaved => tracing::info!("vault saved"),
+            Event::UnlockFailed { error } => {
+                tracing::warn!(error = %error, "vault could not be unlocked")
+            }
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn trace(&self, _event: &Event) {}
+}
@@ -0,0 +1,122 @@
+//! Reloading of vaults when their file is changed by another process.
+
+use crate::crypto::CryptoProvider;
+use crate::errors::Error;
+use crate::shared::Vault;
+use crate::SecretsManager;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A [`SecretsManager`] which is reloaded whenever its vault file is changed on-disk, as
+/// returned by [`SecretsManager::watch()`]. The vault is no longer watched once this is
+/// dropped.
+pub struct WatchedVault<C: CryptoProvider> {
+    sman: Arc<RwLock<SecretsManager<C>>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl<C: CryptoProvider> WatchedVault<C> {
+    /// Locks the `SecretsManager` for reading secrets, blocking while it is being reloaded.
+    pub fn read(&self) -> RwLockReadGuard<'_, SecretsManager<C>> {
+        self.sman.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the `SecretsManager` for changing (and saving) secrets.
+    pub fn write(&self) -> RwLockWriteGuard<'_, SecretsManager<C>> {
+        self.sman.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<C: CryptoProvider + Send + Sync + 'static> SecretsManager<C> {
+    /// Watches the vault file this `SecretsManager` was loaded from (or created at), reloading
+    /// the vault whenever another process saves it so that long-running services pick up
+    /// updated or rotated secrets without restarting.
+    ///
+    /// Reloaded vaults are verified exactly as when they are first loaded, against the keys
+    /// already in memory. `callback` is invoked after each reload with the names of the secrets
+    /// that were added, changed or removed, or with the error that prevented the vault from
+    /// being reloaded, in which case the previously loaded vault remains in use. Changes that
+    /// have not been saved are lost when the vault is reloaded.
+    pub fn watch<F>(self, mut callback: F) -> Result<WatchedVault<C>, Error>
+    where
+        F: FnMut(Result<Vec<String>, Error>) + Send + 'static,
+    {
+        let storage = self.storage.as_ref().ok_or(Error::InMemoryVault)?;
+        let path = storage.file_path().map(Path::to_path_buf).ok_or_else(|| {
+            Error::StorageFailure("only vaults stored in files can be watched".to_string())
+        })?;
+
+        // The vault is replaced (rather than modified) when saved, so the directory must be
+        // watched rather than the file itself
+        let directory = match path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => Path::new(".").to_path_buf(),
+        };
+        let name = path.file_name().map(|n| n.to_os_string());
+
+        let sman = Arc::new(RwLock::new(self));
+        let watched = Arc::clone(&sman);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => return callback(Err(watch_failure(e))),
+                };
+                let relevant = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.iter().any(|p| p.file_name() == name.as_deref());
+                if !relevant {
+                    return;
+                }
+
+                let mut sman = watched.write().unwrap_or_else(|e| e.into_inner());
+                match sman.reload() {
+                    Ok(changed) if changed.is_empty() => {}
+                    result => callback(result),
+                }
+            })
+            .map_err(watch_failure)?;
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .map_err(watch_failure)?;
+
+        Ok(WatchedVault {
+            sman,
+            _watcher: watcher,
+        })
+    }
+}
+
+impl<C: CryptoProvider> SecretsManager<C> {
+    /// Reloads the vault from storage, verifying it with the keys in memory, and returns the
+    /// names of the secrets that differ from those previously loaded.
+    fn reload(&mut self) -> Result<Vec<String>, Error> {
+        let storage = self.storage.as_ref().ok_or(Error::InMemoryVault)?;
+        let mut vault = Vault::parse(&storage.load()?)?;
+        vault.verify_sentinel::<C>(&self.keys, self.allow_unauthenticated)?;
+        vault.verify_mac::<C>(&self.keys, self.allow_unauthenticated)?;
+        // Unauthenticated vaults are given a sentinel, so that they require a MAC once saved
+        if vault.sentinel.is_none() {
+            vault.seal_sentinel::<C>(&self.keys);
+        }
+
+        let old = &self.vault.secrets;
+        let new = &vault.secrets;
+        let mut changed: Vec<String> = new
+            .iter()
+            .filter(|(name, blob)| {
+                !matches!(old.get(*name), Some(o) if o.iv == blob.iv && o.payload == blob.payload)
+            })
+            .map(|(name, _)| name.clone())
+            .chain(old.keys().filter(|n| !new.contains_key(*n)).cloned())
+            .collect();
+        changed.sort();
+
+        self.vault = vault;
+        Ok(changed)
+    }
+}
+
+fn watch_failure(error: notify::Error) -> Error {
+    Error::StorageFailure(format!("watching the vault failed: {}", error))
+}
@@ -0,0 +1,265 @@
+//! Tests running the `securestore` command-line client against vaults on disk
+
+#![cfg(feature = "cli")]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A directory for the vaults and keyfiles of a single test, removed when dropped.
+struct Workspace {
+    dir: PathBuf,
+}
+
+impl Workspace {
+    fn new(name: &str) -> Self {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Workspace { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Runs the client with `args` in the workspace, with stdin closed so that nothing is
+    /// prompted for.
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_securestore"))
+            .current_dir(&self.dir)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .unwrap()
+    }
+
+    /// Runs the client with `args`, panicking if it fails, and returns its standard output.
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "securestore {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Verify that a vault created with a new keyfile can be unlocked with it to set and get secrets
+#[test]
+fn create_set_get() {
+    let ws = Workspace::new("create_set_get");
+    ws.ok(&["create", "--keyfile", "secrets.key"]);
+    assert!(ws.path("secrets.json").exists(), "No vault was created!");
+    assert!(ws.path("secrets.key").exists(), "No keyfile was created!");
+
+    ws.ok(&["set", "--keyfile", "secrets.key", "foo", "bar"]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "baz", "qux"]);
+    assert_eq!("bar\n", ws.ok(&["get", "--keyfile", "secrets.key", "foo"]));
+    assert_eq!("qux\n", ws.ok(&["get", "--keyfile", "secrets.key", "baz"]));
+
+    // a custom store path is honored too
+    ws.ok(&[
+        "create",
+        "--store",
+        "other.json",
+        "--keyfile",
+        "secrets.key",
+    ]);
+    ws.ok(&[
+        "-s",
+        "other.json",
+        "-k",
+        "secrets.key",
+        "set",
+        "foo",
+        "other",
+    ]);
+    assert_eq!(
+        "other\n",
+        ws.ok(&["-s", "other.json", "-k", "secrets.key", "get", "foo"])
+    );
+    assert_eq!("bar\n", ws.ok(&["-k", "secrets.key", "get", "foo"]));
+}
+
+/// Verify that creating a vault does not overwrite an existing one unless forced
+#[test]
+fn create_existing() {
+    let ws = Workspace::new("create_existing");
+    ws.ok(&["create", "--keyfile", "secrets.key"]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "foo", "bar"]);
+
+    let output = ws.run(&["create", "--keyfile", "secrets.key"]);
+    assert_eq!(Some(1), output.status.code());
+    assert_eq!("bar\n", ws.ok(&["get", "--keyfile", "secrets.key", "foo"]));
+
+    ws.ok(&["create", "--force", "--keyfile", "secrets.key"]);
+    assert_eq!("", ws.ok(&["list", "--keyfile", "secrets.key"]));
+}
+
+/// Verify that secrets are listed in sorted order and no longer listed once deleted
+#[test]
+fn list_delete() {
+    let ws = Workspace::new("list_delete");
+    ws.ok(&["create", "--keyfile", "secrets.key"]);
+    for name in &["foo", "bar", "baz"] {
+        ws.ok(&["set", "--keyfile", "secrets.key", name, "value"]);
+    }
+    assert_eq!(
+        "bar\nbaz\nfoo\n",
+        ws.ok(&["list", "--keyfile", "secrets.key"])
+    );
+
+    ws.ok(&["delete", "--keyfile", "secrets.key", "baz"]);
+    assert_eq!("bar\nfoo\n", ws.ok(&["list", "--keyfile", "secrets.key"]));
+
+    let output = ws.run(&["delete", "--keyfile", "secrets.key", "baz"]);
+    assert!(
+        !output.status.success(),
+        "Deleting a missing secret succeeded!"
+    );
+}
+
+/// Verify that exported keys unlock the vault in place of the keyfile they were exported from
+#[test]
+fn export_key() {
+    let ws = Workspace::new("export_key");
+    ws.ok(&[
+        "create",
+        "--keyfile",
+        "secrets.key",
+        "--export-key",
+        "created.key",
+    ]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "foo", "bar"]);
+    ws.ok(&["export-key", "--keyfile", "secrets.key", "exported.key"]);
+
+    let original = std::fs::read(ws.path("secrets.key")).unwrap();
+    assert_eq!(original, std::fs::read(ws.path("exported.key")).unwrap());
+    assert_eq!(original, std::fs::read(ws.path("created.key")).unwrap());
+    assert_eq!("bar\n", ws.ok(&["get", "--keyfile", "exported.key", "foo"]));
+
+    ws.ok(&[
+        "export-key",
+        "--keyfile",
+        "secrets.key",
+        "--armor",
+        "armored.key",
+    ]);
+    assert_eq!("bar\n", ws.ok(&["get", "--keyfile", "armored.key", "foo"]));
+}
+
+/// Verify that failures are distinguished by the exit code, with errors reported as JSON if
+/// requested
+#[test]
+fn exit_codes() {
+    let ws = Workspace::new("exit_codes");
+    ws.ok(&["create", "--keyfile", "secrets.key"]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "foo", "bar"]);
+
+    let output = ws.run(&["get", "--keyfile", "secrets.key", "missing"]);
+    assert_eq!(Some(3), output.status.code());
+
+    // keys generated for another vault fail to unlock this one
+    ws.ok(&["create", "--store", "other.json", "--keyfile", "other.key"]);
+    let output = ws.run(&["get", "--keyfile", "other.key", "foo"]);
+    assert_eq!(Some(4), output.status.code());
+
+    let output = ws.run(&["get", "--keyfile", "other.key", "--output", "json", "foo"]);
+    assert_eq!(Some(4), output.status.code());
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!("decryption_failed", error["error"]["kind"]);
+}
+
+/// Verify that `list`, `get`, `diff` and `verify` print valid JSON if requested
+#[test]
+fn json_output() {
+    let ws = Workspace::new("json_output");
+    let json = |args: &[&str]| -> serde_json::Value {
+        let args = [&["--keyfile", "secrets.key", "--output", "json"], args].concat();
+        serde_json::from_str(&ws.ok(&args)).unwrap()
+    };
+    ws.ok(&["create", "--keyfile", "secrets.key"]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "foo", "bar"]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "baz", "qux"]);
+
+    assert_eq!(serde_json::json!(["baz", "foo"]), json(&["list"]));
+    assert_eq!(
+        serde_json::json!({ "name": "foo", "value": "bar" }),
+        json(&["get", "foo"])
+    );
+    assert_eq!(
+        serde_json::json!({ "ok": true, "secrets": 2, "failures": [] }),
+        json(&["verify"])
+    );
+
+    ws.ok(&[
+        "create",
+        "--store",
+        "other.json",
+        "--keyfile",
+        "secrets.key",
+    ]);
+    ws.ok(&[
+        "-s",
+        "other.json",
+        "-k",
+        "secrets.key",
+        "set",
+        "foo",
+        "changed",
+    ]);
+    ws.ok(&[
+        "-s",
+        "other.json",
+        "-k",
+        "secrets.key",
+        "set",
+        "new",
+        "value",
+    ]);
+    assert_eq!(
+        serde_json::json!({ "added": ["new"], "removed": ["baz"], "changed": ["foo"] }),
+        json(&["diff", "other.json"])
+    );
+}
+
+/// Verify that secrets are exported to files only readable by their owner
+#[cfg(unix)]
+#[test]
+fn export_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let ws = Workspace::new("export_permissions");
+    ws.ok(&["create", "--keyfile", "secrets.key"]);
+    ws.ok(&["set", "--keyfile", "secrets.key", "foo", "bar"]);
+
+    for format in &["env", "json", "csv"] {
+        let path = ws.path(&format!("exported.{}", format));
+        // an existing file is replaced without keeping its permissions
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        ws.ok(&[
+            "export",
+            "--keyfile",
+            "secrets.key",
+            "--format",
+            format,
+            path.to_str().unwrap(),
+        ]);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(
+            0o600,
+            mode & 0o777,
+            "{} export is readable by others!",
+            format
+        );
+    }
+}